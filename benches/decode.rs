@@ -0,0 +1,89 @@
+//! Decoding throughput on `.text` sections the size of real firmware images
+//!
+//! Run with `cargo bench`, and compare against a saved baseline with
+//! `cargo bench -- --save-baseline main` then `cargo bench -- --baseline main`.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+// Criterion's own `black_box` reads its argument a byte at a time, which
+// costs more than decoding does
+use std::hint::black_box;
+
+use riscv_asm::dis::Disassembly;
+use riscv_asm::opcodes;
+use riscv_asm::prelude::decode_opcode;
+
+/// The size of each `.text` section, in bytes
+const TEXT_SIZE: usize = 4 << 20;
+
+/// Words that decode to a mix of every supported instruction, repeated
+fn instructions() -> Vec<u32> {
+    let corpus: Vec<u32> = include_str!("../testdata/objdump-corpus.txt")
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split('\t').next())
+        .map(|word| u32::from_str_radix(word.trim_start_matches("0x"), 16).unwrap())
+        .collect();
+
+    corpus.iter().cycle().take(TEXT_SIZE / 4).cloned().collect()
+}
+
+/// Words that mostly aren't instructions, like data mixed into `.text`
+fn random_words() -> Vec<u32> {
+    let mut word: u32 = 0x1234_5678;
+    (0..TEXT_SIZE / 4)
+        .map(|_| {
+            word ^= word << 13;
+            word ^= word >> 17;
+            word ^= word << 5;
+            word
+        })
+        .collect()
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode_opcode");
+    group.throughput(Throughput::Bytes(TEXT_SIZE as u64));
+
+    for (name, words) in [("instructions", instructions()), ("random", random_words())] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for &word in &words {
+                    black_box(decode_opcode(black_box(word)));
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn find(c: &mut Criterion) {
+    let words = instructions();
+
+    let mut group = c.benchmark_group("opcodes::find");
+    group.throughput(Throughput::Bytes(TEXT_SIZE as u64));
+    group.bench_function("instructions", |b| {
+        b.iter(|| {
+            for &word in &words {
+                black_box(opcodes::find(black_box(word)));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn disassemble(c: &mut Criterion) {
+    let words = instructions();
+
+    let mut group = c.benchmark_group("disassembly");
+    group.throughput(Throughput::Bytes(TEXT_SIZE as u64));
+    group.sample_size(10);
+    group.bench_function("from_words", |b| {
+        b.iter(|| Disassembly::from_words(0x8000_0000, black_box(&words)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, decode, find, disassemble);
+criterion_main!(benches);
@@ -0,0 +1,366 @@
+//! Generates the decoder's mask and match tables from the riscv-opcodes files
+//! in `riscv-opcodes/`, and the decoder itself
+//!
+//! See `src/opcodes.rs` for the types that the tables are made of, and
+//! `decode_opcode` in `src/decode.rs` for where the decoder is included.
+
+use std::collections::BTreeSet;
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// The files to read, in the order that their instructions are matched
+const FILES: &[&str] = &[
+    "rv_i",
+    "rv32_i",
+    "rv64_i",
+    "rv_zicsr",
+    "rv_zifencei",
+    "rv_system",
+    "rv_n",
+];
+
+/// Every argument field, with its variant of `opcodes::Field` and its bits
+const FIELDS: &[(&str, &str, u32, u32)] = &[
+    ("rd", "Rd", 11, 7),
+    ("rs1", "Rs1", 19, 15),
+    ("rs2", "Rs2", 24, 20),
+    ("imm20", "Imm20", 31, 12),
+    ("jimm20", "Jimm20", 31, 12),
+    ("imm12", "Imm12", 31, 20),
+    ("imm12hi", "Imm12hi", 31, 25),
+    ("imm12lo", "Imm12lo", 11, 7),
+    ("bimm12hi", "Bimm12hi", 31, 25),
+    ("bimm12lo", "Bimm12lo", 11, 7),
+    ("shamtw", "Shamtw", 24, 20),
+    ("shamt", "Shamt", 25, 20),
+    ("fm", "Fm", 31, 28),
+    ("pred", "Pred", 27, 24),
+    ("succ", "Succ", 23, 20),
+    ("zimm", "Zimm", 19, 15),
+    ("csr", "Csr", 31, 20),
+];
+
+/// The bits from `hi` down to `lo`, inclusive
+fn range_mask(hi: u32, lo: u32) -> u32 {
+    (u32::MAX >> (31 - hi)) & (u32::MAX << lo)
+}
+
+fn parse_value(s: &str) -> u32 {
+    match s.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .unwrap_or_else(|err| panic!("bad value {:?}: {}", s, err))
+}
+
+struct Encoding {
+    name: String,
+    extension: &'static str,
+    mask: u32,
+    match_bits: u32,
+    fields: Vec<&'static str>,
+}
+
+/// Parse a line like `addi rd rs1 imm12 14..12=0 6..2=0x04 1..0=3`
+fn parse_line(extension: &'static str, line: &str) -> Encoding {
+    let mut tokens = line.split_whitespace();
+    let name = tokens.next().unwrap().to_string();
+
+    let mut encoding = Encoding {
+        name,
+        extension,
+        mask: 0,
+        match_bits: 0,
+        fields: vec![],
+    };
+    for token in tokens {
+        match token.split_once('=') {
+            Some((range, value)) => {
+                let (hi, lo) = match range.split_once("..") {
+                    Some((hi, lo)) => (parse_value(hi), parse_value(lo)),
+                    None => (parse_value(range), parse_value(range)),
+                };
+                let value = parse_value(value);
+                assert!(
+                    value <= range_mask(hi - lo, 0),
+                    "{}: {} doesn't fit",
+                    encoding.name,
+                    token
+                );
+                encoding.mask |= range_mask(hi, lo);
+                encoding.match_bits |= value << lo;
+            }
+            None => {
+                let field = FIELDS
+                    .iter()
+                    .find(|field| field.0 == token)
+                    .unwrap_or_else(|| panic!("{}: unknown field {}", encoding.name, token));
+                encoding.fields.push(field.0);
+            }
+        }
+    }
+
+    // Every bit must be either fixed or part of exactly one field
+    let mut covered = encoding.mask;
+    for &name in &encoding.fields {
+        let &(_, _, hi, lo) = FIELDS.iter().find(|field| field.0 == name).unwrap();
+        let bits = range_mask(hi, lo);
+        assert!(
+            covered & bits == 0,
+            "{}: {} overlaps another field",
+            encoding.name,
+            name
+        );
+        covered |= bits;
+    }
+    assert!(
+        covered == u32::MAX,
+        "{}: bits {:#010x} aren't specified",
+        encoding.name,
+        !covered
+    );
+
+    encoding
+}
+
+/// The `opcodes::Mnemonic` variant for a name like `fence.i`
+fn variant(name: &str) -> String {
+    name.split(['.', '_'])
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            first.to_string() + chars.as_str()
+        })
+        .collect()
+}
+
+/// The index into the dispatch table of a word with these fixed bits, which
+/// is its funct3 followed by its opcode
+fn dispatch_index(bits: u32) -> usize {
+    (((bits >> 5) & 0x380) | (bits & 0x7f)) as usize
+}
+
+fn main() {
+    let dir = Path::new("riscv-opcodes");
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let mut encodings: Vec<Encoding> = vec![];
+    let mut pseudo_ops: Vec<(String, Encoding)> = vec![];
+    for &file in FILES {
+        let path = dir.join(file);
+        println!("cargo:rerun-if-changed={}", path.display());
+        let text =
+            fs::read_to_string(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() || line.starts_with("$import") {
+                continue;
+            }
+            if let Some(rest) = line.strip_prefix("$pseudo_op") {
+                let (original, rest) = rest.trim().split_once(' ').unwrap();
+                pseudo_ops.push((original.to_string(), parse_line(file, rest.trim())));
+            } else {
+                encodings.push(parse_line(file, line));
+            }
+        }
+    }
+
+    // Pseudo-ops are other encodings of an instruction in another file, and
+    // are only needed when that instruction isn't there
+    let defined: BTreeSet<String> = encodings
+        .iter()
+        .map(|encoding| format!("{}::{}", encoding.extension, encoding.name))
+        .collect();
+    for (original, encoding) in pseudo_ops {
+        if !defined.contains(&original) {
+            encodings.push(encoding);
+        }
+    }
+
+    for (i, a) in encodings.iter().enumerate() {
+        for b in &encodings[i + 1..] {
+            let shared = a.mask & b.mask;
+            assert!(
+                (a.match_bits ^ b.match_bits) & shared != 0,
+                "{} and {} have overlapping encodings",
+                a.name,
+                b.name
+            );
+        }
+    }
+
+    let mut out = String::new();
+    writeln!(out, "/// The instructions in `riscv-opcodes/`").unwrap();
+    writeln!(out, "#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]").unwrap();
+    writeln!(out, "pub enum Mnemonic {{").unwrap();
+    for encoding in &encodings {
+        writeln!(out, "    /// `{}`", encoding.name).unwrap();
+        writeln!(out, "    {},", variant(&encoding.name)).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(
+        out,
+        "/// Every encoding in `riscv-opcodes/`, in the order they're matched"
+    )
+    .unwrap();
+    writeln!(out, "pub static ENCODINGS: &[Encoding] = &ALL;").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "static ALL: [Encoding; {}] = [", encodings.len()).unwrap();
+    for encoding in &encodings {
+        let fields: Vec<String> = encoding
+            .fields
+            .iter()
+            .map(|&name| {
+                let &(_, variant, _, _) = FIELDS.iter().find(|field| field.0 == name).unwrap();
+                format!("Field::{}", variant)
+            })
+            .collect();
+        writeln!(
+            out,
+            "    Encoding {{ name: {:?}, mnemonic: Mnemonic::{}, extension: {:?}, mask: {:#010x}, match_bits: {:#010x}, fields: &[{}] }},",
+            encoding.name,
+            variant(&encoding.name),
+            encoding.extension,
+            encoding.mask,
+            encoding.match_bits,
+            fields.join(", ")
+        )
+        .unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    writeln!(out).unwrap();
+
+    // Every encoding fixes its opcode, and most fix funct3, so only a few
+    // share each combination of the two
+    let mut dispatch = vec![vec![]; 1024];
+    for (i, encoding) in encodings.iter().enumerate() {
+        assert!(encoding.mask & 0x7f == 0x7f, "{}: no opcode", encoding.name);
+        for (index, bucket) in dispatch.iter_mut().enumerate() {
+            let bits = dispatch_index(encoding.match_bits);
+            if index & dispatch_index(encoding.mask) == bits {
+                bucket.push(i);
+            }
+        }
+    }
+    writeln!(
+        out,
+        "/// The encodings that a word may match, by its funct3 and opcode"
+    )
+    .unwrap();
+    writeln!(out, "static DISPATCH: [&[&Encoding]; 1024] = [").unwrap();
+    for bucket in &dispatch {
+        let indices: Vec<String> = bucket.iter().map(|i| format!("&ALL[{}]", i)).collect();
+        writeln!(out, "    &[{}],", indices.join(", ")).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("opcodes.rs"), out).unwrap();
+    fs::write(
+        Path::new(&out_dir).join("decode.rs"),
+        decoder(&encodings, &dispatch),
+    )
+    .unwrap();
+}
+
+/// The bits of funct7
+const FUNCT7: u32 = 0xfe00_0000;
+
+/// The body of `decode::dispatch`, which decodes a word with one `match` on
+/// its funct3 and opcode, and another on funct7 where that's what tells the
+/// encodings apart
+///
+/// Each leaf builds its `Instr` with `decode::instr` and a constant
+/// `Mnemonic`, which folds down to extracting just that instruction's fields.
+fn decoder(encodings: &[Encoding], dispatch: &[Vec<usize>]) -> String {
+    // The same leaf is often under several indices, like `lui` under every
+    // funct3, so those share an arm
+    let mut arms: Vec<(Vec<usize>, String)> = vec![];
+    for (index, bucket) in dispatch.iter().enumerate() {
+        if bucket.is_empty() {
+            continue;
+        }
+        let bucket: Vec<&Encoding> = bucket.iter().map(|&i| &encodings[i]).collect();
+        let fixed = (0x7 << 12) | 0x7f;
+        let body = if bucket.len() > 1 && bucket.iter().all(|e| e.mask & FUNCT7 == FUNCT7) {
+            let mut funct7: Vec<u32> = bucket.iter().map(|e| e.match_bits >> 25).collect();
+            funct7.sort_unstable();
+            funct7.dedup();
+            let mut body = String::from("match word >> 25 {\n");
+            for value in funct7 {
+                let group: Vec<&Encoding> = bucket
+                    .iter()
+                    .filter(|e| e.match_bits >> 25 == value)
+                    .copied()
+                    .collect();
+                writeln!(body, "{:#04x} => {},", value, leaf(&group, fixed | FUNCT7)).unwrap();
+            }
+            body + "_ => None,\n}"
+        } else {
+            leaf(&bucket, fixed)
+        };
+        match arms.iter_mut().find(|arm| arm.1 == body) {
+            Some(arm) => arm.0.push(index),
+            None => arms.push((vec![index], body)),
+        }
+    }
+
+    // Leaf 0 is for words that no encoding matches. A dense match on the leaf
+    // compiles to a jump table, where a sparse one on the index doesn't.
+    assert!(arms.len() < 256, "too many leaves to index with a u8");
+    let mut leaves = [0; 1024];
+    for (leaf, (indices, _)) in arms.iter().enumerate() {
+        for &index in indices {
+            leaves[index] = leaf + 1;
+        }
+    }
+
+    let mut out = String::new();
+    writeln!(out, "{{").unwrap();
+    writeln!(out, "static LEAVES: [u8; 1024] = {:?};", leaves).unwrap();
+    writeln!(
+        out,
+        "match LEAVES[(((word >> 5) & 0x380) | (word & 0x7f)) as usize] {{"
+    )
+    .unwrap();
+    for (leaf, (_, body)) in arms.iter().enumerate() {
+        writeln!(out, "{} => {},", leaf + 1, body).unwrap();
+    }
+    writeln!(out, "_ => unmatched(word),").unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+/// Decode one of `encodings`, checking whichever of their fixed bits aren't
+/// in `known`, in the order they're matched
+fn leaf(encodings: &[&Encoding], known: u32) -> String {
+    let mut out = String::new();
+    for encoding in encodings {
+        let build = format!("Some(instr(Mnemonic::{}, word))", variant(&encoding.name));
+        let mask = encoding.mask & !known;
+        if mask == 0 {
+            // Nothing after this can match, since encodings don't overlap
+            return if out.is_empty() {
+                build
+            } else {
+                format!("{}{{ {} }}", out, build)
+            };
+        }
+        write!(
+            out,
+            "if word & {:#010x} == {:#010x} {{ {} }} else ",
+            mask,
+            encoding.match_bits & mask,
+            build
+        )
+        .unwrap();
+    }
+    out + "{ None }"
+}
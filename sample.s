@@ -0,0 +1,32 @@
+
+/tmp/asm/sample.o:	file format ELF32-riscv
+
+
+Disassembly of section .text:
+
+00000000 _start:
+       0: 13 05 a0 00                  	addi	a0, zero, 10
+       4: 97 00 00 00                  	auipc	ra, 0
+       8: e7 80 00 00                  	jalr	0(ra)
+       c: 6f 00 00 00                  	jal	zero, 0
+
+00000010 count:
+      10: 13 01 01 ff                  	addi	sp, sp, -16
+      14: 93 05 00 00                  	addi	a1, zero, 0
+
+00000018 loop:
+; XREF from 0x0000001c (branch)
+      18: 93 85 15 00                  	addi	a1, a1, 1
+      1c: e3 ce a5 fe                  	blt	a1, a0, -4
+      20: 63 04 05 00                  	beq	a0, zero, 8
+      24: 73 26 20 34                  	csrrs	a2, mcause, zero
+
+00000028 done:
+; XREF from 0x00000020 (branch)
+      28: 13 01 01 01                  	addi	sp, sp, 16
+      2c: 67 80 00 00                  	jalr	zero, 0(ra)
+
+Disassembly of section .text.user:
+
+00000030 user:
+      30: 73 00 00 00                  	ecall
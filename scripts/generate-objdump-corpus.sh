@@ -0,0 +1,179 @@
+#!/bin/bash
+
+# Regenerates testdata/objdump-corpus.txt, which the listing tests compare
+# `dis` against.
+#
+# Needs llvm-mc and llvm-objdump. The corpus was made with LLVM 14.
+#
+# Every instruction that `dis` decodes is assembled with a spread of registers
+# and immediates, followed by every CSR that LLVM has a name for, and words
+# that neither should decode.
+
+set -e
+
+out=${1:-testdata/objdump-corpus.txt}
+tmp=$(mktemp -d)
+trap 'rm -rf "$tmp"' EXIT
+
+regs=(zero ra sp gp tp t0 t1 t2 s0 s1 a0 a1 a2 a3 a4 a5 a6 a7 s2 s3 s4 s5 s6 s7 s8 s9 s10 s11 t3 t4 t5 t6)
+
+# Registers that walk through all 32 at different rates
+rd()  { echo "${regs[$1 % 32]}"; }
+rs1() { echo "${regs[($1 * 7 + 3) % 32]}"; }
+rs2() { echo "${regs[($1 * 13 + 5) % 32]}"; }
+
+imm12=(-2048 -2047 -256 -1 0 1 2 255 1024 2047)
+
+rv32() {
+    for op in add sub sll slt sltu xor srl sra or and; do
+        for i in $(seq 0 31); do
+            echo "$op $(rd $i), $(rs1 $i), $(rs2 $i)"
+        done
+    done
+
+    for op in addi slti sltiu xori ori andi; do
+        for i in "${!imm12[@]}"; do
+            echo "$op $(rd $i), $(rs1 $i), ${imm12[$i]}"
+        done
+    done
+
+    for op in slli srli srai; do
+        for shamt in 0 1 7 16 31; do
+            echo "$op $(rd $shamt), $(rs1 $shamt), $shamt"
+        done
+    done
+
+    for op in lb lh lw lbu lhu jalr; do
+        for i in "${!imm12[@]}"; do
+            echo "$op $(rd $i), ${imm12[$i]}($(rs1 $i))"
+        done
+    done
+
+    for op in sb sh sw; do
+        for i in "${!imm12[@]}"; do
+            echo "$op $(rs2 $i), ${imm12[$i]}($(rs1 $i))"
+        done
+    done
+
+    # Branch and jump offsets are relative, so these are written as such
+    for op in beq bne blt bge bltu bgeu; do
+        for offset in -4096 -2048 -64 -2 0 2 12 2046 4094; do
+            echo "$op $(rs1 $offset), $(rs2 $offset), $offset"
+        done
+    done
+    for offset in -1048576 -4096 -96 -2 0 2 76 4096 1048574; do
+        echo "jal $(rd $offset), $offset"
+    done
+
+    for op in lui auipc; do
+        for imm in 0 1 4 0x7ffff 0x80000 0xdeadb 0xfffff; do
+            echo "$op $(rd $imm), $imm"
+        done
+    done
+
+    for sets in "rw, rw" "iorw, iorw" "r, w" "w, r" "i, o" "o, i" "io, rw"; do
+        echo "fence $sets"
+    done
+    echo "fence.tso"
+    echo "fence.i"
+
+    echo "ecall"
+    echo "ebreak"
+    echo "uret"
+    echo "sret"
+    echo "mret"
+    echo "wfi"
+
+    for op in csrrw csrrs csrrc; do
+        for i in 0 1 10 31; do
+            echo "$op $(rd $i), mscratch, $(rs1 $i)"
+        done
+    done
+    for op in csrrwi csrrsi csrrci; do
+        for zimm in 0 1 8 31; do
+            echo "$op $(rd $zimm), mstatus, $zimm"
+        done
+    done
+
+    # Every CSR, where only those that LLVM names are kept
+    for csr in $(seq 0 4095); do
+        echo "csrrs a0, $csr, zero"
+    done
+}
+
+rv64() {
+    for op in ld lwu; do
+        for i in "${!imm12[@]}"; do
+            echo "$op $(rd $i), ${imm12[$i]}($(rs1 $i))"
+        done
+    done
+    for i in "${!imm12[@]}"; do
+        echo "sd $(rs2 $i), ${imm12[$i]}($(rs1 $i))"
+    done
+}
+
+# Words that aren't instructions to either
+unknown() {
+    echo ".word 0xffffffff" # Too long for a 32-bit instruction
+    echo ".word 0x02b50533" # mul, without the M extension
+    echo ".word 0x00000573" # ecall, with a reserved rd
+    echo ".word 0x0000001b" # addiw, which is RV64 only
+    echo ".word 0x0000007f" # An 80-bit instruction
+}
+
+# Print "word<tab>text" for each instruction in an object, with branch and
+# jump targets made relative, as if each instruction were at address 0
+listing() {
+    llvm-objdump -d -M no-aliases "$1" | awk -F'\t' '
+        function hex(s,    n, i) {
+            sub(/^0x/, "", s)
+            n = 0
+            for (i = 1; i <= length(s); i++) {
+                n = n * 16 + index("0123456789abcdef", substr(s, i, 1)) - 1
+            }
+            return n
+        }
+
+        /^ +[0-9a-f]+:/ {
+            split($1, bytes, " ")
+            addr = hex(substr(bytes[1], 1, length(bytes[1]) - 1))
+            word = bytes[5] bytes[4] bytes[3] bytes[2]
+
+            text = $2
+            for (i = 3; i <= NF; i++) text = text "\t" $i
+            sub(/[ \t]+$/, "", text)
+
+            # Absolute targets look like "0x54 <.text+0x54>"
+            if (match(text, /0x[0-9a-f]+ <[^>]*>$/)) {
+                target = hex(substr(text, RSTART, index(substr(text, RSTART), " ") - 1))
+                offset = (target - addr) % 4294967296
+                if (offset < 0) offset += 4294967296
+                text = substr(text, 1, RSTART - 1) sprintf("0x%x", offset)
+            }
+
+            # CSRs without a name are shown as a number, which is skipped
+            if (text ~ /^csrrs\ta0, [0-9]+, zero$/) next
+
+            printf "0x%s\t%s\n", word, text
+        }'
+}
+
+rv32 > "$tmp/rv32.s"
+rv64 > "$tmp/rv64.s"
+unknown > "$tmp/unknown.s"
+
+llvm-mc --triple=riscv32 -filetype=obj "$tmp/rv32.s" -o "$tmp/rv32.o"
+llvm-mc --triple=riscv64 -filetype=obj "$tmp/rv64.s" -o "$tmp/rv64.o"
+llvm-mc --triple=riscv32 -filetype=obj "$tmp/unknown.s" -o "$tmp/unknown.o"
+
+{
+    echo "# Instructions and how llvm-objdump -M no-aliases shows them"
+    echo "#"
+    echo "# Generated by scripts/generate-objdump-corpus.sh. Each line is a word, a tab,"
+    echo "# and its text. Branch and jump targets are as if each word were at address 0."
+    listing "$tmp/rv32.o"
+    listing "$tmp/rv64.o"
+    listing "$tmp/unknown.o"
+} > "$out"
+
+echo "Wrote $(grep -cv '^#' "$out") instructions to $out"
@@ -3,11 +3,17 @@ use std::io::Write;
 
 use clap::Clap;
 
+use riscv_asm::csr::{self, Csr};
+use riscv_asm::custom;
+use riscv_asm::dis::Disassembly;
+use riscv_asm::listing::Annotation;
+
 #[derive(Debug, Clap)]
 #[clap(version)]
 struct DisOpts {
     /// Path to a RISC-V elf to disassemble
-    input: String,
+    #[clap(required_unless_present = "explain-csr")]
+    input: Option<String>,
 
     /// Path to write disassembled output into
     ///
@@ -15,6 +21,77 @@ struct DisOpts {
     /// If "-" is specified, the output is directed to stdout.
     #[clap(short, long)]
     output: Option<String>,
+
+    /// Report the worst-case stack depth instead of disassembling
+    ///
+    /// Frame sizes are listed for every function, followed by the deepest
+    /// the stack can grow from the entry point and each trap handler.
+    /// Output is directed to stdout unless `--output` is specified.
+    #[clap(long)]
+    stack_usage: bool,
+
+    /// Report instructions that can't work as written instead of disassembling
+    ///
+    /// CSR accesses are checked against the privilege mode that they run in.
+    /// Code in sections with "user" in their name (like ".text.user") runs in
+    /// user mode, and everything else runs in machine mode.
+    /// Output is directed to stdout unless `--output` is specified.
+    #[clap(long, conflicts_with = "stack-usage")]
+    lint: bool,
+
+    /// Only disassemble the function with this name
+    #[clap(long, conflicts_with = "address")]
+    function: Option<String>,
+
+    /// Only disassemble the function containing this address
+    ///
+    /// Addresses may be given in decimal or in hex with a leading "0x".
+    #[clap(long, parse(try_from_str = parse_addr))]
+    address: Option<u32>,
+
+    /// Name custom CSRs with the definitions in this TOML or JSON file
+    ///
+    /// Each CSR is an entry in a "csr" list, with a "name", "number", and
+    /// optionally a "privilege" (like "mrw") and "fields" to explain. Files
+    /// ending in ".json" are read as JSON.
+    #[clap(long)]
+    csr_defs: Option<String>,
+
+    /// Decode custom-0 and custom-1 instructions with the specs in this TOML or
+    /// JSON file
+    ///
+    /// Each instruction is an entry in an "instr" list, with a "name", an
+    /// "opcode" ("custom-0" or "custom-1"), and optionally the "funct3" and
+    /// "funct7" to match and the "operands" to show (like ["rd", "rs1"]).
+    /// Files ending in ".json" are read as JSON.
+    #[clap(long)]
+    custom_instrs: Option<String>,
+
+    /// Explain the fields of a CSR's value, like "mcause=0x8000000b"
+    ///
+    /// This can be given more than once, and no input is needed.
+    /// Explanations are written to stdout.
+    #[clap(long, number_of_values = 1)]
+    explain_csr: Vec<String>,
+}
+
+/// Parse an address in either decimal or hex (with a leading "0x")
+fn parse_addr(s: &str) -> Result<u32, std::num::ParseIntError> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u32::from_str_radix(hex, 16)
+    } else {
+        s.parse()
+    }
+}
+
+/// Parse a CSR's name and a value for it, like "mcause=0x8000000b"
+fn parse_csr_value(s: &str) -> Result<(Csr, u32), String> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected CSR=VALUE, got {:?}", s))?;
+    let csr = csr::by_name(name).ok_or_else(|| format!("unknown CSR {:?}", name))?;
+    let value = parse_addr(value).map_err(|err| format!("{}: {}", value, err))?;
+    Ok((csr, value))
 }
 
 impl DisOpts {
@@ -32,10 +109,15 @@ impl DisOpts {
     fn resolve_extras(&mut self) {
         use std::path::Path;
 
+        // Reports aren't disassembly, so don't name them like it.
+        if self.output.is_none() && (self.stack_usage || self.lint) {
+            self.output = Some("-".to_string());
+        }
+
         // This path may optionally be specified directly.
         // When it's not, we need use the input file to derive an output.
-        if self.output.is_none() {
-            let input_path: &Path = &Path::new(&self.input);
+        if let (None, Some(input)) = (&self.output, &self.input) {
+            let input_path: &Path = Path::new(input);
             let file_stem: &str = input_path
                 .file_stem()
                 .expect("Failed to find file stem of input file")
@@ -49,7 +131,22 @@ impl DisOpts {
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let opts = DisOpts::new();
-    let dis = riscv_asm::dis::Disassembly::parse_from_elf_path(&opts.input)?;
+
+    // Custom CSRs need names before anything refers to them
+    if let Some(path) = &opts.csr_defs {
+        csr::register_from_path(path)?;
+    }
+    if let Some(path) = &opts.custom_instrs {
+        custom::register_from_path(path)?;
+    }
+
+    if !opts.explain_csr.is_empty() {
+        return write_csr_explanations(&opts.explain_csr);
+    }
+
+    // Clap requires an input for anything other than explaining CSRs
+    let input = opts.input.as_deref().unwrap();
+    let dis = Disassembly::parse_from_elf_path(input)?;
 
     // Emulate LLVM disassembly output and write to file.
 
@@ -60,7 +157,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut file: File;
     let mut stdout: std::io::Stdout;
 
-    match opts.output.unwrap().as_str() {
+    match opts.output.as_deref().unwrap() {
         "-" => {
             stdout = std::io::stdout();
             out = &mut stdout;
@@ -71,55 +168,135 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
-    write!(out, "\n{}:\tfile format ELF32-riscv\n\n\n", &opts.input)?;
-    writeln!(out, "Disassembly of section .text:")?;
+    if opts.stack_usage {
+        return write_stack_usage(out, &dis);
+    }
 
-    for entry in dis.disassembly() {
-        if !entry.labels.is_empty() {
-            // Add a blank line to separate sections with a label
-            writeln!(out)?;
+    if opts.lint {
+        for lint in riscv_asm::lint::lint(&dis) {
+            writeln!(out, "warning: {}", lint)?;
+        }
+        return Ok(());
+    }
 
-            for label in &entry.labels {
-                // Print the entire address, zero-padding included
-                writeln!(out, "{addr:08x} {label}:", addr = entry.addr, label = label)?;
-            }
+    // When asked for a single function, only that function is listed
+    let range = if let Some(name) = &opts.function {
+        match dis.function_named(name) {
+            Some(func) => Some(func),
+            None => return Err(format!("No function named \"{}\"", name).into()),
         }
+    } else if let Some(addr) = opts.address {
+        match dis.function_containing(addr) {
+            Some(func) => Some(func),
+            None => return Err(format!("No function contains address {:#010x}", addr).into()),
+        }
+    } else {
+        None
+    };
 
-        // Address of instruction
-        write!(out, "{:8x}: ", entry.addr)?;
+    write!(out, "\n{}:\tfile format ELF32-riscv\n\n", input)?;
 
-        // Raw bytes of instruction
-        for byte in entry.bytes.iter() {
-            write!(out, "{:02x} ", byte)?;
-        }
+    dis.write_listing(
+        out,
+        |entry| match &range {
+            Some(func) => func.contains(entry.addr),
+            None => true,
+        },
+        |_entry| Annotation::default(),
+    )?;
 
-        // Spacing
-        write!(out, "{:17}\t", "")?;
+    Ok(())
+}
 
-        // Instruction as text
-        if let Some(instr) = entry.o_instr {
-            // First, print the instruction name
-            write!(out, "{}", instr.name())?;
+fn write_csr_explanations(values: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
 
-            // Then, follow with any args, comma separated.
-            let args = instr.args();
+    for value in values {
+        let (csr, value) = parse_csr_value(value)?;
+        let name = csr::name_or_number(csr.num());
+        match csr::explain(csr, value) {
+            Some(explanation) => writeln!(out, "{} = {:#010x}: {}", name, value, explanation)?,
+            None => writeln!(out, "{} = {:#010x}: no fields to explain", name, value)?,
+        }
+    }
 
-            let mut iter = args.iter();
-            if let Some(arg) = iter.next() {
-                // No comma preceeding the first item
-                write!(out, "\t{}", arg)?;
+    Ok(())
+}
 
-                for arg in iter {
-                    write!(out, ", {}", arg)?;
-                }
-            }
-        } else {
-            write!(out, "???")?;
+fn write_stack_usage(
+    out: &mut dyn Write,
+    dis: &Disassembly,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use riscv_asm::stack::{StackAnalysis, StackDepth, Unbounded};
+
+    let stack = StackAnalysis::new(dis);
+
+    // Functions are named by their label, or by their address when they have none
+    let name = |addr: u32| -> String {
+        match stack
+            .graph()
+            .function(addr)
+            .and_then(|func| func.name.clone())
+        {
+            Some(name) => name,
+            None => format!("{:#010x}", addr),
         }
+    };
 
-        // Always end the entry with a newline
+    writeln!(out, "Frame sizes:")?;
+    for func in stack.graph().functions() {
+        let frame = stack.frame(func.addr).unwrap();
+        write!(
+            out,
+            "{:08x} {:>8} {}",
+            func.addr,
+            frame.size,
+            name(func.addr)
+        )?;
+        if let Some(site) = frame.dynamic {
+            write!(out, " (+ dynamic at {:#010x})", site)?;
+        }
         writeln!(out)?;
     }
 
+    writeln!(out)?;
+    writeln!(out, "Worst-case stack depth:")?;
+    for (root, depth) in stack.roots() {
+        write!(out, "{:08x} {}: ", root, name(root))?;
+
+        match depth {
+            StackDepth::Bounded { bytes, path } => {
+                let path: Vec<String> = path.iter().map(|&addr| name(addr)).collect();
+                writeln!(out, "{} bytes ({})", bytes, path.join(" -> "))?;
+            }
+            StackDepth::Unbounded(Unbounded::Recursion { cycle }) => {
+                let cycle: Vec<String> = cycle.iter().map(|&addr| name(addr)).collect();
+                writeln!(out, "unbounded, recursion through {}", cycle.join(" -> "))?;
+            }
+            StackDepth::Unbounded(Unbounded::IndirectCall { site }) => {
+                writeln!(out, "unbounded, indirect call at {:#010x}", site)?;
+            }
+            StackDepth::Unbounded(Unbounded::UnknownCallee { site, target }) => {
+                writeln!(
+                    out,
+                    "unbounded, call at {:#010x} to unknown code at {:#010x}",
+                    site, target
+                )?;
+            }
+            StackDepth::Unbounded(Unbounded::DynamicFrame { func, site }) => {
+                writeln!(
+                    out,
+                    "unbounded, {} adjusts sp dynamically at {:#010x}",
+                    name(*func),
+                    site
+                )?;
+            }
+            StackDepth::Unbounded(Unbounded::Overflow { func }) => {
+                writeln!(out, "unbounded, frames from {} overflow", name(*func))?;
+            }
+        }
+    }
+
     Ok(())
 }
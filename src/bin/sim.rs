@@ -0,0 +1,227 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::net::TcpListener;
+
+use clap::Clap;
+
+use riscv_asm::csr;
+use riscv_asm::dis::Disassembly;
+use riscv_asm::emu::gdb::{GdbServer, Stdio};
+use riscv_asm::emu::{
+    Clint, Event, Hart, Host, Image, InstrClass, Latencies, MemoryMap, Plic, Profile, Ram, Timing,
+    Uart, CLINT_SIZE, PLIC_SIZE, UART_SIZE,
+};
+use riscv_asm::prelude::*;
+
+/// Where devices are mapped, following QEMU's `virt` machine
+const CLINT_BASE: u32 = 0x0200_0000;
+const PLIC_BASE: u32 = 0x0c00_0000;
+const UART_BASE: u32 = 0x1000_0000;
+
+/// The PLIC source that the UART's interrupt is wired to
+const UART_IRQ: u32 = 10;
+
+#[derive(Debug, Clap)]
+#[clap(version)]
+struct SimOpts {
+    /// Path to a RISC-V elf to run
+    ///
+    /// The program's system calls and semihosting requests are serviced on
+    /// the host, and the simulator exits with the program's status.
+    input: String,
+
+    /// Address of the start of RAM
+    ///
+    /// Addresses may be given in decimal or in hex with a leading "0x".
+    #[clap(long, default_value = "0x80000000", parse(try_from_str = parse_addr))]
+    ram_base: u32,
+
+    /// Size of RAM in bytes
+    #[clap(long, default_value = "0x8000000", parse(try_from_str = parse_addr))]
+    ram_size: u32,
+
+    /// Give up after executing this many instructions
+    #[clap(long, conflicts_with = "gdb")]
+    max_steps: Option<u64>,
+
+    /// Wait for GDB to connect instead of running straight away
+    ///
+    /// Either an address to listen on, like "localhost:1234", or "-" to talk
+    /// over stdin and stdout (for GDB's `target remote | sim --gdb - ...`).
+    #[clap(long)]
+    gdb: Option<String>,
+
+    /// Write a listing of the program with how often each instruction ran
+    ///
+    /// The listing is in the same format as `dis`, with hit counts in the
+    /// margin and how often each branch was taken. It ends with the number of
+    /// instructions that ran in each function.
+    #[clap(long, conflicts_with = "gdb")]
+    listing: Option<String>,
+
+    /// Write an lcov tracefile for coverage of the listing
+    #[clap(long, requires = "listing")]
+    lcov: Option<String>,
+
+    /// Implement custom CSRs with the definitions in this TOML or JSON file
+    ///
+    /// The CSRs hold whatever is written to them, and GDB sees them by name.
+    /// The format is the same as for `dis --csr-defs`.
+    #[clap(long)]
+    csr_defs: Option<String>,
+
+    /// Estimate cycles for a five-stage in-order pipeline, and report them
+    /// when the program exits
+    ///
+    /// Without this, every instruction counts as a single cycle.
+    #[clap(long)]
+    timing: bool,
+
+    /// Override the cycles taken by a class of instructions, like "load=2"
+    ///
+    /// The classes are alu, load, store, branch-taken, branch-not-taken,
+    /// jump, mul, div, csr, and system. The penalties load-use (for reading a
+    /// register straight after loading it) and trap (for entering a trap
+    /// handler) can be set in the same way.
+    #[clap(long, requires = "timing", number_of_values = 1, parse(try_from_str = parse_latency))]
+    latency: Vec<(String, u32)>,
+}
+
+/// Parse an address in either decimal or hex (with a leading "0x")
+fn parse_addr(s: &str) -> std::result::Result<u32, std::num::ParseIntError> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u32::from_str_radix(hex, 16)
+    } else {
+        s.parse()
+    }
+}
+
+/// Parse a latency override, like "load=2"
+fn parse_latency(s: &str) -> std::result::Result<(String, u32), String> {
+    let (name, cycles) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected CLASS=CYCLES, got {:?}", s))?;
+    let cycles = cycles
+        .parse()
+        .map_err(|err| format!("{}: {}", cycles, err))?;
+    Ok((name.to_string(), cycles))
+}
+
+/// The latencies to estimate cycles with
+fn latencies(opts: &SimOpts) -> Result<Latencies> {
+    if !opts.timing {
+        return Ok(Latencies::single_cycle());
+    }
+
+    let mut latencies = Latencies::in_order();
+    for (name, cycles) in &opts.latency {
+        match name.as_str() {
+            "load-use" => latencies.load_use = *cycles,
+            "trap" => latencies.trap = *cycles,
+            _ => latencies.set(name.parse::<InstrClass>()?, *cycles),
+        }
+    }
+    Ok(latencies)
+}
+
+fn main() -> Result<()> {
+    let opts = SimOpts::parse();
+    let image = Image::parse_from_elf_path(&opts.input)?;
+
+    // GDB talks over stdout with "--gdb -", so the program can't use it
+    let gdb_on_stdio = opts.gdb.as_deref() == Some("-");
+    let console = || -> Box<dyn Write> {
+        if gdb_on_stdio {
+            Box::new(io::stderr())
+        } else {
+            Box::new(io::stdout())
+        }
+    };
+
+    let mut map = MemoryMap::new();
+    map.map_ram(Ram::new(opts.ram_base, opts.ram_size))
+        .map(CLINT_BASE, CLINT_SIZE, Clint::new())
+        .map(PLIC_BASE, PLIC_SIZE, Plic::new())
+        .map_with_irq(UART_BASE, UART_SIZE, UART_IRQ, Uart::new(console()));
+
+    let mut hart = Hart::with_image(map, &image)?;
+    hart.timing = Timing::new(latencies(&opts)?);
+    if let Some(path) = &opts.csr_defs {
+        for csr in csr::register_from_path(path)? {
+            hart.csrs.add(csr);
+        }
+    }
+    if image.stack_top.is_none() {
+        hart.set_reg(Reg::Sp, opts.ram_base.wrapping_add(opts.ram_size));
+    }
+
+    let mut host = Host::new().stdout(console()).brk(image.end());
+
+    if let Some(addr) = &opts.gdb {
+        let status = if addr == "-" {
+            GdbServer::new(Stdio::new()).serve(&mut hart, &mut host)?
+        } else {
+            let listener = TcpListener::bind(addr.as_str())?;
+            eprintln!("Waiting for GDB on {}", listener.local_addr()?);
+            let (stream, _addr) = listener.accept()?;
+            stream.set_nodelay(true)?;
+            GdbServer::new(stream).serve(&mut hart, &mut host)?
+        };
+        std::process::exit(status.unwrap_or(0));
+    }
+
+    let mut profile = opts.listing.as_ref().map(|_| Profile::new());
+
+    let max_steps = opts.max_steps.unwrap_or(u64::MAX);
+    let mut status = None;
+    for _ in 0..max_steps {
+        let event = host.tick(&mut hart);
+        if let (Some(profile), Some(commit)) = (&mut profile, hart.last_commit()) {
+            profile.record(commit);
+        }
+        if let Some(Event::Exit(code)) = event {
+            status = Some(code);
+            break;
+        }
+    }
+
+    if let (Some(profile), Some(listing_path)) = (&profile, &opts.listing) {
+        write_profile(&opts, profile, listing_path)?;
+    }
+
+    if opts.timing {
+        let (cycles, instret) = (hart.csrs.cycle, hart.csrs.instret);
+        eprintln!(
+            "{} cycles, {} instructions retired ({:.2} cycles per instruction), {} load-use stalls",
+            cycles,
+            instret,
+            cycles as f64 / instret.max(1) as f64,
+            hart.timing.stalls()
+        );
+    }
+
+    match status {
+        Some(status) => std::process::exit(status),
+        None => Err(format!(
+            "{} didn't exit within {} steps (pc = {:#010x})",
+            opts.input,
+            max_steps,
+            hart.pc()
+        )
+        .into()),
+    }
+}
+
+fn write_profile(opts: &SimOpts, profile: &Profile, listing_path: &str) -> Result<()> {
+    let dis = Disassembly::parse_from_elf_path(&opts.input)?;
+
+    let mut listing = File::create(listing_path)?;
+    let lines = profile.write_listing(&dis, &mut listing)?;
+
+    if let Some(lcov_path) = &opts.lcov {
+        let mut lcov = File::create(lcov_path)?;
+        profile.write_lcov(&dis, listing_path, &lines, &mut lcov)?;
+    }
+
+    Ok(())
+}
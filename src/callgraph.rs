@@ -0,0 +1,349 @@
+//! Recovering the call graph of a program from its disassembly
+//!
+//! Functions are found with [`Disassembly::functions`], and every jump that
+//! leaves a function is an edge in the graph.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::csr;
+use crate::dis::{Disassembly, Entry};
+use crate::prelude::*;
+
+/// How control is transferred from one function into another
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CallKind {
+    /// A jump that links a return address, like `call` or `jalr ra, 0(a0)`
+    Call,
+
+    /// A jump into another function without linking, like `tail`
+    ///
+    /// The callee returns directly to our caller.
+    Tail,
+
+    /// Execution runs off of the end of one function and into the next
+    FallThrough,
+}
+
+/// A single place where a function transfers control to another function
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CallSite {
+    /// Address of the instruction making the call
+    pub addr: u32,
+
+    /// Address of the function being called, if it is known statically
+    ///
+    /// Calls through a function pointer will leave this empty.
+    pub target: Option<u32>,
+
+    pub kind: CallKind,
+}
+
+/// A contiguous range of instructions that are entered from the top
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Function {
+    /// Address of the first instruction
+    pub addr: u32,
+
+    /// Address one past the last instruction
+    pub end: u32,
+
+    /// The first label on the entry instruction, if there is one
+    pub name: Option<String>,
+
+    /// Every call site that leaves this function, in address order
+    pub calls: Vec<CallSite>,
+}
+
+impl Function {
+    /// Check whether `addr` falls inside of this function
+    pub fn contains(&self, addr: u32) -> bool {
+        self.addr <= addr && addr < self.end
+    }
+}
+
+/// The functions of a program and the calls between them
+#[derive(Clone, Debug)]
+pub struct CallGraph {
+    functions: BTreeMap<u32, Function>,
+    roots: Vec<u32>,
+}
+
+impl CallGraph {
+    /// Build the call graph for every instruction in `dis`
+    pub fn new(dis: &Disassembly) -> Self {
+        let roots = find_roots(dis);
+
+        let mut functions: BTreeMap<u32, Function> = dis
+            .functions()
+            .iter()
+            .map(|func| {
+                (
+                    func.addr,
+                    Function {
+                        addr: func.addr,
+                        end: func.end,
+                        name: func.name.clone(),
+                        calls: vec![],
+                    },
+                )
+            })
+            .collect();
+        let starts: BTreeSet<u32> = functions.keys().cloned().collect();
+
+        // Now that the function boundaries are known, we can tell which jumps
+        // leave their function.
+        for func in functions.values_mut() {
+            let mut prev: Option<&Entry> = None;
+            let mut falls_through = true;
+
+            for addr in (func.addr..func.end).step_by(4) {
+                let entry = match dis.get(addr) {
+                    Some(entry) => entry,
+                    None => continue,
+                };
+
+                if let Some(site) = call_site(prev, entry) {
+                    let leaves = match site.target {
+                        Some(target) => !func.contains(target),
+                        None => true,
+                    };
+
+                    // Jumps inside of this function are just control flow
+                    if site.kind == CallKind::Call || leaves {
+                        func.calls.push(site);
+                    }
+                }
+
+                falls_through = !ends_flow(entry);
+                prev = Some(entry);
+            }
+
+            if falls_through && starts.contains(&func.end) {
+                func.calls.push(CallSite {
+                    addr: func.end - 4,
+                    target: Some(func.end),
+                    kind: CallKind::FallThrough,
+                });
+            }
+        }
+
+        CallGraph { functions, roots }
+    }
+
+    /// Look up the function that begins at `addr`
+    pub fn function(&self, addr: u32) -> Option<&Function> {
+        self.functions.get(&addr)
+    }
+
+    /// Look up the function that contains the instruction at `addr`
+    pub fn function_containing(&self, addr: u32) -> Option<&Function> {
+        self.functions
+            .range(..=addr)
+            .next_back()
+            .map(|(_addr, func)| func)
+            .filter(|func| func.contains(addr))
+    }
+
+    /// All functions, in address order
+    pub fn functions(&self) -> impl Iterator<Item = &Function> {
+        self.functions.values()
+    }
+
+    /// Addresses that execution can begin from without being called
+    ///
+    /// This is the entry point of the program, followed by any trap handlers.
+    pub fn roots(&self) -> &[u32] {
+        &self.roots
+    }
+}
+
+/// Find the entry point and every trap handler installed by the program
+pub(crate) fn find_roots(dis: &Disassembly) -> Vec<u32> {
+    let mut roots = vec![];
+
+    if let Some(entry_point) = dis.entry_point() {
+        if dis.get(entry_point).is_some() {
+            roots.push(entry_point);
+        }
+    }
+
+    // Trap handlers are installed by writing their address into a `*tvec`
+    // register. That address is almost always built right before, like:
+    //      lui     t0, %hi(handler)
+    //      addi    t0, t0, %lo(handler)
+    //      csrw    mtvec, t0
+    // `csrs` and `csrc` only change bits of the old value, so what they
+    // write isn't the address in `rs1`.
+    let tvecs = [csr::MTVEC.num(), csr::STVEC.num(), csr::UTVEC.num()];
+    let mut known = KnownRegs::new();
+
+    for entry in dis.disassembly() {
+        match entry.o_instr {
+            Some(Instr::Csrrw { rs1, csr, .. }) if tvecs.contains(&csr) => {
+                // The low two bits select the mode, not the address
+                if let Some(handler) = known.get(rs1).map(|addr| addr & !0b11) {
+                    if dis.get(handler).is_some() && !roots.contains(&handler) {
+                        roots.push(handler);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        known.step(entry);
+    }
+
+    roots
+}
+
+/// Tracks registers that hold constants built by simple instructions
+///
+/// Only `lui`, `auipc`, and `addi` are understood. Other instructions forget
+/// the registers that they write. Labels (which something could jump to),
+/// calls, and jumps forget everything.
+/// This is enough to follow the `lui`/`auipc` + `addi` pairs that build
+/// addresses and large immediates.
+#[derive(Clone, Debug)]
+pub(crate) struct KnownRegs {
+    regs: [Option<u32>; 32],
+}
+
+impl KnownRegs {
+    pub fn new() -> Self {
+        let mut known = KnownRegs { regs: [None; 32] };
+        known.forget_all();
+        known
+    }
+
+    /// The value held in `reg`, if it is known
+    pub fn get(&self, reg: Reg) -> Option<u32> {
+        self.regs[reg as usize]
+    }
+
+    pub fn forget_all(&mut self) {
+        self.regs = [None; 32];
+
+        // `zero` is always zero, no matter what is "written" to it
+        self.regs[Reg::Zero as usize] = Some(0);
+    }
+
+    /// Update the known registers with the effects of executing `entry`
+    pub fn step(&mut self, entry: &Entry) {
+        if !entry.labels.is_empty() {
+            self.forget_all();
+        }
+
+        let instr = match entry.o_instr {
+            Some(instr) => instr,
+            None => {
+                self.forget_all();
+                return;
+            }
+        };
+
+        let (rd, value) = match instr {
+            Instr::Lui { rd, imm } => (rd, Some(imm << 12)),
+            Instr::Auipc { rd, imm } => (rd, Some(entry.addr.wrapping_add(imm << 12))),
+            Instr::Addi { rd, rs1, imm } => {
+                (rd, self.get(rs1).map(|base| base.wrapping_add(imm as u32)))
+            }
+
+            // Whatever runs next comes after a call returns, or from
+            // somewhere else entirely.
+            _ if instr.control_flow() != ControlFlow::Sequential => {
+                self.forget_all();
+                return;
+            }
+
+            _ => {
+                for reg in instr.writes().iter() {
+                    self.regs[reg as usize] = None;
+                }
+                return;
+            }
+        };
+
+        if rd != Reg::Zero {
+            self.regs[rd as usize] = value;
+        }
+    }
+}
+
+/// Decode a jump at `entry` into a call site, if it is one
+///
+/// `prev` is the instruction right before `entry` and is used to resolve
+/// `auipc`+`jalr` pairs, which is how far calls are made.
+/// Returns (`ret`) and jumps through a register that aren't linked
+/// (e.g. `jr a0` for a switch statement) are not call sites.
+pub(crate) fn call_site(prev: Option<&Entry>, entry: &Entry) -> Option<CallSite> {
+    let addr = entry.addr;
+    let instr = entry.o_instr?;
+
+    let kind = match instr.control_flow() {
+        ControlFlow::Call => CallKind::Call,
+        ControlFlow::DirectJump | ControlFlow::IndirectJump => CallKind::Tail,
+        _ => return None,
+    };
+
+    let target = match instr {
+        Instr::Jal { imm, .. } => Some(addr.wrapping_add(imm as u32)),
+        Instr::Jalr { rs1, imm, .. } => {
+            // Look for an `auipc` or `lui` building the base register
+            let base = match prev.and_then(|prev| prev.o_instr.map(|instr| (prev.addr, instr))) {
+                Some((pc, Instr::Auipc { rd: prev_rd, imm })) if prev_rd == rs1 => {
+                    Some(pc.wrapping_add(imm << 12))
+                }
+                Some((_pc, Instr::Lui { rd: prev_rd, imm })) if prev_rd == rs1 => Some(imm << 12),
+                _ if rs1 == Reg::Zero => Some(0),
+                _ => None,
+            };
+            base.map(|base| base.wrapping_add(imm as u32) & !1)
+        }
+        _ => None,
+    };
+
+    // Jumps through a register that we can't resolve are usually switch
+    // statements, not calls.
+    if kind == CallKind::Tail && target.is_none() {
+        return None;
+    }
+
+    Some(CallSite { addr, target, kind })
+}
+
+/// Check whether execution can never continue to the next instruction
+pub(crate) fn ends_flow(entry: &Entry) -> bool {
+    entry.o_instr.is_some_and(|instr| !instr.falls_through())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    #[test]
+    fn check_trap_handler_roots() {
+        let roots = |install: u32| {
+            #[rustfmt::skip]
+            let mut dis = Disassembly::from_words(0x0, &[
+                // _start:
+                0x0000_02b7, // lui     t0, 0
+                0x0102_8293, // addi    t0, t0, 0x10
+                install,
+                0x0000_006f, // j       .
+                // handler:
+                0x0000_006f, // j       .
+            ]);
+            dis.set_entry_point(0x0);
+            find_roots(&dis)
+        };
+
+        // csrw mtvec, t0
+        assert_eq!(roots(0x3052_9073), [0x0, 0x10]);
+
+        // csrs mtvec, t0 sets bits in whatever mtvec held before
+        assert_eq!(roots(0x3052_a073), [0x0]);
+    }
+}
@@ -1,4 +1,13 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use std::sync::RwLock;
+
+#[cfg(feature = "std")]
+use serde::Deserialize;
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "lowercase"))]
 pub enum Privilage {
     /// User read and write
     Urw,
@@ -7,18 +16,130 @@ pub enum Privilage {
 
     /// Supervisor read and write
     Srw,
+    /// Supervisor read-only
+    Sro,
+
+    /// Hypervisor read and write, from HS-mode
+    Hrw,
+    /// Hypervisor read-only, from HS-mode
+    Hro,
 
     /// Machine read and write
     Mrw,
     /// Machine read-only
     Mro,
+
+    /// Debug read and write, only from debug mode
+    Drw,
 }
 pub use Privilage::*;
 
+impl Privilage {
+    /// The least privileged mode that can access the CSR
+    ///
+    /// HS-mode is supervisor mode with the hypervisor extension, and debug
+    /// mode is above machine mode.
+    pub const fn mode(&self) -> Mode {
+        match *self {
+            Urw | Uro => Mode::User,
+            Srw | Sro | Hrw | Hro => Mode::Supervisor,
+            Mrw | Mro | Drw => Mode::Machine,
+        }
+    }
+
+    pub const fn is_read_only(&self) -> bool {
+        matches!(*self, Uro | Sro | Hro | Mro)
+    }
+
+    /// Check whether code running in `mode` may access the CSR
+    ///
+    /// `write` is set for instructions that would write it.
+    /// Debug mode isn't one of the modes that [`Mode`] covers, so debug CSRs
+    /// are never allowed.
+    pub fn allows(&self, mode: Mode, write: bool) -> bool {
+        *self != Drw && mode >= self.mode() && !(write && self.is_read_only())
+    }
+}
+
+/// A privilege mode that a hart executes in
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Mode {
+    User = 0,
+    Supervisor = 1,
+    Machine = 3,
+}
+
+impl Mode {
+    /// The least privileged mode that can access the CSR numbered `num`
+    ///
+    /// This follows the conventions for CSR addresses, where bits `[9:8]`
+    /// encode the mode. Hypervisor CSRs are accessible from HS-mode, so they
+    /// are treated as supervisor CSRs.
+    pub const fn lowest_for_csr(num: u16) -> Mode {
+        match (num >> 8) & 0b11 {
+            0b00 => Mode::User,
+            0b11 => Mode::Machine,
+            _ => Mode::Supervisor,
+        }
+    }
+
+    /// The mode encoded by the low two bits of `bits`, as in `mstatus.MPP`
+    ///
+    /// Returns `None` for `0b10`, which is reserved.
+    pub const fn from_bits(bits: u32) -> Option<Mode> {
+        match bits & 0b11 {
+            0b00 => Some(Mode::User),
+            0b01 => Some(Mode::Supervisor),
+            0b11 => Some(Mode::Machine),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match *self {
+            Mode::User => "user",
+            Mode::Supervisor => "supervisor",
+            Mode::Machine => "machine",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Check whether the CSR numbered `num` is read-only
+///
+/// This follows the conventions for CSR addresses, where bits `[11:10]` are
+/// `0b11` for read-only CSRs.
+pub const fn is_read_only(num: u16) -> bool {
+    (num >> 10) & 0b11 == 0b11
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Csr(pub u16, pub Privilage);
 
 impl Csr {
+    /// The CSR numbered `num`, with the privilege that its number implies
+    ///
+    /// Bits `[11:10]` are `0b11` for read-only CSRs, and bits `[9:8]` are the
+    /// lowest mode that can access it, with `0b10` for hypervisor CSRs.
+    /// Numbers `0x7B0` to `0x7BF` are only accessible in debug mode.
+    pub const fn from_number_conventions(num: u16) -> Csr {
+        let read_only = is_read_only(num);
+        let privilage = match (num >> 8) & 0b11 {
+            _ if num >= 0x7B0 && num <= 0x7BF => Drw,
+            0b00 if read_only => Uro,
+            0b00 => Urw,
+            0b01 if read_only => Sro,
+            0b01 => Srw,
+            0b10 if read_only => Hro,
+            0b10 => Hrw,
+            _ if read_only => Mro,
+            _ => Mrw,
+        };
+        Csr(num, privilage)
+    }
+
     pub const fn num(&self) -> u16 {
         self.0
     }
@@ -67,6 +188,34 @@ pub const FRM: Csr = Csr(0x002, Urw);
 /// Floating-Point Control and Status Register (`frm` + `fflags`)
 pub const FCSR: Csr = Csr(0x003, Urw);
 
+// ====== User Vector CSRs =====================================================
+
+/// Vector start position
+pub const VSTART: Csr = Csr(0x008, Urw);
+
+/// Fixed-Point accrued saturation flag
+pub const VXSAT: Csr = Csr(0x009, Urw);
+
+/// Fixed-Point rounding mode
+pub const VXRM: Csr = Csr(0x00A, Urw);
+
+/// Vector control and status register (`vxrm` + `vxsat`)
+pub const VCSR: Csr = Csr(0x00F, Urw);
+
+/// Vector length
+pub const VL: Csr = Csr(0xC20, Uro);
+
+/// Vector data type register
+pub const VTYPE: Csr = Csr(0xC21, Uro);
+
+/// Vector register length in bytes
+pub const VLENB: Csr = Csr(0xC22, Uro);
+
+// ====== User Entropy Source ==================================================
+
+/// Seed for cryptographic random number generators
+pub const SEED: Csr = Csr(0x015, Urw);
+
 // ====== User Counter/Timers ==================================================
 
 /// Cycle counter for `RDCYCLE` instruction
@@ -262,13 +411,201 @@ pub const HPMCOUNTER30_H: Csr = Csr(0xC9E, Uro);
 pub const HPMCOUNTER31_H: Csr = Csr(0xC9F, Uro);
 
 // ===== Supervisor Trap Setup =================================================
-// TODO: Omitted for brevity at this time
+
+/// Supervisor status register
+pub const SSTATUS: Csr = Csr(0x100, Srw);
+
+/// Supervisor interrupt-enable register
+pub const SIE: Csr = Csr(0x104, Srw);
+
+/// Supervisor trap handler base address
+pub const STVEC: Csr = Csr(0x105, Srw);
+
+/// Supervisor exception delegation register, from the N extension
+pub const SEDELEG: Csr = Csr(0x102, Srw);
+
+/// Supervisor interrupt delegation register, from the N extension
+pub const SIDELEG: Csr = Csr(0x103, Srw);
+
+/// Supervisor counter enable
+pub const SCOUNTEREN: Csr = Csr(0x106, Srw);
+
+// ===== Supervisor Configuration ==============================================
+
+/// Supervisor environment configuration register
+pub const SENVCFG: Csr = Csr(0x10A, Srw);
+
+/// Supervisor state enable register
+pub const SSTATEEN0: Csr = Csr(0x10C, Srw);
+
+/// Supervisor state enable register
+pub const SSTATEEN1: Csr = Csr(0x10D, Srw);
+
+/// Supervisor state enable register
+pub const SSTATEEN2: Csr = Csr(0x10E, Srw);
+
+/// Supervisor state enable register
+pub const SSTATEEN3: Csr = Csr(0x10F, Srw);
 
 // ===== Supervisor Trap Handling ==============================================
-// TODO: Omitted for brevity at this time
+
+/// Scratch register for supervisor trap handlers
+pub const SSCRATCH: Csr = Csr(0x140, Srw);
+
+/// Supervisor exception program counter
+pub const SEPC: Csr = Csr(0x141, Srw);
+
+/// Supervisor trap cause
+pub const SCAUSE: Csr = Csr(0x142, Srw);
+
+/// Supervisor bad address or instruction
+pub const STVAL: Csr = Csr(0x143, Srw);
+
+/// Supervisor interrupt pending
+pub const SIP: Csr = Csr(0x144, Srw);
+
+// ===== Supervisor Timer ======================================================
+
+/// Supervisor timer compare
+pub const STIMECMP: Csr = Csr(0x14D, Srw);
+
+/// Upper bits of `stimecmp`, RV32I only
+pub const STIMECMP_H: Csr = Csr(0x15D, Srw);
+
+/// Supervisor count overflow
+pub const SCOUNTOVF: Csr = Csr(0xDA0, Sro);
 
 // ===== Supervisor Protection and Translation =================================
-// TODO: Omitted for brevity at this time
+
+/// Supervisor address translation and protection
+pub const SATP: Csr = Csr(0x180, Srw);
+
+// ===== Supervisor Debug/Trace Registers ======================================
+
+/// Supervisor-mode context register
+pub const SCONTEXT: Csr = Csr(0x5A8, Srw);
+
+// ===== Hypervisor Trap Setup =================================================
+
+/// Hypervisor status register
+pub const HSTATUS: Csr = Csr(0x600, Hrw);
+
+/// Hypervisor exception delegation register
+pub const HEDELEG: Csr = Csr(0x602, Hrw);
+
+/// Hypervisor interrupt delegation register
+pub const HIDELEG: Csr = Csr(0x603, Hrw);
+
+/// Hypervisor interrupt-enable register
+pub const HIE: Csr = Csr(0x604, Hrw);
+
+/// Hypervisor counter enable
+pub const HCOUNTEREN: Csr = Csr(0x606, Hrw);
+
+/// Hypervisor guest external interrupt-enable register
+pub const HGEIE: Csr = Csr(0x607, Hrw);
+
+// ===== Hypervisor Trap Handling ==============================================
+
+/// Hypervisor bad guest physical address
+pub const HTVAL: Csr = Csr(0x643, Hrw);
+
+/// Hypervisor interrupt pending
+pub const HIP: Csr = Csr(0x644, Hrw);
+
+/// Hypervisor virtual interrupt pending
+pub const HVIP: Csr = Csr(0x645, Hrw);
+
+/// Hypervisor trap instruction (transformed)
+pub const HTINST: Csr = Csr(0x64A, Hrw);
+
+/// Hypervisor guest external interrupt pending
+pub const HGEIP: Csr = Csr(0xE12, Hro);
+
+// ===== Hypervisor Configuration ==============================================
+
+/// Hypervisor environment configuration register
+pub const HENVCFG: Csr = Csr(0x60A, Hrw);
+
+/// Upper bits of `henvcfg`, RV32I only
+pub const HENVCFG_H: Csr = Csr(0x61A, Hrw);
+
+/// Hypervisor state enable register
+pub const HSTATEEN0: Csr = Csr(0x60C, Hrw);
+
+/// Hypervisor state enable register
+pub const HSTATEEN1: Csr = Csr(0x60D, Hrw);
+
+/// Hypervisor state enable register
+pub const HSTATEEN2: Csr = Csr(0x60E, Hrw);
+
+/// Hypervisor state enable register
+pub const HSTATEEN3: Csr = Csr(0x60F, Hrw);
+
+/// Upper bits of `hstateen0`, RV32I only
+pub const HSTATEEN0_H: Csr = Csr(0x61C, Hrw);
+
+/// Upper bits of `hstateen1`, RV32I only
+pub const HSTATEEN1_H: Csr = Csr(0x61D, Hrw);
+
+/// Upper bits of `hstateen2`, RV32I only
+pub const HSTATEEN2_H: Csr = Csr(0x61E, Hrw);
+
+/// Upper bits of `hstateen3`, RV32I only
+pub const HSTATEEN3_H: Csr = Csr(0x61F, Hrw);
+
+// ===== Hypervisor Protection and Translation =================================
+
+/// Hypervisor guest address translation and protection
+pub const HGATP: Csr = Csr(0x680, Hrw);
+
+// ===== Hypervisor Debug/Trace Registers ======================================
+
+/// Hypervisor-mode context register
+pub const HCONTEXT: Csr = Csr(0x6A8, Hrw);
+
+// ===== Hypervisor Counter/Timer Virtualization Registers =====================
+
+/// Delta for VS/VU-mode timer
+pub const HTIMEDELTA: Csr = Csr(0x605, Hrw);
+
+/// Upper bits of `htimedelta`, RV32I only
+pub const HTIMEDELTA_H: Csr = Csr(0x615, Hrw);
+
+// ===== Virtual Supervisor Registers ==========================================
+
+/// Virtual supervisor status register
+pub const VSSTATUS: Csr = Csr(0x200, Hrw);
+
+/// Virtual supervisor interrupt-enable register
+pub const VSIE: Csr = Csr(0x204, Hrw);
+
+/// Virtual supervisor trap handler base address
+pub const VSTVEC: Csr = Csr(0x205, Hrw);
+
+/// Virtual supervisor scratch register
+pub const VSSCRATCH: Csr = Csr(0x240, Hrw);
+
+/// Virtual supervisor exception program counter
+pub const VSEPC: Csr = Csr(0x241, Hrw);
+
+/// Virtual supervisor trap cause
+pub const VSCAUSE: Csr = Csr(0x242, Hrw);
+
+/// Virtual supervisor bad address or instruction
+pub const VSTVAL: Csr = Csr(0x243, Hrw);
+
+/// Virtual supervisor interrupt pending
+pub const VSIP: Csr = Csr(0x244, Hrw);
+
+/// Virtual supervisor address translation and protection
+pub const VSATP: Csr = Csr(0x280, Hrw);
+
+/// Virtual supervisor timer compare
+pub const VSTIMECMP: Csr = Csr(0x24D, Hrw);
+
+/// Upper bits of `vstimecmp`, RV32I only
+pub const VSTIMECMP_H: Csr = Csr(0x25D, Hrw);
 
 // ===== Machine Information Registers =========================================
 
@@ -284,6 +621,9 @@ pub const MIMPID: Csr = Csr(0xF13, Mro);
 /// Hardware thread ID
 pub const MHARTID: Csr = Csr(0xF14, Mro);
 
+/// Pointer to configuration data structure
+pub const MCONFIGPTR: Csr = Csr(0xF15, Mro);
+
 // ===== Machine Trap Setup ====================================================
 
 /// Machine status register
@@ -307,6 +647,9 @@ pub const MTVEC: Csr = Csr(0x305, Mrw);
 /// Machine counter enable
 pub const MCOUNTEREN: Csr = Csr(0x306, Mrw);
 
+/// Upper bits of `mstatus`, RV32I only
+pub const MSTATUS_H: Csr = Csr(0x310, Mrw);
+
 // ===== Machine Trap Handling =================================================
 
 /// Scratch register for machine trap handlers
@@ -324,5 +667,2046 @@ pub const MTVAL: Csr = Csr(0x343, Mrw);
 /// Machine interrupt pending
 pub const MIP: Csr = Csr(0x344, Mrw);
 
+/// Machine trap instruction (transformed)
+pub const MTINST: Csr = Csr(0x34A, Mrw);
+
+/// Machine bad guest physical address
+pub const MTVAL2: Csr = Csr(0x34B, Mrw);
+
+// ===== Machine Configuration =================================================
+
+/// Machine environment configuration register
+pub const MENVCFG: Csr = Csr(0x30A, Mrw);
+
+/// Upper bits of `menvcfg`, RV32I only
+pub const MENVCFG_H: Csr = Csr(0x31A, Mrw);
+
+/// Machine security configuration register
+pub const MSECCFG: Csr = Csr(0x747, Mrw);
+
+/// Upper bits of `mseccfg`, RV32I only
+pub const MSECCFG_H: Csr = Csr(0x757, Mrw);
+
+/// Machine state enable register
+pub const MSTATEEN0: Csr = Csr(0x30C, Mrw);
+
+/// Machine state enable register
+pub const MSTATEEN1: Csr = Csr(0x30D, Mrw);
+
+/// Machine state enable register
+pub const MSTATEEN2: Csr = Csr(0x30E, Mrw);
+
+/// Machine state enable register
+pub const MSTATEEN3: Csr = Csr(0x30F, Mrw);
+
+/// Upper bits of `mstateen0`, RV32I only
+pub const MSTATEEN0_H: Csr = Csr(0x31C, Mrw);
+
+/// Upper bits of `mstateen1`, RV32I only
+pub const MSTATEEN1_H: Csr = Csr(0x31D, Mrw);
+
+/// Upper bits of `mstateen2`, RV32I only
+pub const MSTATEEN2_H: Csr = Csr(0x31E, Mrw);
+
+/// Upper bits of `mstateen3`, RV32I only
+pub const MSTATEEN3_H: Csr = Csr(0x31F, Mrw);
+
 // ===== Machine Memory Protection =============================================
-// TODO: Omitted for brevity at this time
+
+/// Physical memory protection configuration
+pub const PMPCFG0: Csr = Csr(0x3A0, Mrw);
+
+/// Physical memory protection configuration, RV32I only
+pub const PMPCFG1: Csr = Csr(0x3A1, Mrw);
+
+/// Physical memory protection configuration
+pub const PMPCFG2: Csr = Csr(0x3A2, Mrw);
+
+/// Physical memory protection configuration, RV32I only
+pub const PMPCFG3: Csr = Csr(0x3A3, Mrw);
+
+/// Physical memory protection configuration
+pub const PMPCFG4: Csr = Csr(0x3A4, Mrw);
+
+/// Physical memory protection configuration, RV32I only
+pub const PMPCFG5: Csr = Csr(0x3A5, Mrw);
+
+/// Physical memory protection configuration
+pub const PMPCFG6: Csr = Csr(0x3A6, Mrw);
+
+/// Physical memory protection configuration, RV32I only
+pub const PMPCFG7: Csr = Csr(0x3A7, Mrw);
+
+/// Physical memory protection configuration
+pub const PMPCFG8: Csr = Csr(0x3A8, Mrw);
+
+/// Physical memory protection configuration, RV32I only
+pub const PMPCFG9: Csr = Csr(0x3A9, Mrw);
+
+/// Physical memory protection configuration
+pub const PMPCFG10: Csr = Csr(0x3AA, Mrw);
+
+/// Physical memory protection configuration, RV32I only
+pub const PMPCFG11: Csr = Csr(0x3AB, Mrw);
+
+/// Physical memory protection configuration
+pub const PMPCFG12: Csr = Csr(0x3AC, Mrw);
+
+/// Physical memory protection configuration, RV32I only
+pub const PMPCFG13: Csr = Csr(0x3AD, Mrw);
+
+/// Physical memory protection configuration
+pub const PMPCFG14: Csr = Csr(0x3AE, Mrw);
+
+/// Physical memory protection configuration, RV32I only
+pub const PMPCFG15: Csr = Csr(0x3AF, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR0: Csr = Csr(0x3B0, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR1: Csr = Csr(0x3B1, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR2: Csr = Csr(0x3B2, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR3: Csr = Csr(0x3B3, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR4: Csr = Csr(0x3B4, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR5: Csr = Csr(0x3B5, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR6: Csr = Csr(0x3B6, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR7: Csr = Csr(0x3B7, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR8: Csr = Csr(0x3B8, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR9: Csr = Csr(0x3B9, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR10: Csr = Csr(0x3BA, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR11: Csr = Csr(0x3BB, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR12: Csr = Csr(0x3BC, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR13: Csr = Csr(0x3BD, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR14: Csr = Csr(0x3BE, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR15: Csr = Csr(0x3BF, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR16: Csr = Csr(0x3C0, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR17: Csr = Csr(0x3C1, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR18: Csr = Csr(0x3C2, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR19: Csr = Csr(0x3C3, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR20: Csr = Csr(0x3C4, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR21: Csr = Csr(0x3C5, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR22: Csr = Csr(0x3C6, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR23: Csr = Csr(0x3C7, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR24: Csr = Csr(0x3C8, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR25: Csr = Csr(0x3C9, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR26: Csr = Csr(0x3CA, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR27: Csr = Csr(0x3CB, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR28: Csr = Csr(0x3CC, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR29: Csr = Csr(0x3CD, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR30: Csr = Csr(0x3CE, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR31: Csr = Csr(0x3CF, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR32: Csr = Csr(0x3D0, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR33: Csr = Csr(0x3D1, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR34: Csr = Csr(0x3D2, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR35: Csr = Csr(0x3D3, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR36: Csr = Csr(0x3D4, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR37: Csr = Csr(0x3D5, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR38: Csr = Csr(0x3D6, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR39: Csr = Csr(0x3D7, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR40: Csr = Csr(0x3D8, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR41: Csr = Csr(0x3D9, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR42: Csr = Csr(0x3DA, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR43: Csr = Csr(0x3DB, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR44: Csr = Csr(0x3DC, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR45: Csr = Csr(0x3DD, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR46: Csr = Csr(0x3DE, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR47: Csr = Csr(0x3DF, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR48: Csr = Csr(0x3E0, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR49: Csr = Csr(0x3E1, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR50: Csr = Csr(0x3E2, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR51: Csr = Csr(0x3E3, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR52: Csr = Csr(0x3E4, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR53: Csr = Csr(0x3E5, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR54: Csr = Csr(0x3E6, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR55: Csr = Csr(0x3E7, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR56: Csr = Csr(0x3E8, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR57: Csr = Csr(0x3E9, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR58: Csr = Csr(0x3EA, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR59: Csr = Csr(0x3EB, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR60: Csr = Csr(0x3EC, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR61: Csr = Csr(0x3ED, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR62: Csr = Csr(0x3EE, Mrw);
+
+/// Physical memory protection address register
+pub const PMPADDR63: Csr = Csr(0x3EF, Mrw);
+
+// ===== Machine Counter/Timers ================================================
+
+/// Machine cycle counter
+pub const MCYCLE: Csr = Csr(0xB00, Mrw);
+
+/// Machine instructions-retired counter
+pub const MINSTRET: Csr = Csr(0xB02, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER3: Csr = Csr(0xB03, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER4: Csr = Csr(0xB04, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER5: Csr = Csr(0xB05, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER6: Csr = Csr(0xB06, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER7: Csr = Csr(0xB07, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER8: Csr = Csr(0xB08, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER9: Csr = Csr(0xB09, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER10: Csr = Csr(0xB0A, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER11: Csr = Csr(0xB0B, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER12: Csr = Csr(0xB0C, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER13: Csr = Csr(0xB0D, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER14: Csr = Csr(0xB0E, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER15: Csr = Csr(0xB0F, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER16: Csr = Csr(0xB10, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER17: Csr = Csr(0xB11, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER18: Csr = Csr(0xB12, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER19: Csr = Csr(0xB13, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER20: Csr = Csr(0xB14, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER21: Csr = Csr(0xB15, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER22: Csr = Csr(0xB16, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER23: Csr = Csr(0xB17, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER24: Csr = Csr(0xB18, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER25: Csr = Csr(0xB19, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER26: Csr = Csr(0xB1A, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER27: Csr = Csr(0xB1B, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER28: Csr = Csr(0xB1C, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER29: Csr = Csr(0xB1D, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER30: Csr = Csr(0xB1E, Mrw);
+
+/// Machine performance-monitoring counter
+pub const MHPMCOUNTER31: Csr = Csr(0xB1F, Mrw);
+
+/// Upper bits of `mcycle`, RV32I only
+pub const MCYCLE_H: Csr = Csr(0xB80, Mrw);
+
+/// Upper bits of `minstret`, RV32I only
+pub const MINSTRET_H: Csr = Csr(0xB82, Mrw);
+
+/// Upper bits of `mhpmcounter3`, RV32I only
+pub const MHPMCOUNTER3_H: Csr = Csr(0xB83, Mrw);
+
+/// Upper bits of `mhpmcounter4`, RV32I only
+pub const MHPMCOUNTER4_H: Csr = Csr(0xB84, Mrw);
+
+/// Upper bits of `mhpmcounter5`, RV32I only
+pub const MHPMCOUNTER5_H: Csr = Csr(0xB85, Mrw);
+
+/// Upper bits of `mhpmcounter6`, RV32I only
+pub const MHPMCOUNTER6_H: Csr = Csr(0xB86, Mrw);
+
+/// Upper bits of `mhpmcounter7`, RV32I only
+pub const MHPMCOUNTER7_H: Csr = Csr(0xB87, Mrw);
+
+/// Upper bits of `mhpmcounter8`, RV32I only
+pub const MHPMCOUNTER8_H: Csr = Csr(0xB88, Mrw);
+
+/// Upper bits of `mhpmcounter9`, RV32I only
+pub const MHPMCOUNTER9_H: Csr = Csr(0xB89, Mrw);
+
+/// Upper bits of `mhpmcounter10`, RV32I only
+pub const MHPMCOUNTER10_H: Csr = Csr(0xB8A, Mrw);
+
+/// Upper bits of `mhpmcounter11`, RV32I only
+pub const MHPMCOUNTER11_H: Csr = Csr(0xB8B, Mrw);
+
+/// Upper bits of `mhpmcounter12`, RV32I only
+pub const MHPMCOUNTER12_H: Csr = Csr(0xB8C, Mrw);
+
+/// Upper bits of `mhpmcounter13`, RV32I only
+pub const MHPMCOUNTER13_H: Csr = Csr(0xB8D, Mrw);
+
+/// Upper bits of `mhpmcounter14`, RV32I only
+pub const MHPMCOUNTER14_H: Csr = Csr(0xB8E, Mrw);
+
+/// Upper bits of `mhpmcounter15`, RV32I only
+pub const MHPMCOUNTER15_H: Csr = Csr(0xB8F, Mrw);
+
+/// Upper bits of `mhpmcounter16`, RV32I only
+pub const MHPMCOUNTER16_H: Csr = Csr(0xB90, Mrw);
+
+/// Upper bits of `mhpmcounter17`, RV32I only
+pub const MHPMCOUNTER17_H: Csr = Csr(0xB91, Mrw);
+
+/// Upper bits of `mhpmcounter18`, RV32I only
+pub const MHPMCOUNTER18_H: Csr = Csr(0xB92, Mrw);
+
+/// Upper bits of `mhpmcounter19`, RV32I only
+pub const MHPMCOUNTER19_H: Csr = Csr(0xB93, Mrw);
+
+/// Upper bits of `mhpmcounter20`, RV32I only
+pub const MHPMCOUNTER20_H: Csr = Csr(0xB94, Mrw);
+
+/// Upper bits of `mhpmcounter21`, RV32I only
+pub const MHPMCOUNTER21_H: Csr = Csr(0xB95, Mrw);
+
+/// Upper bits of `mhpmcounter22`, RV32I only
+pub const MHPMCOUNTER22_H: Csr = Csr(0xB96, Mrw);
+
+/// Upper bits of `mhpmcounter23`, RV32I only
+pub const MHPMCOUNTER23_H: Csr = Csr(0xB97, Mrw);
+
+/// Upper bits of `mhpmcounter24`, RV32I only
+pub const MHPMCOUNTER24_H: Csr = Csr(0xB98, Mrw);
+
+/// Upper bits of `mhpmcounter25`, RV32I only
+pub const MHPMCOUNTER25_H: Csr = Csr(0xB99, Mrw);
+
+/// Upper bits of `mhpmcounter26`, RV32I only
+pub const MHPMCOUNTER26_H: Csr = Csr(0xB9A, Mrw);
+
+/// Upper bits of `mhpmcounter27`, RV32I only
+pub const MHPMCOUNTER27_H: Csr = Csr(0xB9B, Mrw);
+
+/// Upper bits of `mhpmcounter28`, RV32I only
+pub const MHPMCOUNTER28_H: Csr = Csr(0xB9C, Mrw);
+
+/// Upper bits of `mhpmcounter29`, RV32I only
+pub const MHPMCOUNTER29_H: Csr = Csr(0xB9D, Mrw);
+
+/// Upper bits of `mhpmcounter30`, RV32I only
+pub const MHPMCOUNTER30_H: Csr = Csr(0xB9E, Mrw);
+
+/// Upper bits of `mhpmcounter31`, RV32I only
+pub const MHPMCOUNTER31_H: Csr = Csr(0xB9F, Mrw);
+
+// ===== Machine Counter Setup =================================================
+
+/// Machine counter-inhibit register
+pub const MCOUNTINHIBIT: Csr = Csr(0x320, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT3: Csr = Csr(0x323, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT4: Csr = Csr(0x324, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT5: Csr = Csr(0x325, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT6: Csr = Csr(0x326, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT7: Csr = Csr(0x327, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT8: Csr = Csr(0x328, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT9: Csr = Csr(0x329, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT10: Csr = Csr(0x32A, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT11: Csr = Csr(0x32B, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT12: Csr = Csr(0x32C, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT13: Csr = Csr(0x32D, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT14: Csr = Csr(0x32E, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT15: Csr = Csr(0x32F, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT16: Csr = Csr(0x330, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT17: Csr = Csr(0x331, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT18: Csr = Csr(0x332, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT19: Csr = Csr(0x333, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT20: Csr = Csr(0x334, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT21: Csr = Csr(0x335, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT22: Csr = Csr(0x336, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT23: Csr = Csr(0x337, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT24: Csr = Csr(0x338, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT25: Csr = Csr(0x339, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT26: Csr = Csr(0x33A, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT27: Csr = Csr(0x33B, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT28: Csr = Csr(0x33C, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT29: Csr = Csr(0x33D, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT30: Csr = Csr(0x33E, Mrw);
+
+/// Machine performance-monitoring event selector
+pub const MHPMEVENT31: Csr = Csr(0x33F, Mrw);
+
+/// Upper bits of `mhpmevent3`, RV32I only
+pub const MHPMEVENT3_H: Csr = Csr(0x723, Mrw);
+
+/// Upper bits of `mhpmevent4`, RV32I only
+pub const MHPMEVENT4_H: Csr = Csr(0x724, Mrw);
+
+/// Upper bits of `mhpmevent5`, RV32I only
+pub const MHPMEVENT5_H: Csr = Csr(0x725, Mrw);
+
+/// Upper bits of `mhpmevent6`, RV32I only
+pub const MHPMEVENT6_H: Csr = Csr(0x726, Mrw);
+
+/// Upper bits of `mhpmevent7`, RV32I only
+pub const MHPMEVENT7_H: Csr = Csr(0x727, Mrw);
+
+/// Upper bits of `mhpmevent8`, RV32I only
+pub const MHPMEVENT8_H: Csr = Csr(0x728, Mrw);
+
+/// Upper bits of `mhpmevent9`, RV32I only
+pub const MHPMEVENT9_H: Csr = Csr(0x729, Mrw);
+
+/// Upper bits of `mhpmevent10`, RV32I only
+pub const MHPMEVENT10_H: Csr = Csr(0x72A, Mrw);
+
+/// Upper bits of `mhpmevent11`, RV32I only
+pub const MHPMEVENT11_H: Csr = Csr(0x72B, Mrw);
+
+/// Upper bits of `mhpmevent12`, RV32I only
+pub const MHPMEVENT12_H: Csr = Csr(0x72C, Mrw);
+
+/// Upper bits of `mhpmevent13`, RV32I only
+pub const MHPMEVENT13_H: Csr = Csr(0x72D, Mrw);
+
+/// Upper bits of `mhpmevent14`, RV32I only
+pub const MHPMEVENT14_H: Csr = Csr(0x72E, Mrw);
+
+/// Upper bits of `mhpmevent15`, RV32I only
+pub const MHPMEVENT15_H: Csr = Csr(0x72F, Mrw);
+
+/// Upper bits of `mhpmevent16`, RV32I only
+pub const MHPMEVENT16_H: Csr = Csr(0x730, Mrw);
+
+/// Upper bits of `mhpmevent17`, RV32I only
+pub const MHPMEVENT17_H: Csr = Csr(0x731, Mrw);
+
+/// Upper bits of `mhpmevent18`, RV32I only
+pub const MHPMEVENT18_H: Csr = Csr(0x732, Mrw);
+
+/// Upper bits of `mhpmevent19`, RV32I only
+pub const MHPMEVENT19_H: Csr = Csr(0x733, Mrw);
+
+/// Upper bits of `mhpmevent20`, RV32I only
+pub const MHPMEVENT20_H: Csr = Csr(0x734, Mrw);
+
+/// Upper bits of `mhpmevent21`, RV32I only
+pub const MHPMEVENT21_H: Csr = Csr(0x735, Mrw);
+
+/// Upper bits of `mhpmevent22`, RV32I only
+pub const MHPMEVENT22_H: Csr = Csr(0x736, Mrw);
+
+/// Upper bits of `mhpmevent23`, RV32I only
+pub const MHPMEVENT23_H: Csr = Csr(0x737, Mrw);
+
+/// Upper bits of `mhpmevent24`, RV32I only
+pub const MHPMEVENT24_H: Csr = Csr(0x738, Mrw);
+
+/// Upper bits of `mhpmevent25`, RV32I only
+pub const MHPMEVENT25_H: Csr = Csr(0x739, Mrw);
+
+/// Upper bits of `mhpmevent26`, RV32I only
+pub const MHPMEVENT26_H: Csr = Csr(0x73A, Mrw);
+
+/// Upper bits of `mhpmevent27`, RV32I only
+pub const MHPMEVENT27_H: Csr = Csr(0x73B, Mrw);
+
+/// Upper bits of `mhpmevent28`, RV32I only
+pub const MHPMEVENT28_H: Csr = Csr(0x73C, Mrw);
+
+/// Upper bits of `mhpmevent29`, RV32I only
+pub const MHPMEVENT29_H: Csr = Csr(0x73D, Mrw);
+
+/// Upper bits of `mhpmevent30`, RV32I only
+pub const MHPMEVENT30_H: Csr = Csr(0x73E, Mrw);
+
+/// Upper bits of `mhpmevent31`, RV32I only
+pub const MHPMEVENT31_H: Csr = Csr(0x73F, Mrw);
+
+// ===== Debug/Trace Registers (shared with Debug Mode) ========================
+
+/// Debug/Trace trigger register select
+pub const TSELECT: Csr = Csr(0x7A0, Mrw);
+
+/// First Debug/Trace trigger data register
+pub const TDATA1: Csr = Csr(0x7A1, Mrw);
+
+/// Second Debug/Trace trigger data register
+pub const TDATA2: Csr = Csr(0x7A2, Mrw);
+
+/// Third Debug/Trace trigger data register
+pub const TDATA3: Csr = Csr(0x7A3, Mrw);
+
+/// Machine-mode context register
+pub const MCONTEXT: Csr = Csr(0x7A8, Mrw);
+
+// ===== Debug Mode Registers ==================================================
+
+/// Debug control and status register
+pub const DCSR: Csr = Csr(0x7B0, Drw);
+
+/// Debug PC
+pub const DPC: Csr = Csr(0x7B1, Drw);
+
+/// Debug scratch register 0
+pub const DSCRATCH0: Csr = Csr(0x7B2, Drw);
+
+/// Debug scratch register 1
+pub const DSCRATCH1: Csr = Csr(0x7B3, Drw);
+
+// ===== Names =================================================================
+
+/// Every CSR above, by the name that assemblers use for it
+const NAMES: &[(&str, Csr)] = &[
+    ("ustatus", USTATUS),
+    ("uie", UIE),
+    ("utvec", UTVEC),
+    ("uscratch", USCRATCH),
+    ("uepc", UEPC),
+    ("ucause", UCAUSE),
+    ("utval", UTVAL),
+    ("uip", UIP),
+    ("fflags", FFLAGS),
+    ("frm", FRM),
+    ("fcsr", FCSR),
+    ("vstart", VSTART),
+    ("vxsat", VXSAT),
+    ("vxrm", VXRM),
+    ("vcsr", VCSR),
+    ("vl", VL),
+    ("vtype", VTYPE),
+    ("vlenb", VLENB),
+    ("seed", SEED),
+    ("cycle", CYCLE),
+    ("time", TIME),
+    ("instret", INSTRET),
+    ("hpmcounter3", HPMCOUNTER3),
+    ("hpmcounter4", HPMCOUNTER4),
+    ("hpmcounter5", HPMCOUNTER5),
+    ("hpmcounter6", HPMCOUNTER6),
+    ("hpmcounter7", HPMCOUNTER7),
+    ("hpmcounter8", HPMCOUNTER8),
+    ("hpmcounter9", HPMCOUNTER9),
+    ("hpmcounter10", HPMCOUNTER10),
+    ("hpmcounter11", HPMCOUNTER11),
+    ("hpmcounter12", HPMCOUNTER12),
+    ("hpmcounter13", HPMCOUNTER13),
+    ("hpmcounter14", HPMCOUNTER14),
+    ("hpmcounter15", HPMCOUNTER15),
+    ("hpmcounter16", HPMCOUNTER16),
+    ("hpmcounter17", HPMCOUNTER17),
+    ("hpmcounter18", HPMCOUNTER18),
+    ("hpmcounter19", HPMCOUNTER19),
+    ("hpmcounter20", HPMCOUNTER20),
+    ("hpmcounter21", HPMCOUNTER21),
+    ("hpmcounter22", HPMCOUNTER22),
+    ("hpmcounter23", HPMCOUNTER23),
+    ("hpmcounter24", HPMCOUNTER24),
+    ("hpmcounter25", HPMCOUNTER25),
+    ("hpmcounter26", HPMCOUNTER26),
+    ("hpmcounter27", HPMCOUNTER27),
+    ("hpmcounter28", HPMCOUNTER28),
+    ("hpmcounter29", HPMCOUNTER29),
+    ("hpmcounter30", HPMCOUNTER30),
+    ("hpmcounter31", HPMCOUNTER31),
+    ("cycleh", CYCLE_H),
+    ("timeh", TIME_H),
+    ("instreth", INSTRET_H),
+    ("hpmcounter3h", HPMCOUNTER3_H),
+    ("hpmcounter4h", HPMCOUNTER4_H),
+    ("hpmcounter5h", HPMCOUNTER5_H),
+    ("hpmcounter6h", HPMCOUNTER6_H),
+    ("hpmcounter7h", HPMCOUNTER7_H),
+    ("hpmcounter8h", HPMCOUNTER8_H),
+    ("hpmcounter9h", HPMCOUNTER9_H),
+    ("hpmcounter10h", HPMCOUNTER10_H),
+    ("hpmcounter11h", HPMCOUNTER11_H),
+    ("hpmcounter12h", HPMCOUNTER12_H),
+    ("hpmcounter13h", HPMCOUNTER13_H),
+    ("hpmcounter14h", HPMCOUNTER14_H),
+    ("hpmcounter15h", HPMCOUNTER15_H),
+    ("hpmcounter16h", HPMCOUNTER16_H),
+    ("hpmcounter17h", HPMCOUNTER17_H),
+    ("hpmcounter18h", HPMCOUNTER18_H),
+    ("hpmcounter19h", HPMCOUNTER19_H),
+    ("hpmcounter20h", HPMCOUNTER20_H),
+    ("hpmcounter21h", HPMCOUNTER21_H),
+    ("hpmcounter22h", HPMCOUNTER22_H),
+    ("hpmcounter23h", HPMCOUNTER23_H),
+    ("hpmcounter24h", HPMCOUNTER24_H),
+    ("hpmcounter25h", HPMCOUNTER25_H),
+    ("hpmcounter26h", HPMCOUNTER26_H),
+    ("hpmcounter27h", HPMCOUNTER27_H),
+    ("hpmcounter28h", HPMCOUNTER28_H),
+    ("hpmcounter29h", HPMCOUNTER29_H),
+    ("hpmcounter30h", HPMCOUNTER30_H),
+    ("hpmcounter31h", HPMCOUNTER31_H),
+    ("sstatus", SSTATUS),
+    ("sie", SIE),
+    ("stvec", STVEC),
+    ("sedeleg", SEDELEG),
+    ("sideleg", SIDELEG),
+    ("scounteren", SCOUNTEREN),
+    ("senvcfg", SENVCFG),
+    ("sstateen0", SSTATEEN0),
+    ("sstateen1", SSTATEEN1),
+    ("sstateen2", SSTATEEN2),
+    ("sstateen3", SSTATEEN3),
+    ("sscratch", SSCRATCH),
+    ("sepc", SEPC),
+    ("scause", SCAUSE),
+    ("stval", STVAL),
+    ("sip", SIP),
+    ("stimecmp", STIMECMP),
+    ("stimecmph", STIMECMP_H),
+    ("scountovf", SCOUNTOVF),
+    ("satp", SATP),
+    ("scontext", SCONTEXT),
+    ("hstatus", HSTATUS),
+    ("hedeleg", HEDELEG),
+    ("hideleg", HIDELEG),
+    ("hie", HIE),
+    ("hcounteren", HCOUNTEREN),
+    ("hgeie", HGEIE),
+    ("htval", HTVAL),
+    ("hip", HIP),
+    ("hvip", HVIP),
+    ("htinst", HTINST),
+    ("hgeip", HGEIP),
+    ("henvcfg", HENVCFG),
+    ("henvcfgh", HENVCFG_H),
+    ("hstateen0", HSTATEEN0),
+    ("hstateen1", HSTATEEN1),
+    ("hstateen2", HSTATEEN2),
+    ("hstateen3", HSTATEEN3),
+    ("hstateen0h", HSTATEEN0_H),
+    ("hstateen1h", HSTATEEN1_H),
+    ("hstateen2h", HSTATEEN2_H),
+    ("hstateen3h", HSTATEEN3_H),
+    ("hgatp", HGATP),
+    ("hcontext", HCONTEXT),
+    ("htimedelta", HTIMEDELTA),
+    ("htimedeltah", HTIMEDELTA_H),
+    ("vsstatus", VSSTATUS),
+    ("vsie", VSIE),
+    ("vstvec", VSTVEC),
+    ("vsscratch", VSSCRATCH),
+    ("vsepc", VSEPC),
+    ("vscause", VSCAUSE),
+    ("vstval", VSTVAL),
+    ("vsip", VSIP),
+    ("vsatp", VSATP),
+    ("vstimecmp", VSTIMECMP),
+    ("vstimecmph", VSTIMECMP_H),
+    ("mvendorid", MVENDORID),
+    ("marchid", MARCHID),
+    ("mimpid", MIMPID),
+    ("mhartid", MHARTID),
+    ("mconfigptr", MCONFIGPTR),
+    ("mstatus", MSTATUS),
+    ("misa", MISA),
+    ("medeleg", MEDELEG),
+    ("mideleg", MIDELEG),
+    ("mie", MIE),
+    ("mtvec", MTVEC),
+    ("mcounteren", MCOUNTEREN),
+    ("mstatush", MSTATUS_H),
+    ("mscratch", MSCRATCH),
+    ("mepc", MEPC),
+    ("mcause", MCAUSE),
+    ("mtval", MTVAL),
+    ("mip", MIP),
+    ("mtinst", MTINST),
+    ("mtval2", MTVAL2),
+    ("menvcfg", MENVCFG),
+    ("menvcfgh", MENVCFG_H),
+    ("mseccfg", MSECCFG),
+    ("mseccfgh", MSECCFG_H),
+    ("mstateen0", MSTATEEN0),
+    ("mstateen1", MSTATEEN1),
+    ("mstateen2", MSTATEEN2),
+    ("mstateen3", MSTATEEN3),
+    ("mstateen0h", MSTATEEN0_H),
+    ("mstateen1h", MSTATEEN1_H),
+    ("mstateen2h", MSTATEEN2_H),
+    ("mstateen3h", MSTATEEN3_H),
+    ("pmpcfg0", PMPCFG0),
+    ("pmpcfg1", PMPCFG1),
+    ("pmpcfg2", PMPCFG2),
+    ("pmpcfg3", PMPCFG3),
+    ("pmpcfg4", PMPCFG4),
+    ("pmpcfg5", PMPCFG5),
+    ("pmpcfg6", PMPCFG6),
+    ("pmpcfg7", PMPCFG7),
+    ("pmpcfg8", PMPCFG8),
+    ("pmpcfg9", PMPCFG9),
+    ("pmpcfg10", PMPCFG10),
+    ("pmpcfg11", PMPCFG11),
+    ("pmpcfg12", PMPCFG12),
+    ("pmpcfg13", PMPCFG13),
+    ("pmpcfg14", PMPCFG14),
+    ("pmpcfg15", PMPCFG15),
+    ("pmpaddr0", PMPADDR0),
+    ("pmpaddr1", PMPADDR1),
+    ("pmpaddr2", PMPADDR2),
+    ("pmpaddr3", PMPADDR3),
+    ("pmpaddr4", PMPADDR4),
+    ("pmpaddr5", PMPADDR5),
+    ("pmpaddr6", PMPADDR6),
+    ("pmpaddr7", PMPADDR7),
+    ("pmpaddr8", PMPADDR8),
+    ("pmpaddr9", PMPADDR9),
+    ("pmpaddr10", PMPADDR10),
+    ("pmpaddr11", PMPADDR11),
+    ("pmpaddr12", PMPADDR12),
+    ("pmpaddr13", PMPADDR13),
+    ("pmpaddr14", PMPADDR14),
+    ("pmpaddr15", PMPADDR15),
+    ("pmpaddr16", PMPADDR16),
+    ("pmpaddr17", PMPADDR17),
+    ("pmpaddr18", PMPADDR18),
+    ("pmpaddr19", PMPADDR19),
+    ("pmpaddr20", PMPADDR20),
+    ("pmpaddr21", PMPADDR21),
+    ("pmpaddr22", PMPADDR22),
+    ("pmpaddr23", PMPADDR23),
+    ("pmpaddr24", PMPADDR24),
+    ("pmpaddr25", PMPADDR25),
+    ("pmpaddr26", PMPADDR26),
+    ("pmpaddr27", PMPADDR27),
+    ("pmpaddr28", PMPADDR28),
+    ("pmpaddr29", PMPADDR29),
+    ("pmpaddr30", PMPADDR30),
+    ("pmpaddr31", PMPADDR31),
+    ("pmpaddr32", PMPADDR32),
+    ("pmpaddr33", PMPADDR33),
+    ("pmpaddr34", PMPADDR34),
+    ("pmpaddr35", PMPADDR35),
+    ("pmpaddr36", PMPADDR36),
+    ("pmpaddr37", PMPADDR37),
+    ("pmpaddr38", PMPADDR38),
+    ("pmpaddr39", PMPADDR39),
+    ("pmpaddr40", PMPADDR40),
+    ("pmpaddr41", PMPADDR41),
+    ("pmpaddr42", PMPADDR42),
+    ("pmpaddr43", PMPADDR43),
+    ("pmpaddr44", PMPADDR44),
+    ("pmpaddr45", PMPADDR45),
+    ("pmpaddr46", PMPADDR46),
+    ("pmpaddr47", PMPADDR47),
+    ("pmpaddr48", PMPADDR48),
+    ("pmpaddr49", PMPADDR49),
+    ("pmpaddr50", PMPADDR50),
+    ("pmpaddr51", PMPADDR51),
+    ("pmpaddr52", PMPADDR52),
+    ("pmpaddr53", PMPADDR53),
+    ("pmpaddr54", PMPADDR54),
+    ("pmpaddr55", PMPADDR55),
+    ("pmpaddr56", PMPADDR56),
+    ("pmpaddr57", PMPADDR57),
+    ("pmpaddr58", PMPADDR58),
+    ("pmpaddr59", PMPADDR59),
+    ("pmpaddr60", PMPADDR60),
+    ("pmpaddr61", PMPADDR61),
+    ("pmpaddr62", PMPADDR62),
+    ("pmpaddr63", PMPADDR63),
+    ("mcycle", MCYCLE),
+    ("minstret", MINSTRET),
+    ("mhpmcounter3", MHPMCOUNTER3),
+    ("mhpmcounter4", MHPMCOUNTER4),
+    ("mhpmcounter5", MHPMCOUNTER5),
+    ("mhpmcounter6", MHPMCOUNTER6),
+    ("mhpmcounter7", MHPMCOUNTER7),
+    ("mhpmcounter8", MHPMCOUNTER8),
+    ("mhpmcounter9", MHPMCOUNTER9),
+    ("mhpmcounter10", MHPMCOUNTER10),
+    ("mhpmcounter11", MHPMCOUNTER11),
+    ("mhpmcounter12", MHPMCOUNTER12),
+    ("mhpmcounter13", MHPMCOUNTER13),
+    ("mhpmcounter14", MHPMCOUNTER14),
+    ("mhpmcounter15", MHPMCOUNTER15),
+    ("mhpmcounter16", MHPMCOUNTER16),
+    ("mhpmcounter17", MHPMCOUNTER17),
+    ("mhpmcounter18", MHPMCOUNTER18),
+    ("mhpmcounter19", MHPMCOUNTER19),
+    ("mhpmcounter20", MHPMCOUNTER20),
+    ("mhpmcounter21", MHPMCOUNTER21),
+    ("mhpmcounter22", MHPMCOUNTER22),
+    ("mhpmcounter23", MHPMCOUNTER23),
+    ("mhpmcounter24", MHPMCOUNTER24),
+    ("mhpmcounter25", MHPMCOUNTER25),
+    ("mhpmcounter26", MHPMCOUNTER26),
+    ("mhpmcounter27", MHPMCOUNTER27),
+    ("mhpmcounter28", MHPMCOUNTER28),
+    ("mhpmcounter29", MHPMCOUNTER29),
+    ("mhpmcounter30", MHPMCOUNTER30),
+    ("mhpmcounter31", MHPMCOUNTER31),
+    ("mcycleh", MCYCLE_H),
+    ("minstreth", MINSTRET_H),
+    ("mhpmcounter3h", MHPMCOUNTER3_H),
+    ("mhpmcounter4h", MHPMCOUNTER4_H),
+    ("mhpmcounter5h", MHPMCOUNTER5_H),
+    ("mhpmcounter6h", MHPMCOUNTER6_H),
+    ("mhpmcounter7h", MHPMCOUNTER7_H),
+    ("mhpmcounter8h", MHPMCOUNTER8_H),
+    ("mhpmcounter9h", MHPMCOUNTER9_H),
+    ("mhpmcounter10h", MHPMCOUNTER10_H),
+    ("mhpmcounter11h", MHPMCOUNTER11_H),
+    ("mhpmcounter12h", MHPMCOUNTER12_H),
+    ("mhpmcounter13h", MHPMCOUNTER13_H),
+    ("mhpmcounter14h", MHPMCOUNTER14_H),
+    ("mhpmcounter15h", MHPMCOUNTER15_H),
+    ("mhpmcounter16h", MHPMCOUNTER16_H),
+    ("mhpmcounter17h", MHPMCOUNTER17_H),
+    ("mhpmcounter18h", MHPMCOUNTER18_H),
+    ("mhpmcounter19h", MHPMCOUNTER19_H),
+    ("mhpmcounter20h", MHPMCOUNTER20_H),
+    ("mhpmcounter21h", MHPMCOUNTER21_H),
+    ("mhpmcounter22h", MHPMCOUNTER22_H),
+    ("mhpmcounter23h", MHPMCOUNTER23_H),
+    ("mhpmcounter24h", MHPMCOUNTER24_H),
+    ("mhpmcounter25h", MHPMCOUNTER25_H),
+    ("mhpmcounter26h", MHPMCOUNTER26_H),
+    ("mhpmcounter27h", MHPMCOUNTER27_H),
+    ("mhpmcounter28h", MHPMCOUNTER28_H),
+    ("mhpmcounter29h", MHPMCOUNTER29_H),
+    ("mhpmcounter30h", MHPMCOUNTER30_H),
+    ("mhpmcounter31h", MHPMCOUNTER31_H),
+    ("mcountinhibit", MCOUNTINHIBIT),
+    ("mhpmevent3", MHPMEVENT3),
+    ("mhpmevent4", MHPMEVENT4),
+    ("mhpmevent5", MHPMEVENT5),
+    ("mhpmevent6", MHPMEVENT6),
+    ("mhpmevent7", MHPMEVENT7),
+    ("mhpmevent8", MHPMEVENT8),
+    ("mhpmevent9", MHPMEVENT9),
+    ("mhpmevent10", MHPMEVENT10),
+    ("mhpmevent11", MHPMEVENT11),
+    ("mhpmevent12", MHPMEVENT12),
+    ("mhpmevent13", MHPMEVENT13),
+    ("mhpmevent14", MHPMEVENT14),
+    ("mhpmevent15", MHPMEVENT15),
+    ("mhpmevent16", MHPMEVENT16),
+    ("mhpmevent17", MHPMEVENT17),
+    ("mhpmevent18", MHPMEVENT18),
+    ("mhpmevent19", MHPMEVENT19),
+    ("mhpmevent20", MHPMEVENT20),
+    ("mhpmevent21", MHPMEVENT21),
+    ("mhpmevent22", MHPMEVENT22),
+    ("mhpmevent23", MHPMEVENT23),
+    ("mhpmevent24", MHPMEVENT24),
+    ("mhpmevent25", MHPMEVENT25),
+    ("mhpmevent26", MHPMEVENT26),
+    ("mhpmevent27", MHPMEVENT27),
+    ("mhpmevent28", MHPMEVENT28),
+    ("mhpmevent29", MHPMEVENT29),
+    ("mhpmevent30", MHPMEVENT30),
+    ("mhpmevent31", MHPMEVENT31),
+    ("mhpmevent3h", MHPMEVENT3_H),
+    ("mhpmevent4h", MHPMEVENT4_H),
+    ("mhpmevent5h", MHPMEVENT5_H),
+    ("mhpmevent6h", MHPMEVENT6_H),
+    ("mhpmevent7h", MHPMEVENT7_H),
+    ("mhpmevent8h", MHPMEVENT8_H),
+    ("mhpmevent9h", MHPMEVENT9_H),
+    ("mhpmevent10h", MHPMEVENT10_H),
+    ("mhpmevent11h", MHPMEVENT11_H),
+    ("mhpmevent12h", MHPMEVENT12_H),
+    ("mhpmevent13h", MHPMEVENT13_H),
+    ("mhpmevent14h", MHPMEVENT14_H),
+    ("mhpmevent15h", MHPMEVENT15_H),
+    ("mhpmevent16h", MHPMEVENT16_H),
+    ("mhpmevent17h", MHPMEVENT17_H),
+    ("mhpmevent18h", MHPMEVENT18_H),
+    ("mhpmevent19h", MHPMEVENT19_H),
+    ("mhpmevent20h", MHPMEVENT20_H),
+    ("mhpmevent21h", MHPMEVENT21_H),
+    ("mhpmevent22h", MHPMEVENT22_H),
+    ("mhpmevent23h", MHPMEVENT23_H),
+    ("mhpmevent24h", MHPMEVENT24_H),
+    ("mhpmevent25h", MHPMEVENT25_H),
+    ("mhpmevent26h", MHPMEVENT26_H),
+    ("mhpmevent27h", MHPMEVENT27_H),
+    ("mhpmevent28h", MHPMEVENT28_H),
+    ("mhpmevent29h", MHPMEVENT29_H),
+    ("mhpmevent30h", MHPMEVENT30_H),
+    ("mhpmevent31h", MHPMEVENT31_H),
+    ("tselect", TSELECT),
+    ("tdata1", TDATA1),
+    ("tdata2", TDATA2),
+    ("tdata3", TDATA3),
+    ("mcontext", MCONTEXT),
+    ("dcsr", DCSR),
+    ("dpc", DPC),
+    ("dscratch0", DSCRATCH0),
+    ("dscratch1", DSCRATCH1),
+];
+
+/// Older names for CSRs that assemblers still accept
+const ALIASES: &[(&str, Csr)] = &[
+    ("ubadaddr", UTVAL),
+    ("sbadaddr", STVAL),
+    ("mbadaddr", MTVAL),
+    ("sptbr", SATP),
+    ("mucounteren", MCOUNTINHIBIT),
+    ("dscratch", DSCRATCH0),
+];
+
+/// Look up the CSR numbered `num`, along with its name
+pub fn by_number(num: u16) -> Option<(&'static str, Csr)> {
+    NAMES
+        .iter()
+        .find(|(_name, csr)| csr.num() == num)
+        .cloned()
+        .or_else(|| find_custom(|_name, csr| csr.num() == num))
+}
+
+/// Look up a CSR by its name, or one of its older names
+///
+/// Names are matched without regard to case, so `MSTATUS` is `mstatus`.
+pub fn by_name(name: &str) -> Option<Csr> {
+    NAMES
+        .iter()
+        .chain(ALIASES)
+        .find(|(csr_name, _csr)| csr_name.eq_ignore_ascii_case(name))
+        .cloned()
+        .or_else(|| find_custom(|custom_name, _csr| custom_name.eq_ignore_ascii_case(name)))
+        .map(|(_name, csr)| csr)
+}
+
+/// The name of the CSR numbered `num`, or the number in hex if it has none
+#[cfg(feature = "std")]
+pub fn name_or_number(num: u16) -> String {
+    match by_number(num) {
+        Some((name, _csr)) => name.to_string(),
+        None => format!("{:#x}", num),
+    }
+}
+
+// ===== Custom CSRs ===========================================================
+
+/// A CSR defined by a vendor, rather than by the privileged spec
+///
+/// These can be registered with [`register`], or loaded from a file with
+/// [`register_from_toml`] or [`register_from_json`]. In a file, each CSR is
+/// an entry in a `csr` list:
+/// ```toml
+/// [[csr]]
+/// name = "mxctl"
+/// number = 0x7c0
+/// privilege = "mrw"
+/// fields = [
+///     { name = "EN", lsb = 0 },
+///     { name = "MODE", lsb = 1, msb = 3 },
+/// ]
+/// ```
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomCsr {
+    pub name: String,
+    pub number: u16,
+
+    /// Who may access the CSR
+    ///
+    /// If this is missing, it follows the conventions for CSR addresses.
+    #[serde(default)]
+    pub privilege: Option<Privilage>,
+
+    /// Fields to show when explaining the CSR's value, in any order
+    #[serde(default)]
+    pub fields: Vec<CustomField>,
+}
+
+/// A named range of bits in a [`CustomCsr`]
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomField {
+    pub name: String,
+
+    /// The lowest bit of the field
+    pub lsb: u8,
+
+    /// The highest bit of the field, which is `lsb` if it's missing
+    #[serde(default)]
+    pub msb: Option<u8>,
+}
+
+#[cfg(feature = "std")]
+impl CustomField {
+    /// The value of this field in `value`
+    pub fn extract(&self, value: u32) -> u32 {
+        let msb = self.msb.unwrap_or(self.lsb);
+        let width = (msb - self.lsb + 1) as u32;
+        (value >> self.lsb) & (u32::MAX >> (32 - width))
+    }
+}
+
+/// The contents of a file of [`CustomCsr`]s
+#[cfg(feature = "std")]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CustomCsrFile {
+    #[serde(default)]
+    csr: Vec<CustomCsr>,
+}
+
+/// A registered [`CustomCsr`]
+///
+/// Names are leaked when they're registered, so that they can be looked up
+/// like the names of standard CSRs.
+#[cfg(feature = "std")]
+struct Registered {
+    name: &'static str,
+    csr: Csr,
+    fields: Vec<CustomField>,
+}
+
+/// Every CSR added by [`register`]
+#[cfg(feature = "std")]
+static CUSTOM: RwLock<Vec<Registered>> = RwLock::new(Vec::new());
+
+/// Add a custom CSR, so that it can be looked up like the standard ones
+///
+/// The number must fit in 12 bits, and the name and number must not already
+/// belong to another CSR.
+#[cfg(feature = "std")]
+pub fn register(custom: CustomCsr) -> crate::Result<Csr> {
+    let mut csrs = register_all(vec![custom])?;
+    Ok(csrs.remove(0))
+}
+
+/// Register every CSR defined in `text`, in TOML
+///
+/// See [`CustomCsr`] for the format. If any of them can't be registered,
+/// none of them are.
+#[cfg(feature = "std")]
+pub fn register_from_toml(text: &str) -> crate::Result<Vec<Csr>> {
+    let file: CustomCsrFile = toml::from_str(text)?;
+    register_all(file.csr)
+}
+
+/// Register every CSR defined in `text`, in JSON
+///
+/// This is the same format as [`register_from_toml`], like
+/// `{ "csr": [{ "name": "mxctl", "number": 1984 }] }`.
+#[cfg(feature = "std")]
+pub fn register_from_json(text: &str) -> crate::Result<Vec<Csr>> {
+    let file: CustomCsrFile = serde_json::from_str(text)?;
+    register_all(file.csr)
+}
+
+/// Register all of `customs`, or none of them if any can't be registered
+///
+/// The registry stays locked from checking for duplicates until the CSRs are
+/// added, so that two threads can't register the same name or number.
+#[cfg(feature = "std")]
+fn register_all(customs: Vec<CustomCsr>) -> crate::Result<Vec<Csr>> {
+    let mut registry = CUSTOM.write().unwrap();
+
+    for (i, custom) in customs.iter().enumerate() {
+        if custom.number > 0xfff {
+            return Err(format!("CSR number {:#x} doesn't fit in 12 bits", custom.number).into());
+        }
+
+        // Earlier CSRs in `customs` count as taken too
+        let standard = NAMES.iter().map(|(name, csr)| (*name, csr.num()));
+        let registered = registry
+            .iter()
+            .map(|custom| (custom.name, custom.csr.num()));
+        let pending = customs[..i]
+            .iter()
+            .map(|custom| (custom.name.as_str(), custom.number));
+        let mut taken = standard.chain(registered).chain(pending);
+        if let Some((name, _num)) = taken.find(|(_name, num)| *num == custom.number) {
+            return Err(format!("CSR {:#x} is already named {}", custom.number, name).into());
+        }
+
+        let standard = NAMES.iter().chain(ALIASES).map(|(name, _csr)| *name);
+        let registered = registry.iter().map(|custom| custom.name);
+        let pending = customs[..i].iter().map(|custom| custom.name.as_str());
+        if standard
+            .chain(registered)
+            .chain(pending)
+            .any(|name| name.eq_ignore_ascii_case(&custom.name))
+        {
+            return Err(format!("there is already a CSR named {}", custom.name).into());
+        }
+
+        for field in &custom.fields {
+            let msb = field.msb.unwrap_or(field.lsb);
+            if msb < field.lsb || msb >= 32 {
+                let name = &field.name;
+                return Err(format!(
+                    "field {} of {} isn't within bits 0 to 31",
+                    name, custom.name
+                )
+                .into());
+            }
+        }
+    }
+
+    let mut csrs = Vec::with_capacity(customs.len());
+    for custom in customs {
+        let csr = match custom.privilege {
+            Some(privilage) => Csr(custom.number, privilage),
+            None => Csr::from_number_conventions(custom.number),
+        };
+
+        let mut fields = custom.fields;
+        fields.sort_by_key(|field| field.lsb);
+
+        registry.push(Registered {
+            name: Box::leak(custom.name.into_boxed_str()),
+            csr,
+            fields,
+        });
+        csrs.push(csr);
+    }
+    Ok(csrs)
+}
+
+/// Register every CSR defined in the file at `path`
+///
+/// Files ending in `.json` are read as JSON, and everything else as TOML.
+#[cfg(feature = "std")]
+pub fn register_from_path(path: impl AsRef<std::path::Path>) -> crate::Result<Vec<Csr>> {
+    let path = path.as_ref();
+    let text =
+        std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let csrs = if path.extension().is_some_and(|ext| ext == "json") {
+        register_from_json(&text)
+    } else {
+        register_from_toml(&text)
+    };
+    csrs.map_err(|err| format!("{}: {}", path.display(), err).into())
+}
+
+/// Every custom CSR that has been registered, in the order they were added
+#[cfg(feature = "std")]
+pub fn custom() -> Vec<(&'static str, Csr)> {
+    CUSTOM
+        .read()
+        .unwrap()
+        .iter()
+        .map(|custom| (custom.name, custom.csr))
+        .collect()
+}
+
+/// Describe `value` with the fields of a custom CSR
+#[cfg(feature = "std")]
+fn explain_custom(csr: Csr, value: u32) -> Option<String> {
+    let registered = CUSTOM.read().unwrap();
+    let custom = registered.iter().find(|custom| custom.csr == csr)?;
+    if custom.fields.is_empty() {
+        return None;
+    }
+
+    let fields: Vec<String> = custom
+        .fields
+        .iter()
+        .map(|field| format!("{}={:#x}", field.name, field.extract(value)))
+        .collect();
+    Some(fields.join(" "))
+}
+
+/// The first registered custom CSR that `matches`, along with its name
+#[cfg(feature = "std")]
+fn find_custom(matches: impl Fn(&str, Csr) -> bool) -> Option<(&'static str, Csr)> {
+    CUSTOM
+        .read()
+        .unwrap()
+        .iter()
+        .find(|custom| matches(custom.name, custom.csr))
+        .map(|custom| (custom.name, custom.csr))
+}
+
+/// Without `std` there's no registry, so there are no custom CSRs
+#[cfg(not(feature = "std"))]
+fn find_custom(_matches: impl Fn(&str, Csr) -> bool) -> Option<(&'static str, Csr)> {
+    None
+}
+
+// ===== Fields ================================================================
+
+/// Bit `n` of `value`, as 0 or 1
+fn bit(value: u32, n: u32) -> u32 {
+    (value >> n) & 1
+}
+
+/// The state of an extension's registers in `mstatus.FS` and `mstatus.XS`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ExtensionState {
+    Off,
+    Initial,
+    Clean,
+    Dirty,
+}
+
+impl ExtensionState {
+    fn from_bits(bits: u32) -> Self {
+        match bits & 0b11 {
+            0 => ExtensionState::Off,
+            1 => ExtensionState::Initial,
+            2 => ExtensionState::Clean,
+            _ => ExtensionState::Dirty,
+        }
+    }
+}
+
+impl fmt::Display for ExtensionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match *self {
+            ExtensionState::Off => "off",
+            ExtensionState::Initial => "initial",
+            ExtensionState::Clean => "clean",
+            ExtensionState::Dirty => "dirty",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The fields of `mstatus`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Mstatus(pub u32);
+
+impl From<u32> for Mstatus {
+    fn from(value: u32) -> Self {
+        Mstatus(value)
+    }
+}
+
+impl Mstatus {
+    /// Interrupts are enabled in S-mode
+    pub fn sie(&self) -> bool {
+        bit(self.0, 1) != 0
+    }
+
+    /// Interrupts are enabled in M-mode
+    pub fn mie(&self) -> bool {
+        bit(self.0, 3) != 0
+    }
+
+    /// The value of `SIE` before the current trap into S-mode
+    pub fn spie(&self) -> bool {
+        bit(self.0, 5) != 0
+    }
+
+    /// The value of `MIE` before the current trap into M-mode
+    pub fn mpie(&self) -> bool {
+        bit(self.0, 7) != 0
+    }
+
+    /// The mode before the current trap into S-mode
+    pub fn spp(&self) -> Mode {
+        if bit(self.0, 8) != 0 {
+            Mode::Supervisor
+        } else {
+            Mode::User
+        }
+    }
+
+    /// The mode before the current trap into M-mode, or `None` if it's the
+    /// reserved encoding
+    pub fn mpp(&self) -> Option<Mode> {
+        Mode::from_bits(self.0 >> 11)
+    }
+
+    /// The state of the floating-point registers
+    pub fn fs(&self) -> ExtensionState {
+        ExtensionState::from_bits(self.0 >> 13)
+    }
+
+    /// The state of any other extension's registers
+    pub fn xs(&self) -> ExtensionState {
+        ExtensionState::from_bits(self.0 >> 15)
+    }
+
+    /// Loads and stores use the privilege in `MPP`
+    pub fn mprv(&self) -> bool {
+        bit(self.0, 17) != 0
+    }
+
+    /// S-mode may access user pages
+    pub fn sum(&self) -> bool {
+        bit(self.0, 18) != 0
+    }
+
+    /// Loads from executable pages are allowed
+    pub fn mxr(&self) -> bool {
+        bit(self.0, 19) != 0
+    }
+
+    /// S-mode can't access `satp` or run `sfence.vma`
+    pub fn tvm(&self) -> bool {
+        bit(self.0, 20) != 0
+    }
+
+    /// `wfi` traps when run below M-mode
+    pub fn tw(&self) -> bool {
+        bit(self.0, 21) != 0
+    }
+
+    /// `sret` traps in S-mode
+    pub fn tsr(&self) -> bool {
+        bit(self.0, 22) != 0
+    }
+
+    /// `FS` or `XS` is dirty
+    pub fn sd(&self) -> bool {
+        bit(self.0, 31) != 0
+    }
+}
+
+impl fmt::Display for Mstatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let v = self.0;
+        write!(
+            f,
+            "SIE={} MIE={} SPIE={} MPIE={} SPP={} ",
+            bit(v, 1),
+            bit(v, 3),
+            bit(v, 5),
+            bit(v, 7),
+            self.spp()
+        )?;
+        match self.mpp() {
+            Some(mode) => write!(f, "MPP={} ", mode)?,
+            None => write!(f, "MPP=reserved ")?,
+        }
+        write!(
+            f,
+            "FS={} XS={} MPRV={} SUM={} MXR={} TVM={} TW={} TSR={} SD={}",
+            self.fs(),
+            self.xs(),
+            bit(v, 17),
+            bit(v, 18),
+            bit(v, 19),
+            bit(v, 20),
+            bit(v, 21),
+            bit(v, 22),
+            bit(v, 31)
+        )
+    }
+}
+
+/// The fields of `mcause` or `scause`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Mcause(pub u32);
+
+impl From<u32> for Mcause {
+    fn from(value: u32) -> Self {
+        Mcause(value)
+    }
+}
+
+impl Mcause {
+    /// The trap was an interrupt, rather than an exception
+    pub fn is_interrupt(&self) -> bool {
+        bit(self.0, 31) != 0
+    }
+
+    /// The interrupt or exception code
+    pub fn code(&self) -> u32 {
+        self.0 & !(1 << 31)
+    }
+
+    /// What the code means, if the privileged spec gives it a meaning
+    pub fn description(&self) -> Option<&'static str> {
+        let description = if self.is_interrupt() {
+            match self.code() {
+                1 => "supervisor software interrupt",
+                2 => "virtual supervisor software interrupt",
+                3 => "machine software interrupt",
+                5 => "supervisor timer interrupt",
+                6 => "virtual supervisor timer interrupt",
+                7 => "machine timer interrupt",
+                9 => "supervisor external interrupt",
+                10 => "virtual supervisor external interrupt",
+                11 => "machine external interrupt",
+                12 => "supervisor guest external interrupt",
+                13 => "counter overflow interrupt",
+                16..=0x7fff_ffff => "platform interrupt",
+                _ => return None,
+            }
+        } else {
+            match self.code() {
+                0 => "instruction address misaligned",
+                1 => "instruction access fault",
+                2 => "illegal instruction",
+                3 => "breakpoint",
+                4 => "load address misaligned",
+                5 => "load access fault",
+                6 => "store/AMO address misaligned",
+                7 => "store/AMO access fault",
+                8 => "environment call from U-mode",
+                9 => "environment call from S-mode",
+                10 => "environment call from VS-mode",
+                11 => "environment call from M-mode",
+                12 => "instruction page fault",
+                13 => "load page fault",
+                15 => "store/AMO page fault",
+                20 => "instruction guest-page fault",
+                21 => "load guest-page fault",
+                22 => "virtual instruction",
+                23 => "store/AMO guest-page fault",
+                24..=31 | 48..=63 => "custom exception",
+                _ => return None,
+            }
+        };
+        Some(description)
+    }
+}
+
+impl fmt::Display for Mcause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.is_interrupt() {
+            "interrupt"
+        } else {
+            "exception"
+        };
+        write!(
+            f,
+            "{} {} ({})",
+            kind,
+            self.code(),
+            self.description().unwrap_or("reserved")
+        )
+    }
+}
+
+/// The fields of `misa`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Misa(pub u32);
+
+impl From<u32> for Misa {
+    fn from(value: u32) -> Self {
+        Misa(value)
+    }
+}
+
+impl Misa {
+    /// The native base integer width, or `None` if `MXL` is 0
+    ///
+    /// `misa` may read as zero on harts that don't implement it.
+    pub fn xlen(&self) -> Option<u32> {
+        match self.0 >> 30 {
+            1 => Some(32),
+            2 => Some(64),
+            3 => Some(128),
+            _ => None,
+        }
+    }
+
+    /// Whether the extension with the single-letter name `letter` is present
+    pub fn has(&self, letter: char) -> bool {
+        let letter = letter.to_ascii_uppercase();
+        letter.is_ascii_uppercase() && bit(self.0, letter as u32 - 'A' as u32) != 0
+    }
+
+    /// The letters of every present extension, in alphabetical order
+    pub fn letters(&self) -> impl Iterator<Item = char> + '_ {
+        ('A'..='Z').filter(move |&letter| self.has(letter))
+    }
+
+    /// [`Misa::letters`] as a string, like "IMAC"
+    #[cfg(feature = "std")]
+    pub fn extensions(&self) -> String {
+        self.letters().collect()
+    }
+}
+
+impl fmt::Display for Misa {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.xlen() {
+            Some(xlen) => write!(f, "RV{}", xlen)?,
+            None if self.0 == 0 => return write!(f, "not implemented"),
+            None => write!(f, "MXL=0, extensions ")?,
+        }
+        for letter in self.letters() {
+            write!(f, "{}", letter)?;
+        }
+        Ok(())
+    }
+}
+
+/// The fields of `mtvec` or `stvec`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Mtvec(pub u32);
+
+impl From<u32> for Mtvec {
+    fn from(value: u32) -> Self {
+        Mtvec(value)
+    }
+}
+
+impl Mtvec {
+    /// The address of the trap handler, or of the vector table
+    pub fn base(&self) -> u32 {
+        self.0 & !0b11
+    }
+
+    /// Interrupts jump to `base + 4 * cause` instead of `base`
+    pub fn is_vectored(&self) -> bool {
+        self.0 & 0b11 == 0b01
+    }
+}
+
+impl fmt::Display for Mtvec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self.0 & 0b11 {
+            0b00 => "direct",
+            0b01 => "vectored",
+            _ => "reserved",
+        };
+        write!(f, "base {:#010x}, {}", self.base(), mode)
+    }
+}
+
+/// Names of the bits in `mip` and `mie`, without the trailing "P" or "E"
+const INTERRUPT_BITS: [(u32, &str); 10] = [
+    (1, "SSI"),
+    (2, "VSSI"),
+    (3, "MSI"),
+    (5, "STI"),
+    (6, "VSTI"),
+    (7, "MTI"),
+    (9, "SEI"),
+    (10, "VSEI"),
+    (11, "MEI"),
+    (12, "SGEI"),
+];
+
+/// Write the name of each interrupt set in `value`, ending each with `suffix`
+fn write_interrupts(f: &mut fmt::Formatter<'_>, value: u32, suffix: &str) -> fmt::Result {
+    if value == 0 {
+        return write!(f, "none");
+    }
+
+    let set = (0..32).filter(|&n| bit(value, n) != 0);
+    for (i, n) in set.enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        match INTERRUPT_BITS.iter().find(|&&(bit, _name)| bit == n) {
+            Some((_bit, name)) => write!(f, "{}{}", name, suffix)?,
+            None => write!(f, "bit {}", n)?,
+        }
+    }
+    Ok(())
+}
+
+/// The interrupts pending in `mip` or `sip`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Mip(pub u32);
+
+impl From<u32> for Mip {
+    fn from(value: u32) -> Self {
+        Mip(value)
+    }
+}
+
+impl fmt::Display for Mip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_interrupts(f, self.0, "P")
+    }
+}
+
+/// The interrupts enabled in `mie` or `sie`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Mie(pub u32);
+
+impl From<u32> for Mie {
+    fn from(value: u32) -> Self {
+        Mie(value)
+    }
+}
+
+impl fmt::Display for Mie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_interrupts(f, self.0, "E")
+    }
+}
+
+/// The fields of `satp` on RV32
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Satp(pub u32);
+
+impl From<u32> for Satp {
+    fn from(value: u32) -> Self {
+        Satp(value)
+    }
+}
+
+impl Satp {
+    /// Addresses are translated with Sv32 page tables, rather than used as-is
+    pub fn is_sv32(&self) -> bool {
+        bit(self.0, 31) != 0
+    }
+
+    /// The address space identifier
+    pub fn asid(&self) -> u32 {
+        (self.0 >> 22) & 0x1ff
+    }
+
+    /// The physical page number of the root page table
+    pub fn ppn(&self) -> u32 {
+        self.0 & 0x3f_ffff
+    }
+
+    /// The physical address of the root page table
+    ///
+    /// Sv32 has 34-bit physical addresses, so this doesn't fit in a `u32`.
+    pub fn root(&self) -> u64 {
+        (self.ppn() as u64) << 12
+    }
+}
+
+impl fmt::Display for Satp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_sv32() {
+            write!(
+                f,
+                "Sv32, ASID {}, root page table at {:#011x}",
+                self.asid(),
+                self.root()
+            )
+        } else {
+            write!(f, "Bare, ASID {}, PPN {:#x}", self.asid(), self.ppn())
+        }
+    }
+}
+
+/// Describe the fields of `value` read from `csr`
+///
+/// Returns `None` for CSRs without a view above, or custom fields.
+#[cfg(feature = "std")]
+pub fn explain(csr: Csr, value: u32) -> Option<String> {
+    let num = csr.num();
+    let explanation = match num {
+        _ if num == MSTATUS.num() => Mstatus(value).to_string(),
+        _ if num == MCAUSE.num() || num == SCAUSE.num() => Mcause(value).to_string(),
+        _ if num == MISA.num() => Misa(value).to_string(),
+        _ if num == MTVEC.num() || num == STVEC.num() => Mtvec(value).to_string(),
+        _ if num == MIP.num() || num == SIP.num() => Mip(value).to_string(),
+        _ if num == MIE.num() || num == SIE.num() => Mie(value).to_string(),
+        _ if num == SATP.num() => Satp(value).to_string(),
+        _ => return explain_custom(csr, value),
+    };
+    Some(explanation)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    #[test]
+    fn check_by_number() {
+        assert_eq!(by_number(0x342), Some(("mcause", MCAUSE)));
+        assert_eq!(by_number(0xC80), Some(("cycleh", CYCLE_H)));
+        assert_eq!(by_number(0x3A0 + 15), Some(("pmpcfg15", PMPCFG15)));
+        assert_eq!(by_number(0x7C0), None);
+
+        assert_eq!(name_or_number(0x342), "mcause");
+        assert_eq!(name_or_number(0x7C0), "0x7c0");
+    }
+
+    #[test]
+    fn check_by_name() {
+        assert_eq!(by_name("mstatus"), Some(MSTATUS));
+        assert_eq!(by_name("MSTATUS"), Some(MSTATUS));
+        assert_eq!(by_name("fflags"), Some(FFLAGS));
+        assert_eq!(by_name("mhpmcounter31h"), Some(MHPMCOUNTER31_H));
+        assert_eq!(by_name("mbadaddr"), Some(MTVAL));
+        assert_eq!(by_name("dscratch"), Some(DSCRATCH0));
+        assert_eq!(by_name("mcycle_h"), None);
+    }
+
+    #[test]
+    fn check_number_conventions() {
+        assert_eq!(Csr::from_number_conventions(0xC00), CYCLE);
+        assert_eq!(Csr::from_number_conventions(0x100), SSTATUS);
+        assert_eq!(Csr::from_number_conventions(0xDA0), SCOUNTOVF);
+        assert_eq!(Csr::from_number_conventions(0xE12), HGEIP);
+        assert_eq!(Csr::from_number_conventions(0x7B1), DPC);
+        assert_eq!(Csr::from_number_conventions(0x7C0), Csr(0x7C0, Mrw));
+
+        // Every CSR defined above follows the conventions
+        for &(name, csr) in NAMES {
+            assert_eq!(Csr::from_number_conventions(csr.num()), csr, "{}", name);
+        }
+    }
+
+    #[test]
+    fn check_names_are_unique() {
+        for (i, (name, csr)) in NAMES.iter().enumerate() {
+            for (other_name, other) in &NAMES[i + 1..] {
+                assert_ne!(name, other_name);
+                assert_ne!(csr.num(), other.num(), "{} and {}", name, other_name);
+            }
+        }
+    }
+
+    #[test]
+    fn check_mstatus() {
+        let mstatus = Mstatus::from(0x0000_1888);
+        assert!(mstatus.mie());
+        assert!(mstatus.mpie());
+        assert_eq!(mstatus.mpp(), Some(Mode::Machine));
+        assert_eq!(mstatus.fs(), ExtensionState::Off);
+        assert_eq!(
+            mstatus.to_string(),
+            "SIE=0 MIE=1 SPIE=0 MPIE=1 SPP=user MPP=machine FS=off XS=off MPRV=0 SUM=0 MXR=0 \
+             TVM=0 TW=0 TSR=0 SD=0"
+        );
+
+        let mstatus = Mstatus::from(0x8060_7000);
+        assert_eq!(mstatus.mpp(), None);
+        assert_eq!(mstatus.fs(), ExtensionState::Dirty);
+        assert!(mstatus.tw() && mstatus.tsr() && mstatus.sd());
+        assert!(!mstatus.tvm());
+    }
+
+    #[test]
+    fn check_mcause() {
+        let mcause = Mcause::from(0x8000_000b);
+        assert!(mcause.is_interrupt());
+        assert_eq!(mcause.code(), 11);
+        assert_eq!(
+            mcause.to_string(),
+            "interrupt 11 (machine external interrupt)"
+        );
+
+        assert_eq!(Mcause(2).to_string(), "exception 2 (illegal instruction)");
+        assert_eq!(Mcause(14).to_string(), "exception 14 (reserved)");
+        assert_eq!(Mcause(0x8000_0004).description(), None);
+    }
+
+    #[test]
+    fn check_misa() {
+        let misa = Misa::from(0x4014_1105);
+        assert_eq!(misa.xlen(), Some(32));
+        assert!(misa.has('i') && misa.has('M') && !misa.has('F'));
+        assert_eq!(misa.to_string(), "RV32ACIMSU");
+        assert_eq!(Misa(0).to_string(), "not implemented");
+        assert_eq!(Misa(0x105).to_string(), "MXL=0, extensions ACI");
+    }
+
+    #[test]
+    fn check_explain() {
+        assert_eq!(
+            explain(MTVEC, 0x8000_0101).as_deref(),
+            Some("base 0x80000100, vectored")
+        );
+        assert_eq!(explain(MIP, 0x880).as_deref(), Some("MTIP MEIP"));
+        assert_eq!(explain(MIE, 0x1_0008).as_deref(), Some("MSIE bit 16"));
+        assert_eq!(explain(SIE, 0).as_deref(), Some("none"));
+        assert_eq!(
+            explain(SATP, 0x8040_0123).as_deref(),
+            Some("Sv32, ASID 1, root page table at 0x000123000")
+        );
+        assert_eq!(explain(SATP, 0).as_deref(), Some("Bare, ASID 0, PPN 0x0"));
+        assert_eq!(explain(MSCRATCH, 0), None);
+    }
+
+    // Custom CSRs are shared by every test, so each of these registers
+    // different names and numbers
+
+    #[test]
+    fn check_register() {
+        let csr = register(CustomCsr {
+            name: "mxtest".to_string(),
+            number: 0x7c8,
+            privilege: None,
+            fields: vec![],
+        })
+        .unwrap();
+        assert_eq!(csr, Csr(0x7c8, Mrw));
+        assert_eq!(by_number(0x7c8), Some(("mxtest", csr)));
+        assert_eq!(by_name("MXTEST"), Some(csr));
+        assert!(custom().contains(&("mxtest", csr)));
+        assert_eq!(explain(csr, 1), None);
+
+        // Names and numbers can't be reused
+        let again = |name: &str, number: u16| {
+            register(CustomCsr {
+                name: name.to_string(),
+                number,
+                privilege: None,
+                fields: vec![],
+            })
+        };
+        assert!(again("mxtest", 0x7c9).is_err());
+        assert!(again("mxother", 0x7c8).is_err());
+        assert!(again("mxother", MSTATUS.num()).is_err());
+        assert!(again("mstatus", 0x7c9).is_err());
+        assert!(again("mxother", 0x17c9).is_err());
+        assert_eq!(by_number(0x7c9), None);
+        assert_eq!(by_name("mxother"), None);
+    }
+
+    #[test]
+    fn check_register_from_toml() {
+        let csrs = register_from_toml(
+            r#"
+            [[csr]]
+            name = "uxtoml"
+            number = 0xcc0
+            fields = [
+                { name = "EN", lsb = 0 },
+                { name = "MODE", lsb = 4, msb = 7 },
+            ]
+
+            [[csr]]
+            name = "sxtoml"
+            number = 0x5c0
+            privilege = "srw"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(csrs, vec![Csr(0xcc0, Uro), Csr(0x5c0, Srw)]);
+        assert_eq!(explain(csrs[0], 0xa5).as_deref(), Some("EN=0x1 MODE=0xa"));
+
+        assert!(register_from_toml("[[csr]]\nname = \"x\"").is_err());
+        assert!(register_from_toml(
+            "[[csr]]\nname = \"uxbad\"\nnumber = 0xcc1\nfields = [{ name = \"A\", lsb = 4, msb = 2 }]"
+        )
+        .is_err());
+
+        // A file with a bad CSR registers none of them
+        let err = register_from_toml(
+            r#"
+            [[csr]]
+            name = "uxfirst"
+            number = 0xcc2
+
+            [[csr]]
+            name = "uxsecond"
+            number = 0xcc2
+            "#,
+        );
+        assert!(err.is_err());
+        assert_eq!(by_name("uxfirst"), None);
+        assert_eq!(by_number(0xcc2), None);
+    }
+
+    #[test]
+    fn check_register_from_json() {
+        let csrs = register_from_json(
+            r#"{ "csr": [{ "name": "mxjson", "number": 3008, "privilege": "mro" }] }"#,
+        )
+        .unwrap();
+        assert_eq!(csrs, vec![Csr(0xbc0, Mro)]);
+        assert_eq!(name_or_number(0xbc0), "mxjson");
+
+        let err = register_from_json(
+            r#"{ "csr": [
+                { "name": "mxjsonok", "number": 3009 },
+                { "name": "mxjsonbig", "number": 4096 }
+            ] }"#,
+        );
+        assert!(err.is_err());
+        assert_eq!(by_name("mxjsonok"), None);
+    }
+}
@@ -0,0 +1,577 @@
+//! Instructions in the `custom-0` and `custom-1` opcode spaces
+//!
+//! The base ISA reserves opcodes `0x0b` and `0x2b` for vendor extensions, so
+//! nothing there can be decoded without knowing the hardware. Instructions in
+//! them are registered here, either in code with [`register`] or from a spec
+//! file with [`register_from_path`], and decode as [`Instr::Custom`].
+//!
+//! The registry needs `std`, so without it these opcodes never decode.
+
+#[cfg(feature = "std")]
+use std::convert::TryFrom;
+#[cfg(feature = "std")]
+use std::sync::RwLock;
+
+#[cfg(feature = "std")]
+use serde::Deserialize;
+
+#[cfg(feature = "std")]
+use crate::decode::Bits;
+#[cfg(feature = "std")]
+use crate::instr::{Arg, Reg};
+use crate::instr::{Args, Instr, RegSet};
+
+/// The major opcode of `custom-0`
+pub const CUSTOM_0: u32 = 0x0b;
+
+/// The major opcode of `custom-1`
+pub const CUSTOM_1: u32 = 0x2b;
+
+/// A custom instruction that can be decoded and disassembled
+///
+/// Every method is given the whole instruction word, so one implementation
+/// may describe a family of instructions that share an encoding.
+pub trait CustomInstr: Send + Sync {
+    /// The all-lowercase mnemonic for `word`
+    fn name(&self, word: u32) -> &str;
+
+    /// Whether `word` encodes this instruction
+    ///
+    /// This is only asked about words with the `custom-0` or `custom-1`
+    /// opcode.
+    fn matches(&self, word: u32) -> bool;
+
+    /// The arguments of `word`, in assembly order
+    ///
+    /// At most [`Args::MAX`] arguments fit. [`Args::push`] hands back any
+    /// more, and collecting into [`Args`] leaves them out.
+    fn args(&self, word: u32) -> Args;
+
+    /// Registers whose values are used by `word`
+    fn reads(&self, _word: u32) -> RegSet {
+        RegSet::new()
+    }
+
+    /// Registers whose values are changed by `word`
+    fn writes(&self, _word: u32) -> RegSet {
+        RegSet::new()
+    }
+}
+
+/// Every instruction added by [`register`], indexed by [`Instr::Custom`]'s `id`
+///
+/// Instructions are never removed, so they're leaked to hand out `'static`
+/// references to them, like the names that [`Instr::name`] returns.
+#[cfg(feature = "std")]
+static CUSTOM: RwLock<Vec<&'static dyn CustomInstr>> = RwLock::new(Vec::new());
+
+/// Add a custom instruction, so that matching words decode as [`Instr::Custom`]
+///
+/// When more than one instruction matches a word, the one registered first
+/// wins. Returns the `id` that the instruction decodes with, or an error if
+/// every `id` is taken.
+#[cfg(feature = "std")]
+pub fn register(instr: impl CustomInstr + 'static) -> crate::Result<u16> {
+    let mut custom = CUSTOM.write().unwrap();
+    let id = u16::try_from(custom.len()).map_err(|_| {
+        format!(
+            "can't register more than {} custom instructions",
+            custom.len()
+        )
+    })?;
+    custom.push(Box::leak(Box::new(instr)));
+    Ok(id)
+}
+
+/// Decode a word in the `custom-0` or `custom-1` opcode space
+///
+/// Without `std` there's no registry, so these never decode.
+pub(crate) fn decode(word: u32) -> Option<Instr> {
+    #[cfg(feature = "std")]
+    {
+        CUSTOM
+            .read()
+            .unwrap()
+            .iter()
+            .position(|instr| instr.matches(word))
+            .map(|id| Instr::Custom {
+                id: id as u16,
+                word,
+            })
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        let _ = word;
+        None
+    }
+}
+
+/// The registered instruction `id`, if there is one
+pub(crate) fn get(id: u16) -> Option<&'static dyn CustomInstr> {
+    #[cfg(feature = "std")]
+    {
+        CUSTOM.read().unwrap().get(id as usize).copied()
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        let _ = id;
+        None
+    }
+}
+
+// ===== Spec files ============================================================
+
+/// Which of the opcode spaces an [`InstrSpec`] is in
+#[cfg(feature = "std")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+pub enum Opcode {
+    #[serde(rename = "custom-0")]
+    Custom0,
+    #[serde(rename = "custom-1")]
+    Custom1,
+}
+
+#[cfg(feature = "std")]
+impl Opcode {
+    /// The bits of the major opcode
+    pub fn bits(self) -> u32 {
+        match self {
+            Opcode::Custom0 => CUSTOM_0,
+            Opcode::Custom1 => CUSTOM_1,
+        }
+    }
+}
+
+/// A field of an [`InstrSpec`] that is shown as an argument
+#[cfg(feature = "std")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operand {
+    /// A destination register, in bits 11 to 7
+    Rd,
+    /// A source register, in bits 19 to 15
+    Rs1,
+    /// A source register, in bits 24 to 20
+    Rs2,
+    /// A source register, in bits 31 to 27 (like `fmadd.s`)
+    Rs3,
+    /// A signed immediate in bits 31 to 20, like `addi`
+    Imm12,
+    /// A signed immediate split between bits 31 to 25 and 11 to 7, like `sw`
+    Simm12,
+    /// An unsigned immediate in bits 19 to 15, like `csrrwi`
+    Zimm5,
+    /// An unsigned immediate in bits 24 to 20, like `slli`
+    Shamt,
+    /// An address in `rs1` plus the immediate in bits 31 to 20, like `lw`
+    Addr,
+}
+
+#[cfg(feature = "std")]
+impl Operand {
+    /// The bits of the word that hold this operand
+    fn mask(self) -> u32 {
+        match self {
+            Operand::Rd => 0x0000_0f80,
+            Operand::Rs1 | Operand::Zimm5 => 0x000f_8000,
+            Operand::Rs2 | Operand::Shamt => 0x01f0_0000,
+            Operand::Rs3 => 0xf800_0000,
+            Operand::Imm12 => 0xfff0_0000,
+            Operand::Simm12 => 0xfe00_0f80,
+            Operand::Addr => 0xffff_8000,
+        }
+    }
+
+    fn reg(word: u32, hi: u8, lo: u8) -> Reg {
+        Reg::try_from(word.bits(hi, lo)).unwrap()
+    }
+
+    /// The value of this operand in `word`
+    fn arg(self, word: u32) -> Arg {
+        match self {
+            Operand::Rd => Operand::reg(word, 11, 7).into(),
+            Operand::Rs1 => Operand::reg(word, 19, 15).into(),
+            Operand::Rs2 => Operand::reg(word, 24, 20).into(),
+            Operand::Rs3 => Operand::reg(word, 31, 27).into(),
+            Operand::Imm12 => word.bits(31, 20).sign_ext(11).into(),
+            Operand::Simm12 => ((word.bits(31, 25) << 5) | word.bits(11, 7))
+                .sign_ext(11)
+                .into(),
+            Operand::Zimm5 => word.bits(19, 15).into(),
+            Operand::Shamt => word.bits(24, 20).into(),
+            Operand::Addr => Arg::Address {
+                base: Operand::reg(word, 19, 15),
+                offset: word.bits(31, 20).sign_ext(11),
+            },
+        }
+    }
+}
+
+/// A custom instruction described in a spec file
+///
+/// Spec files list instructions under `instr`, like:
+/// ```toml
+/// [[instr]]
+/// name = "acc.mac"
+/// opcode = "custom-0"
+/// funct3 = 0
+/// funct7 = 1
+/// operands = ["rd", "rs1", "rs2"]
+/// ```
+///
+/// Words match when their opcode, `funct3`, and `funct7` are the same as the
+/// spec's. Leaving out `funct3` or `funct7` matches any value of them.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InstrSpec {
+    pub name: String,
+    pub opcode: Opcode,
+
+    /// Bits 14 to 12
+    #[serde(default)]
+    pub funct3: Option<u8>,
+
+    /// Bits 31 to 25
+    #[serde(default)]
+    pub funct7: Option<u8>,
+
+    /// The fields shown as arguments, in assembly order
+    #[serde(default)]
+    pub operands: Vec<Operand>,
+}
+
+#[cfg(feature = "std")]
+impl InstrSpec {
+    /// The bits of a word that must equal [`InstrSpec::match_bits`]
+    pub fn mask(&self) -> u32 {
+        let mut mask = 0x7f;
+        if self.funct3.is_some() {
+            mask |= 0x0000_7000;
+        }
+        if self.funct7.is_some() {
+            mask |= 0xfe00_0000;
+        }
+        mask
+    }
+
+    /// The fixed bits of every word that encodes this instruction
+    pub fn match_bits(&self) -> u32 {
+        let funct3 = self.funct3.unwrap_or(0) as u32;
+        let funct7 = self.funct7.unwrap_or(0) as u32;
+        (funct7 << 25) | (funct3 << 12) | self.opcode.bits()
+    }
+
+    /// Check that the fields fit in their bits and don't overlap
+    fn validate(&self) -> crate::Result<()> {
+        if self.funct3.is_some_and(|funct3| funct3 > 0x7) {
+            return Err(format!("funct3 of {} doesn't fit in 3 bits", self.name).into());
+        }
+        if self.funct7.is_some_and(|funct7| funct7 > 0x7f) {
+            return Err(format!("funct7 of {} doesn't fit in 7 bits", self.name).into());
+        }
+
+        let mut used = self.mask();
+        for operand in &self.operands {
+            if used & operand.mask() != 0 {
+                return Err(format!(
+                    "operand {:?} of {} overlaps another field",
+                    operand, self.name
+                )
+                .into());
+            }
+            used |= operand.mask();
+        }
+        Ok(())
+    }
+
+    fn registers(&self, word: u32, operands: &[Operand]) -> RegSet {
+        self.operands
+            .iter()
+            .filter(|operand| operands.contains(operand))
+            .filter_map(|operand| match operand.arg(word) {
+                Arg::Register(reg) => Some(reg),
+                Arg::Address { base, .. } => Some(base),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "std")]
+impl CustomInstr for InstrSpec {
+    fn name(&self, _word: u32) -> &str {
+        &self.name
+    }
+
+    fn matches(&self, word: u32) -> bool {
+        word & self.mask() == self.match_bits()
+    }
+
+    fn args(&self, word: u32) -> Args {
+        // Operands can't overlap, so no more than `Args::MAX` fit in a word
+        self.operands
+            .iter()
+            .map(|operand| operand.arg(word))
+            .collect()
+    }
+
+    fn reads(&self, word: u32) -> RegSet {
+        use Operand::*;
+        self.registers(word, &[Rs1, Rs2, Rs3, Addr])
+    }
+
+    fn writes(&self, word: u32) -> RegSet {
+        let mut writes = self.registers(word, &[Operand::Rd]);
+        writes.remove(Reg::Zero);
+        writes
+    }
+}
+
+/// The contents of a file of [`InstrSpec`]s
+#[cfg(feature = "std")]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpecFile {
+    #[serde(default)]
+    instr: Vec<InstrSpec>,
+}
+
+/// Register every instruction described in `specs`, checking them first
+#[cfg(feature = "std")]
+fn register_specs(specs: Vec<InstrSpec>) -> crate::Result<Vec<u16>> {
+    for spec in &specs {
+        spec.validate()?;
+    }
+    specs.into_iter().map(register).collect()
+}
+
+/// Register every instruction described in `text`, in TOML
+///
+/// See [`InstrSpec`] for the format.
+#[cfg(feature = "std")]
+pub fn register_from_toml(text: &str) -> crate::Result<Vec<u16>> {
+    let file: SpecFile = toml::from_str(text)?;
+    register_specs(file.instr)
+}
+
+/// Register every instruction described in `text`, in JSON
+///
+/// This is the same format as [`register_from_toml`], like
+/// `{ "instr": [{ "name": "acc.clr", "opcode": "custom-1" }] }`.
+#[cfg(feature = "std")]
+pub fn register_from_json(text: &str) -> crate::Result<Vec<u16>> {
+    let file: SpecFile = serde_json::from_str(text)?;
+    register_specs(file.instr)
+}
+
+/// Register every instruction described in the file at `path`
+///
+/// Files ending in `.json` are read as JSON, and everything else as TOML.
+#[cfg(feature = "std")]
+pub fn register_from_path(path: impl AsRef<std::path::Path>) -> crate::Result<Vec<u16>> {
+    let path = path.as_ref();
+    let text =
+        std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let ids = if path.extension().is_some_and(|ext| ext == "json") {
+        register_from_json(&text)
+    } else {
+        register_from_toml(&text)
+    };
+    ids.map_err(|err| format!("{}: {}", path.display(), err).into())
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    use crate::prelude::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    // The registry is shared by every test, so each test uses its own funct3
+    // in custom-0 or custom-1.
+
+    /// A hand-written decoder for `tst.sum rd, rs1, ...`, where funct7 is a
+    /// count of extra registers after rs1
+    struct Sum;
+
+    impl CustomInstr for Sum {
+        fn name(&self, _word: u32) -> &str {
+            "tst.sum"
+        }
+
+        fn matches(&self, word: u32) -> bool {
+            word & 0x707f == (0x7 << 12) | CUSTOM_1
+        }
+
+        fn args(&self, word: u32) -> Args {
+            let first = word.bits(19, 15);
+            let mut args: Args = [Operand::Rd.arg(word), Operand::Rs1.arg(word)].into();
+            for n in 1..=word.bits(31, 25) {
+                if args
+                    .push(Reg::try_from((first + n) % 32).unwrap().into())
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            args
+        }
+    }
+
+    #[test]
+    fn check_register() {
+        let id = register(Sum).unwrap();
+
+        // tst.sum a0, a1, +2 more
+        let word = (2 << 25) | (11 << 15) | (0x7 << 12) | (10 << 7) | CUSTOM_1;
+        let instr = decode_opcode(word).unwrap();
+        assert_eq!(instr, Instr::Custom { id, word });
+        assert_eq!(instr.name(), "tst.sum");
+
+        let args: Vec<String> = instr.args().iter().map(|arg| arg.to_string()).collect();
+        assert_eq!(args, ["a0", "a1", "a2", "a3"]);
+
+        // tst.sum a0, a1, +5 more, which only has room for two of them
+        let word = (5 << 25) | (11 << 15) | (0x7 << 12) | (10 << 7) | CUSTOM_1;
+        let instr = decode_opcode(word).unwrap();
+        assert_eq!(instr, Instr::Custom { id, word });
+        let args: Vec<String> = instr.args().iter().map(|arg| arg.to_string()).collect();
+        assert_eq!(args, ["a0", "a1", "a2", "a3"]);
+
+        // Nothing was said about registers
+        assert!(instr.reads().is_empty());
+        assert!(instr.writes().is_empty());
+        assert_eq!(instr.extension(), Extension::Custom);
+
+        // The same funct3 in custom-0 isn't registered
+        assert_eq!(decode_opcode(word ^ CUSTOM_0 ^ CUSTOM_1), None);
+    }
+
+    #[test]
+    fn check_register_from_toml() {
+        let ids = register_from_toml(
+            r#"
+            [[instr]]
+            name = "acc.mac"
+            opcode = "custom-0"
+            funct3 = 1
+            funct7 = 0
+            operands = ["rd", "rs1", "rs2"]
+
+            [[instr]]
+            name = "acc.ld"
+            opcode = "custom-0"
+            funct3 = 2
+            operands = ["rd", "addr"]
+
+            [[instr]]
+            name = "acc.st"
+            opcode = "custom-0"
+            funct3 = 3
+            operands = ["rs2", "simm12", "zimm5"]
+            "#,
+        )
+        .unwrap();
+
+        // acc.mac a0, a1, a2
+        let mac = decode_opcode(0x00c5_950b).unwrap();
+        assert_eq!(
+            mac,
+            Instr::Custom {
+                id: ids[0],
+                word: 0x00c5_950b
+            }
+        );
+        assert_eq!(mac.name(), "acc.mac");
+        let reads: Vec<Reg> = mac.reads().iter().collect();
+        assert_eq!(reads, [Reg::A1, Reg::A2]);
+        let writes: Vec<Reg> = mac.writes().iter().collect();
+        assert_eq!(writes, [Reg::A0]);
+
+        // funct7 is fixed, so this isn't acc.mac
+        assert_eq!(decode_opcode(0x02c5_950b), None);
+
+        // acc.ld t0, -8(sp)
+        let ld = decode_opcode(0xff81_228b).unwrap();
+        assert_eq!(ld.name(), "acc.ld");
+        assert_eq!(
+            ld.args().as_slice(),
+            [
+                Arg::Register(Reg::T0),
+                Arg::Address {
+                    base: Reg::Sp,
+                    offset: -8
+                }
+            ]
+        );
+        let reads: Vec<Reg> = ld.reads().iter().collect();
+        assert_eq!(reads, [Reg::Sp]);
+
+        // acc.st a5, -4, 3
+        let st = decode_opcode(0xfef1_be8b).unwrap();
+        assert_eq!(st.name(), "acc.st");
+        assert_eq!(
+            st.args().as_slice(),
+            [
+                Arg::Register(Reg::A5),
+                Arg::SignedImm(-3),
+                Arg::UnsignedImm(3)
+            ]
+        );
+    }
+
+    #[test]
+    fn check_register_from_json() {
+        let ids = register_from_json(
+            r#"{ "instr": [{ "name": "acc.clr", "opcode": "custom-1", "funct3": 4 }] }"#,
+        )
+        .unwrap();
+
+        let word = (0x4 << 12) | CUSTOM_1;
+        assert_eq!(
+            decode_opcode(word),
+            Some(Instr::Custom { id: ids[0], word })
+        );
+        assert!(decode_opcode(word).unwrap().args().is_empty());
+    }
+
+    #[test]
+    fn check_invalid_specs() {
+        let err = register_from_toml(
+            r#"
+            [[instr]]
+            name = "bad.overlap"
+            opcode = "custom-1"
+            funct7 = 0
+            operands = ["rd", "imm12"]
+            "#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "operand Imm12 of bad.overlap overlaps another field"
+        );
+
+        assert!(register_from_toml(
+            r#"
+            [[instr]]
+            name = "bad.opcode"
+            opcode = "op-imm"
+            "#,
+        )
+        .is_err());
+
+        assert!(register_from_toml(
+            r#"
+            [[instr]]
+            name = "bad.funct3"
+            opcode = "custom-1"
+            funct3 = 8
+            "#,
+        )
+        .is_err());
+    }
+}
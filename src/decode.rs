@@ -1,12 +1,12 @@
 #![deny(unreachable_patterns)]
 
+use crate::custom;
 use crate::instr::{Instr, Reg};
+use crate::opcodes::Mnemonic;
 use Instr::*;
 
-use std::convert::TryInto;
-
 /// Internal trait to simplify bit operations
-trait Bits {
+pub(crate) trait Bits {
     type Signed;
 
     /// Extract the bit at index `idx`
@@ -46,6 +46,13 @@ impl Bits for u32 {
     }
 }
 
+/// Decode a 32-bit instruction, with the encodings in [`crate::opcodes`]
+///
+/// Returns `None` for words that aren't a supported instruction.
+///
+/// This is always inlined, so that decoding a whole section doesn't pay for a
+/// call per word.
+#[inline(always)]
 pub fn decode_opcode(word: u32) -> Option<Instr> {
     /*
       Different instructions may use different named fields in the enoding,
@@ -85,282 +92,402 @@ pub fn decode_opcode(word: u32) -> Option<Instr> {
         +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
     */
 
-    // We extract each field value here, then reference them in the
-    // larger match block below.
+    // One leaf per funct3 and opcode, and per funct7 where that matters,
+    // generated by `build.rs` from the same tables as `opcodes::find`
+    include!(concat!(env!("OUT_DIR"), "/decode.rs"))
+}
+
+/// Words that no encoding in the tables matches
+#[inline(always)]
+fn unmatched(word: u32) -> Option<Instr> {
+    // The all-zero instruction is special-cased as illegal, so we handle
+    // it here like an instruction. For the rest of our decoding, we'll
+    // handle invalid instructions like an error.
+    if word == 0x0 {
+        return Some(Illegal);
+    }
+
+    // Vendor extensions aren't in the tables, and are registered at runtime
     let opcode = word.bits(6, 0);
-    let funct3 = word.bits(14, 12);
-    let rd_idx = word.bits(11, 7) as u8;
-    let rs2_idx = word.bits(24, 20) as u8;
-    let rs1_idx = word.bits(19, 15) as u8;
-    let funct7 = word.bits(31, 25);
-    let funct12 = word.bits(31, 20);
-    let successor = word.bits(27, 24) as u8;
-    let predecessor = word.bits(23, 20) as u8;
-    let fm = word.bits(31, 28) as u8;
+    if opcode == custom::CUSTOM_0 || opcode == custom::CUSTOM_1 {
+        return custom::decode(word);
+    }
+    None
+}
 
-    // csr is a lot like funct12/I-type immediates, but it is zero-extended
-    let csr = funct12 as u16;
+/// The instruction `mnemonic` with the fields in `word`
+///
+/// `word` must match the encoding of `mnemonic`. Every leaf of the decoder
+/// passes a constant, so this match is resolved at compile time.
+#[inline(always)]
+fn instr(mnemonic: Mnemonic, word: u32) -> Instr {
+    // The fixed bits of each instruction come from riscv-opcodes, so once
+    // we know which instruction this is, its fields are all valid. Each arm
+    // only extracts the fields that it uses.
+    // Every encoding there has a match arm, so adding one to the tables
+    // won't build until it has an `Instr` too.
+    match mnemonic {
+        Mnemonic::Lui => Lui {
+            rd: rd(word),
+            imm: u_imm(word),
+        },
+        Mnemonic::Auipc => Auipc {
+            rd: rd(word),
+            imm: u_imm(word),
+        },
 
-    let rd: Reg = rd_idx.try_into().unwrap_or(Reg::Zero);
-    let rs2: Reg = rs2_idx.try_into().unwrap_or(Reg::Zero);
-    let rs1: Reg = rs1_idx.try_into().unwrap_or(Reg::Zero);
+        // Note: Jal uses J-type encoding, but Jalr uses I-type encoding
+        Mnemonic::Jal => Jal {
+            rd: rd(word),
+            imm: j_imm(word),
+        },
+        Mnemonic::Jalr => Jalr {
+            rd: rd(word),
+            rs1: rs1(word),
+            imm: i_imm(word),
+        },
 
-    // TODO: Some filler instruction parsing still use these
-    let imm5: u8 = 0;
-    let imm12: i32 = 0;
+        Mnemonic::Beq => Beq {
+            rs1: rs1(word),
+            rs2: rs2(word),
+            imm: b_imm(word),
+        },
+        Mnemonic::Bne => Bne {
+            rs1: rs1(word),
+            rs2: rs2(word),
+            imm: b_imm(word),
+        },
+        Mnemonic::Blt => Blt {
+            rs1: rs1(word),
+            rs2: rs2(word),
+            imm: b_imm(word),
+        },
+        Mnemonic::Bge => Bge {
+            rs1: rs1(word),
+            rs2: rs2(word),
+            imm: b_imm(word),
+        },
+        Mnemonic::Bltu => Bltu {
+            rs1: rs1(word),
+            rs2: rs2(word),
+            imm: b_imm(word),
+        },
+        Mnemonic::Bgeu => Bgeu {
+            rs1: rs1(word),
+            rs2: rs2(word),
+            imm: b_imm(word),
+        },
 
-    // R-type instructions do not have an immediate encoded
-    let _r_imm: ();
+        // Load Instructions
+        Mnemonic::Lb => Lb {
+            rd: rd(word),
+            rs1: rs1(word),
+            imm: i_imm(word),
+        },
+        Mnemonic::Lh => Lh {
+            rd: rd(word),
+            rs1: rs1(word),
+            imm: i_imm(word),
+        },
+        Mnemonic::Lw => Lw {
+            rd: rd(word),
+            rs1: rs1(word),
+            imm: i_imm(word),
+        },
+        Mnemonic::Ld => Ld {
+            rd: rd(word),
+            rs1: rs1(word),
+            imm: i_imm(word),
+        },
+        Mnemonic::Lbu => Lbu {
+            rd: rd(word),
+            rs1: rs1(word),
+            imm: i_imm(word) as u32,
+        },
+        Mnemonic::Lhu => Lhu {
+            rd: rd(word),
+            rs1: rs1(word),
+            imm: i_imm(word) as u32,
+        },
+        Mnemonic::Lwu => Lwu {
+            rd: rd(word),
+            rs1: rs1(word),
+            imm: i_imm(word) as u32,
+        },
 
-    let i_imm: i32 = word.bits(31, 20).sign_ext(11);
+        // Store Instructions
+        Mnemonic::Sb => Sb {
+            rs1: rs1(word),
+            rs2: rs2(word),
+            imm: s_imm(word),
+        },
+        Mnemonic::Sh => Sh {
+            rs1: rs1(word),
+            rs2: rs2(word),
+            imm: s_imm(word),
+        },
+        Mnemonic::Sw => Sw {
+            rs1: rs1(word),
+            rs2: rs2(word),
+            imm: s_imm(word),
+        },
+        Mnemonic::Sd => Sd {
+            rs1: rs1(word),
+            rs2: rs2(word),
+            imm: s_imm(word),
+        },
 
-    let s_imm: i32 = ((word.bits(31, 25) << 5) | word.bits(11, 7)).sign_ext(11);
+        Mnemonic::Addi => Addi {
+            rd: rd(word),
+            rs1: rs1(word),
+            imm: i_imm(word),
+        },
+        Mnemonic::Slti => Slti {
+            rd: rd(word),
+            rs1: rs1(word),
+            imm12: i_imm(word),
+        },
+        Mnemonic::Sltiu => Sltiu {
+            rd: rd(word),
+            rs1: rs1(word),
+            imm12: i_imm(word),
+        },
+        Mnemonic::Xori => Xori {
+            rd: rd(word),
+            rs1: rs1(word),
+            imm12: i_imm(word),
+        },
+        Mnemonic::Ori => Ori {
+            rd: rd(word),
+            rs1: rs1(word),
+            imm12: i_imm(word),
+        },
+        Mnemonic::Andi => Andi {
+            rd: rd(word),
+            rs1: rs1(word),
+            imm: i_imm(word),
+        },
+        Mnemonic::Slli => Slli {
+            rd: rd(word),
+            rs1: rs1(word),
+            imm5: shamt(word),
+        },
+        Mnemonic::Srli => Srli {
+            rd: rd(word),
+            rs1: rs1(word),
+            imm5: shamt(word),
+        },
+        Mnemonic::Srai => Srai {
+            rd: rd(word),
+            rs1: rs1(word),
+            imm5: shamt(word),
+        },
+
+        Mnemonic::Add => Add {
+            rd: rd(word),
+            rs1: rs1(word),
+            rs2: rs2(word),
+        },
+        Mnemonic::Sub => Sub {
+            rd: rd(word),
+            rs1: rs1(word),
+            rs2: rs2(word),
+        },
+        Mnemonic::Sll => Sll {
+            rd: rd(word),
+            rs1: rs1(word),
+            rs2: rs2(word),
+        },
+        Mnemonic::Slt => Slt {
+            rd: rd(word),
+            rs1: rs1(word),
+            rs2: rs2(word),
+        },
+        Mnemonic::Sltu => Sltu {
+            rd: rd(word),
+            rs1: rs1(word),
+            rs2: rs2(word),
+        },
+        Mnemonic::Xor => Xor {
+            rd: rd(word),
+            rs1: rs1(word),
+            rs2: rs2(word),
+        },
+        Mnemonic::Srl => Srl {
+            rd: rd(word),
+            rs1: rs1(word),
+            rs2: rs2(word),
+        },
+        Mnemonic::Sra => Sra {
+            rd: rd(word),
+            rs1: rs1(word),
+            rs2: rs2(word),
+        },
+        Mnemonic::Or => Or {
+            rd: rd(word),
+            rs1: rs1(word),
+            rs2: rs2(word),
+        },
+        Mnemonic::And => And {
+            rd: rd(word),
+            rs1: rs1(word),
+            rs2: rs2(word),
+        },
+
+        // Fences
+        Mnemonic::Fence => Fence {
+            rd: rd(word),
+            rs1: rs1(word),
+            successor: successor(word),
+            predecessor: predecessor(word),
+            fm: fm(word),
+        },
+        Mnemonic::FenceI => FenceI {
+            rd: rd(word),
+            rs1: rs1(word),
+            imm12: i_imm(word),
+        },
 
-    #[rustfmt::skip]
-    let b_imm: i32 = (
+        // SYSTEM opcodes
+        Mnemonic::Ecall => Ecall {
+            rd: rd(word),
+            rs1: rs1(word),
+        },
+        Mnemonic::Ebreak => Ebreak {
+            rd: rd(word),
+            rs1: rs1(word),
+        },
+        Mnemonic::Uret => Uret {},
+        Mnemonic::Sret => Sret {},
+        Mnemonic::Mret => Mret {},
+        Mnemonic::Wfi => Wfi {},
+
+        Mnemonic::Csrrw => Csrrw {
+            rd: rd(word),
+            rs1: rs1(word),
+            csr: csr(word),
+        },
+        Mnemonic::Csrrs => Csrrs {
+            rd: rd(word),
+            rs1: rs1(word),
+            csr: csr(word),
+        },
+        Mnemonic::Csrrc => Csrrc {
+            rd: rd(word),
+            rs1: rs1(word),
+            csr: csr(word),
+        },
+        Mnemonic::Csrrwi => Csrrwi {
+            rd: rd(word),
+            src: zimm(word),
+            csr: csr(word),
+        },
+        Mnemonic::Csrrsi => Csrrsi {
+            rd: rd(word),
+            src: zimm(word),
+            csr: csr(word),
+        },
+        Mnemonic::Csrrci => Csrrci {
+            rd: rd(word),
+            src: zimm(word),
+            csr: csr(word),
+        },
+    }
+}
+
+// Each field of an instruction, extracted from the word that encodes it.
+// These are small enough to inline into every arm of `decode_opcode`, so
+// that no arm pays for fields that it doesn't use.
+
+#[inline(always)]
+fn rd(word: u32) -> Reg {
+    Reg::from_u5(word.bits(11, 7))
+}
+
+#[inline(always)]
+fn rs1(word: u32) -> Reg {
+    Reg::from_u5(word.bits(19, 15))
+}
+
+#[inline(always)]
+fn rs2(word: u32) -> Reg {
+    Reg::from_u5(word.bits(24, 20))
+}
+
+/// The immediate source of `csrr*i`, which sits where rs1 usually does
+#[inline(always)]
+fn zimm(word: u32) -> u8 {
+    word.bits(19, 15) as u8
+}
+
+/// Shift amounts are stored in the low bits of I-type immediates
+#[inline(always)]
+fn shamt(word: u32) -> u8 {
+    word.bits(24, 20) as u8
+}
+
+/// csr is a lot like funct12/I-type immediates, but it is zero-extended
+#[inline(always)]
+fn csr(word: u32) -> u16 {
+    word.bits(31, 20) as u16
+}
+
+#[inline(always)]
+fn predecessor(word: u32) -> u8 {
+    word.bits(27, 24) as u8
+}
+
+#[inline(always)]
+fn successor(word: u32) -> u8 {
+    word.bits(23, 20) as u8
+}
+
+#[inline(always)]
+fn fm(word: u32) -> u8 {
+    word.bits(31, 28) as u8
+}
+
+// R-type instructions do not have an immediate encoded
+
+#[inline(always)]
+fn i_imm(word: u32) -> i32 {
+    word.bits(31, 20).sign_ext(11)
+}
+
+#[inline(always)]
+fn s_imm(word: u32) -> i32 {
+    ((word.bits(31, 25) << 5) | word.bits(11, 7)).sign_ext(11)
+}
+
+#[inline(always)]
+#[rustfmt::skip]
+fn b_imm(word: u32) -> i32 {
+    (
         (word.bit(31) << 12)           // ┌ Note: lsb is always 0!
         | (word.bit(7) << 11)          // │
         | (word.bits(30, 25) << 5)     // │
         | (word.bits(11, 8) << 1)      // ┘
     )
-    .sign_ext(12);
+    .sign_ext(12)
+}
 
-    // U-type encodings may shift this by 12, or may keep it as-is but
-    // reinterpret the bits as the upper 20-bits of a word.
-    let u_imm: u32 = word.bits(31, 12);
+/// U-type encodings may shift this by 12, or may keep it as-is but
+/// reinterpret the bits as the upper 20-bits of a word.
+#[inline(always)]
+fn u_imm(word: u32) -> u32 {
+    word.bits(31, 12)
+}
 
-    #[rustfmt::skip]
-    let j_imm: i32 = (
+#[inline(always)]
+#[rustfmt::skip]
+fn j_imm(word: u32) -> i32 {
+    (
         (word.bit(31) << 20)           // ┌ Note: lsb is always 0!
         | (word.bits(19, 12) << 12)    // │
         | (word.bit(20) << 11)         // │
         | (word.bits(30, 21) << 1)     // ┘
     )
-    .sign_ext(20);
-
-    // Print some useful state so that it's visible when a unit test fails.
-    // This kind of printing is extremely expensive compared to decoding,
-    // so it is worth it to only enable this on a debug build.
-    // Debug builds already suffer performance issues in this function because
-    // none of the function calls here (*none* of them) are inlined!
-    if cfg!(test) && cfg!(debug_asserts) {
-        println!("=== DECODE STATE ===");
-        println!("word    0x{bits:08x} 0b{bits:032b} {:>12}", bits = word);
-        println!("opcode  0x{bits:08x} 0b{bits:032b} {:>12}", bits = opcode);
-        println!("funct3  0x{bits:08x} 0b{bits:032b} {:>12}", bits = funct3);
-        println!("funct7  0x{bits:08x} 0b{bits:032b} {:>12}", bits = funct7);
-        println!("funct12 0x{bits:08x} 0b{bits:032b} {:>12}", bits = funct12);
-        #[rustfmt::skip]
-        println!("succ.   0x{bits:08x} 0b{bits:032b} {:>12}", bits = successor);
-        #[rustfmt::skip]
-        println!("predec. 0x{bits:08x} 0b{bits:032b} {:>12}", bits = predecessor);
-        println!("fm 0x{bits:08x} 0b{bits:032b} {:>12}", bits = fm);
-        println!();
-
-        println!("i_imm   0x{bits:08x} 0b{bits:032b} {:>12}", bits = i_imm);
-        println!("s_imm   0x{bits:08x} 0b{bits:032b} {:>12}", bits = s_imm);
-        println!("b_imm   0x{bits:08x} 0b{bits:032b} {:>12}", bits = b_imm);
-        println!("u_imm   0x{bits:08x} 0b{bits:032b} {:>12}", bits = u_imm);
-        println!("j_imm   0x{bits:08x} 0b{bits:032b} {:>12}", bits = j_imm);
-        println!();
-
-        println!("rd      {:?}", rd);
-        println!("rd_idx  0x{bits:08x} 0b{bits:032b} {:>5}", bits = rd_idx);
-        println!("rs2     {:?}", rs2);
-        println!("rs2_idx 0x{bits:08x} 0b{bits:032b} {:>5}", bits = rs2_idx);
-        println!("rs1     {:?}", rs1);
-        println!("rs1_idx 0x{bits:08x} 0b{bits:032b} {:>5}", bits = rs1_idx);
-        println!();
-    }
-
-    match (opcode, funct3) {
-        // Special values
-        _ if word == 0x0 => {
-            // The all-zero instruction is special-cased as illegal, so we handle
-            // it here like an instruction. For the rest of our decoding, we'll handle
-            // invalid instructions like an error.
-            Some(Illegal)
-        }
-
-        // Load Instructions
-        (0x03, 0x0) => Some(Lb {
-            rd,
-            rs1,
-            imm: i_imm,
-        }),
-        (0x03, 0x1) => Some(Lh {
-            rd,
-            rs1,
-            imm: i_imm,
-        }),
-        (0x03, 0x2) => Some(Lw {
-            rd,
-            rs1,
-            imm: i_imm,
-        }),
-        (0x03, 0x3) => Some(Ld {
-            rd,
-            rs1,
-            imm: i_imm,
-        }),
-        (0x03, 0x4) => Some(Lbu {
-            rd,
-            rs1,
-            imm: i_imm as u32,
-        }),
-        (0x03, 0x5) => Some(Lhu {
-            rd,
-            rs1,
-            imm: i_imm as u32,
-        }),
-        (0x03, 0x6) => Some(Lwu {
-            rd,
-            rs1,
-            imm: i_imm as u32,
-        }),
-
-        // Fences
-        (0x0f, 0x0) => Some(Fence {
-            rd,
-            rs1,
-            successor,
-            predecessor,
-            fm,
-        }),
-        (0x0f, 0x1) => Some(FenceI { rd, rs1, imm12 }),
-
-        (0x13, 0x0) => Some(Addi {
-            rd,
-            rs1,
-            imm: i_imm,
-        }),
-        (0x13, 0x1) if funct7 == 0x00 => Some(Slli {
-            rd,
-            rs1,
-            imm5: i_imm as u8,
-        }),
-        (0x13, 0x2) => Some(Slti { rd, rs1, imm12 }),
-        (0x13, 0x3) => Some(Sltiu { rd, rs1, imm12 }),
-        (0x13, 0x4) => Some(Xori { rd, rs1, imm12 }),
-        (0x13, 0x5) if funct7 == 0x00 => Some(Srli { rd, rs1, imm5 }),
-        (0x13, 0x5) if funct7 == 0x20 => Some(Srai { rd, rs1, imm5 }),
-        (0x13, 0x6) => Some(Ori { rd, rs1, imm12 }),
-        (0x13, 0x7) => Some(Andi {
-            rd,
-            rs1,
-            imm: i_imm,
-        }),
-
-        (0x17, _) => Some(Auipc { rd, imm: u_imm }),
-
-        // // Store Instructions
-        (0x23, 0x0) => Some(Sb {
-            rs1,
-            rs2,
-            imm: s_imm,
-        }),
-        (0x23, 0x1) => Some(Sh {
-            rs1,
-            rs2,
-            imm: s_imm,
-        }),
-        (0x23, 0x2) => Some(Sw {
-            rs1,
-            rs2,
-            imm: s_imm,
-        }),
-        (0x23, 0x3) => Some(Sd {
-            rs1,
-            rs2,
-            imm: s_imm,
-        }),
-
-        (0x33, 0x0) if funct7 == 0x00 => Some(Add { rd, rs1, rs2 }),
-        (0x33, 0x0) if funct7 == 0x20 => Some(Sub { rd, rs1, rs2 }),
-        (0x33, 0x1) => Some(Sll { rd, rs1, rs2 }),
-        (0x33, 0x2) => Some(Slt { rd, rs1, rs2 }),
-        (0x33, 0x3) => Some(Sltu { rd, rs1, rs2 }),
-        (0x33, 0x4) => Some(Xor { rd, rs1, rs2 }),
-        (0x33, 0x5) if funct7 == 0x00 => Some(Srl { rd, rs1, rs2 }),
-        (0x33, 0x5) if funct7 == 0x20 => Some(Sra { rd, rs1, rs2 }),
-        (0x33, 0x6) => Some(Or { rd, rs1, rs2 }),
-        (0x33, 0x7) => Some(And { rd, rs1, rs2 }),
-
-        (0x37, _) => Some(Lui { rd, imm: u_imm }),
-
-        (0x63, 0x0) => Some(Beq {
-            rs1,
-            rs2,
-            imm: b_imm,
-        }),
-        (0x63, 0x1) => Some(Bne {
-            rs1,
-            rs2,
-            imm: b_imm,
-        }),
-        (0x63, 0x4) => Some(Blt {
-            rs1,
-            rs2,
-            imm: b_imm,
-        }),
-        (0x63, 0x5) => Some(Bge {
-            rs1,
-            rs2,
-            imm: b_imm,
-        }),
-        (0x63, 0x6) => Some(Bltu {
-            rs1,
-            rs2,
-            imm: b_imm,
-        }),
-        (0x63, 0x7) => Some(Bgeu {
-            rs1,
-            rs2,
-            imm: b_imm,
-        }),
-
-        // Note: Jal uses J-type encoding, but Jalr uses I-type encoding
-        (0x67, 0x0) => Some(Jalr {
-            rd,
-            rs1,
-            imm: i_imm,
-        }),
-        (0x6f, _) => Some(Jal { rd, imm: j_imm }),
-
-        // SYSTEM opcodes
-        (0x73, 0x0) => match (funct7, rs2_idx) {
-            (0x00, 0x2) => Some(Uret {}),
-            (0x08, 0x2) => Some(Sret {}),
-            (0x18, 0x2) => Some(Mret {}),
-
-            (0x08, 0x5) => Some(Wfi {}),
-
-            (0x00, 0x0) => Some(Ebreak { rd, rs1 }),
-            (0x00, 0x1) => Some(Ecall { rd, rs1 }),
-
-            _ => None,
-        },
-
-        (0x73, 0x1) => Some(Csrrw { rd, rs1, csr }),
-        (0x73, 0x2) => Some(Csrrs { rd, rs1, csr }),
-        (0x73, 0x3) => Some(Csrrc { rd, rs1, csr }),
-        (0x73, 0x5) => Some(Csrrwi {
-            rd,
-            src: rs1_idx,
-            csr,
-        }),
-        (0x73, 0x6) => Some(Csrrsi {
-            rd,
-            src: rs1_idx,
-            csr,
-        }),
-        (0x73, 0x7) => Some(Csrrci {
-            rd,
-            src: rs1_idx,
-            csr,
-        }),
-        _ => None,
-    }
+    .sign_ext(20)
 }
 
 #[cfg(test)]
@@ -368,6 +495,7 @@ mod test {
     use super::*;
 
     use crate::csr;
+    use crate::opcodes;
     use Reg::*;
 
     // These asserts give us diffs when they fail.
@@ -469,18 +597,58 @@ mod test {
     }
 
     // This test takes takes too long, while the rest are instant.
-    /// (~15 seconds on release and ~1238 seconds (yes really) on debug)
-    // Until this is sped up, mark it as ignored. We can run it manually:
+    // (~10 seconds on release, and several times that on debug)
+    // Mark it as ignored. We can run it manually:
     //      time cargo test --release brute -- --ignored
+    // For decoding speed, see `cargo bench` instead.
     #[test]
     #[ignore]
     fn brute_force_decode() {
         // Test every word to make sure that we don't panic :)
         for word in 0..=u32::MAX {
-            let _ = decode_opcode(word);
+            std::hint::black_box(decode_opcode(word));
+        }
+    }
+
+    #[test]
+    fn check_mulh_is_not_sll() {
+        // mulh a0, a1, a2 only differs from sll in funct7
+        let word = u32::from_le_bytes([0x33, 0x95, 0xc5, 0x02]);
+        assert_eq!(decode_opcode(word), None);
+    }
+
+    #[test]
+    fn check_dispatch_matches_find() {
+        // The generated leaves must decode the same words as the tables
+        let check = |word: u32| {
+            let expected = opcodes::find(word).map(|encoding| instr(encoding.mnemonic, word));
+            assert_eq!(decode_opcode(word), expected, "{:#010x}", word);
+        };
+
+        // `fence.tso` is a `fence` in the tables, but has its own name
+        check(0x8330_000f);
+
+        let mut random: u32 = 1;
+        for _ in 0..10_000 {
+            random ^= random << 13;
+            random ^= random >> 17;
+            random ^= random << 5;
+
+            for encoding in opcodes::ENCODINGS {
+                check((random & !0x7f) | (encoding.match_bits & 0x7f));
+            }
         }
     }
 
+    #[test]
+    fn check_reserved_fields() {
+        // ecall with rd = a0, which the spec reserves
+        assert_eq!(decode_opcode(0x0000_0573), None);
+
+        // slli a0, a1, 32 is only valid on RV64
+        assert_eq!(decode_opcode(0x0205_9513), None);
+    }
+
     macro_rules! make_instr_test {
         ( $( $test_name:ident : $le_bytes:expr => $expected:expr ),+ ) => {
             $(
@@ -564,6 +732,15 @@ mod test {
             fm: 0
         },
 
+        // Fence  r, w
+        check_fence_r_w:                [0x0f, 0x00, 0x10, 0x02] => Fence {
+            rd: Zero,
+            rs1: Zero,
+            successor: 0b_0001,
+            predecessor: 0b_0010,
+            fm: 0
+        },
+
         check_j_0:                      [0x6f, 0x00, 0x00, 0x00] => Jal { rd: Zero, imm: 0 },
         check_j_900:                    [0x6f, 0x00, 0x40, 0x38] => Jal { rd: Zero, imm: 900 },
 
@@ -594,6 +771,20 @@ mod test {
 
         check_slli_a0_a0_2:             [0x13, 0x15, 0x25, 0x00] => Slli { rd: A0, rs1: A0, imm5: 2 },
 
+        check_slli_a0_a1_17:            [0x13, 0x95, 0x15, 0x01] => Slli { rd: A0, rs1: A1, imm5: 17 },
+        check_srli_a0_a1_31:            [0x13, 0xd5, 0xf5, 0x01] => Srli { rd: A0, rs1: A1, imm5: 31 },
+        check_srai_a0_a1_3:             [0x13, 0xd5, 0x35, 0x40] => Srai { rd: A0, rs1: A1, imm5: 3 },
+
+        check_slti_a0_a1_neg_5:         [0x13, 0xa5, 0xb5, 0xff] => Slti { rd: A0, rs1: A1, imm12: -5 },
+        check_sltiu_a0_a1_7:            [0x13, 0xb5, 0x75, 0x00] => Sltiu { rd: A0, rs1: A1, imm12: 7 },
+
+        // Not  a0, a1
+        check_xori_a0_a1_neg_1:         [0x13, 0xc5, 0xf5, 0xff] => Xori { rd: A0, rs1: A1, imm12: -1 },
+        check_ori_a0_a1_2047:           [0x13, 0xe5, 0xf5, 0x7f] => Ori { rd: A0, rs1: A1, imm12: 2047 },
+
+        check_ecall:                    [0x73, 0x00, 0x00, 0x00] => Ecall { rd: Zero, rs1: Zero },
+        check_ebreak:                   [0x73, 0x00, 0x10, 0x00] => Ebreak { rd: Zero, rs1: Zero },
+
         check_sub_sp_sp_t0:             [0x33, 0x01, 0x51, 0x40] => Sub { rd: Sp, rs1: Sp, rs2: T0 },
 
         // Wfi
@@ -1,7 +1,12 @@
-use std::collections::HashMap;
+use std::cell::OnceCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
 use std::fs;
 use std::path::Path;
 
+use goblin::elf::header::ET_REL;
+use goblin::elf::section_header::{SHF_EXECINSTR, SHT_PROGBITS};
+use goblin::elf::sym::{STB_GLOBAL, STB_WEAK, STT_FUNC};
 use goblin::{elf::Elf, Object};
 
 use crate::prelude::*;
@@ -13,7 +18,22 @@ use crate::prelude::*;
 /// Assembly can fail with link errors if symbols are referenced without a
 /// definition.
 pub struct Disassembly {
-    entries: HashMap<u32, Entry>,
+    entries: BTreeMap<u32, Entry>,
+
+    /// Sections that the instructions were loaded from, in address order
+    sections: Vec<Section>,
+
+    /// Address that execution begins at, if the source specified one
+    entry_point: Option<u32>,
+
+    /// Symbols that reference instructions, in the order they were found
+    symbols: Vec<Symbol>,
+
+    /// [`Disassembly::functions`], which is found the first time it's needed
+    ///
+    /// This depends on the symbols and entry point, so changing either of
+    /// them clears it.
+    functions: OnceCell<Vec<FunctionRange>>,
 }
 
 #[derive(Clone, Debug)]
@@ -25,11 +45,79 @@ pub struct Entry {
     pub labels: Vec<String>,
 }
 
+/// A named address in the disassembly, usually from an elf's symbol table
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub addr: u32,
+
+    /// Number of bytes that the symbol covers, or 0 if unknown
+    pub size: u32,
+
+    /// Whether the symbol was marked as a function (`STT_FUNC`)
+    pub is_function: bool,
+
+    /// Whether the symbol is visible outside of its object file
+    ///
+    /// Hand-written assembly rarely marks functions with `.type`, but their
+    /// entry points are usually `.global`. Local labels are usually loops.
+    pub is_global: bool,
+}
+
+/// A named range of instructions, usually from an elf's section headers
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Section {
+    pub name: String,
+
+    /// Address of the first instruction
+    pub addr: u32,
+
+    /// Address one past the last instruction
+    pub end: u32,
+}
+
+impl Section {
+    /// Check whether `addr` falls inside of this section
+    pub fn contains(&self, addr: u32) -> bool {
+        self.addr <= addr && addr < self.end
+    }
+}
+
+/// A range of instructions that make up a single function
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionRange {
+    /// Address of the first instruction
+    pub addr: u32,
+
+    /// Address one past the last instruction
+    pub end: u32,
+
+    /// The first label on the first instruction, if there is one
+    pub name: Option<String>,
+
+    /// Whether the range came directly from a symbol's size (`st_size`)
+    ///
+    /// Otherwise, the range was inferred and may be less accurate.
+    pub from_symbol: bool,
+}
+
+impl FunctionRange {
+    /// Size of the function in bytes
+    pub fn size(&self) -> u32 {
+        self.end - self.addr
+    }
+
+    /// Check whether `addr` falls inside of this function
+    pub fn contains(&self, addr: u32) -> bool {
+        self.addr <= addr && addr < self.end
+    }
+}
+
 impl Disassembly {
     /// Parse a disassembly from an elf file on disk
     pub fn parse_from_elf_path<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path: &Path = path.as_ref();
-        let buffer: Vec<u8> = fs::read(&path)?;
+        let buffer: Vec<u8> = fs::read(path)?;
         let elf: Elf = match Object::parse(&buffer)? {
             Object::Elf(elf) => elf,
             Object::PE(_pe) => {
@@ -61,77 +149,97 @@ impl Disassembly {
     ///
     /// `elf` must have been parsed from `buffer`.
     fn parse_from_elf<'a>(elf: &'a Elf, buffer: &'a [u8]) -> Result<Self> {
-        // We panic instead of handling an error condition in here.
-        // For now, we will silence this clippy warning, but in the future
-        // we should return a proper error here.
-        #![allow(clippy::unnecessary_wraps)]
-
-        // First off, we need to isolate the '.text' section.
-        //
-        // We expect to find exactly one ".text" section.
-        // If there are multiple (is that allowed?), we will ignore them.
-        // We'll use this section index to correlate symbols to the
-        // .text section, later.
-        let (text_shndx, section) =
-            match elf
-                .section_headers
-                .iter()
-                .enumerate()
-                .find(|(_idx, section)| {
-                    let name = &elf.shdr_strtab[section.sh_name];
-                    name == ".text"
-                }) {
-                Some(pair) => pair,
-                None => {
-                    // TODO: return an error
-                    panic!("No '.text' section in elf")
-                }
-            };
+        // Every section of executable code is disassembled.
+        // We'll keep track of each section's index to correlate symbols to
+        // them, later.
+        let mut sections = vec![];
+        let mut section_bases: BTreeMap<usize, u32> = BTreeMap::new();
+        let mut entries = BTreeMap::new();
 
-        // The '.text' section contains the executable code that we will load
-        // into the Disassembly object, so we need to extract and parse the
-        // bytes into instructions.
-        let start = section.sh_offset as usize;
-        let end = start + section.sh_size as usize;
-        let bytes = &buffer[start..=end];
-
-        // riscv32i instructions are always exactly 32-bits, stored in little
-        // Endian regardless of the endianness of the target machine.
-        let words: Vec<u32> = bytes
-            .chunks_exact(4)
-            .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
-            .collect();
+        // Sections in object files haven't been placed yet, and all claim to
+        // be at address 0. We lay them out one after another instead.
+        let relocatable = elf.header.e_type == ET_REL;
+        let mut next_free: u32 = 0;
 
-        let instrs: Vec<Option<Instr>> = words
-            .iter()
-            .cloned()
-            .map(crate::decode::decode_opcode)
-            .collect();
+        for (shndx, section) in elf.section_headers.iter().enumerate() {
+            if section.sh_type != SHT_PROGBITS || section.sh_flags & SHF_EXECINSTR as u64 == 0 {
+                continue;
+            }
+
+            // These sections contain the executable code that we will load
+            // into the Disassembly object, so we need to extract and parse
+            // the bytes into instructions.
+            let bytes = usize::try_from(section.sh_offset).ok().and_then(|start| {
+                let end = start.checked_add(usize::try_from(section.sh_size).ok()?)?;
+                buffer.get(start..end)
+            });
+            let bytes = match bytes {
+                Some(bytes) => bytes,
+                None => return Err("Section runs past the end of the elf".into()),
+            };
+            let size = match u32::try_from(bytes.len()) {
+                Ok(size) => size,
+                Err(_) => return Err("Section runs past the end of the address space".into()),
+            };
 
-        assert_eq!(words.len(), instrs.len());
+            // The section header tells us where the section lives in memory.
+            // Symbols and pc-relative targets are all relative to this
+            // address, so we need to use it instead of assuming that code
+            // loads at 0.
+            let addr_base = if relocatable {
+                let align = match u32::try_from(section.sh_addralign) {
+                    Ok(align) if align <= 4 => 4,
+                    Ok(align) if align.is_power_of_two() => align,
+                    _ => return Err("Section alignment isn't a 32-bit power of two".into()),
+                };
+                next_free
+                    .checked_add(align - 1)
+                    .map(|addr| addr & !(align - 1))
+            } else {
+                u32::try_from(section.sh_addr).ok()
+            };
 
-        // TODO: Check the elf - I'm pretty sure the elf can specify that
-        // the binary loads at a non-zero v/paddr...
-        let addr_base = 0;
+            // Every word's address is below the end, so this is the only
+            // check that they need
+            let addr_base = match addr_base.filter(|addr| addr.checked_add(size).is_some()) {
+                Some(addr_base) => addr_base,
+                None => return Err("Section runs past the end of the address space".into()),
+            };
+            next_free = addr_base + size;
 
-        let mut entries = HashMap::new();
+            // riscv32i instructions are always exactly 32-bits, stored in
+            // little Endian regardless of the endianness of the target machine.
+            for (i, w) in bytes.chunks_exact(4).enumerate() {
+                let addr = addr_base + (core::mem::size_of::<u32>() * i) as u32;
+                let word = u32::from_le_bytes([w[0], w[1], w[2], w[3]]);
 
-        for (i, (word, o_instr)) in words.into_iter().zip(instrs).enumerate() {
-            let addr = (core::mem::size_of::<u32>() * i) as u32 + addr_base;
+                let entry = Entry {
+                    addr,
+                    word,
+                    bytes: word.to_le_bytes(),
+                    o_instr: crate::decode::decode_opcode(word),
+                    labels: vec![],
+                };
+                entries.insert(addr, entry);
+            }
 
-            let entry = Entry {
-                addr,
-                word,
-                bytes: word.to_le_bytes(),
-                o_instr,
-                labels: vec![],
-            };
-            entries.insert(addr, entry);
+            sections.push(Section {
+                name: elf.shdr_strtab[section.sh_name].to_string(),
+                addr: addr_base,
+                end: addr_base + (size & !0b11),
+            });
+            section_bases.insert(shndx, addr_base);
         }
 
+        if sections.is_empty() {
+            return Err("No executable sections in elf".into());
+        }
+        sections.sort_by_key(|section| section.addr);
+
         // Find the symbols (labels) that we need to disassamble from
         // the symbols table in the elf.
         // dbg!(elf.strtab.to_vec());
+        let mut symbols = vec![];
         for sym in &elf.syms {
             let name = &elf.strtab[sym.st_name];
 
@@ -140,34 +248,523 @@ impl Disassembly {
                 continue;
             }
 
-            // Limit our symbols to those referencing the text section
-            if sym.st_shndx != text_shndx {
-                continue;
-            }
+            // Limit our symbols to those referencing executable sections
+            let base = match section_bases.get(&sym.st_shndx) {
+                Some(&base) => base,
+                None => continue,
+            };
 
-            // This is the address (within the .text section) that the symbol
-            // references.
-            let addr = sym.st_value as u32;
+            // This is the address that the symbol references.
+            // In object files, symbols are relative to their section instead.
+            let addr = if relocatable {
+                base.checked_add(sym.st_value as u32)
+            } else {
+                Some(sym.st_value as u32)
+            };
+            let addr = match addr {
+                Some(addr) => addr,
+                None => continue,
+            };
 
             if let Some(entry) = entries.get_mut(&addr) {
                 entry.labels.push(name.to_string());
+
+                symbols.push(Symbol {
+                    name: name.to_string(),
+                    addr,
+                    size: sym.st_size as u32,
+                    is_function: sym.st_type() == STT_FUNC,
+                    is_global: sym.st_bind() == STB_GLOBAL || sym.st_bind() == STB_WEAK,
+                });
+            }
+        }
+
+        Ok(Disassembly {
+            entries,
+            sections,
+            entry_point: Some(elf.entry as u32),
+            symbols,
+            functions: OnceCell::new(),
+        })
+    }
+
+    /// Build a disassembly from raw instruction words
+    ///
+    /// The first word is placed at `addr_base`, and each following word is
+    /// placed 4 bytes after the last. There are no labels or entry point.
+    pub fn from_words(addr_base: u32, words: &[u32]) -> Self {
+        let entries = words
+            .iter()
+            .enumerate()
+            .map(|(i, &word)| {
+                let addr = (core::mem::size_of::<u32>() * i) as u32 + addr_base;
+                let entry = Entry {
+                    addr,
+                    word,
+                    bytes: word.to_le_bytes(),
+                    o_instr: crate::decode::decode_opcode(word),
+                    labels: vec![],
+                };
+                (addr, entry)
+            })
+            .collect();
+
+        Disassembly {
+            entries,
+            sections: vec![],
+            entry_point: None,
+            symbols: vec![],
+            functions: OnceCell::new(),
+        }
+    }
+
+    /// Attach a global label to the instruction at `addr`
+    ///
+    /// Returns `false` if there is no instruction at that address.
+    pub fn add_label(&mut self, addr: u32, label: &str) -> bool {
+        self.add_symbol(Symbol {
+            name: label.to_string(),
+            addr,
+            size: 0,
+            is_function: false,
+            is_global: true,
+        })
+    }
+
+    /// Attach a symbol to the instruction at `addr`, labeling it
+    ///
+    /// Returns `false` if there is no instruction at the symbol's address.
+    pub fn add_symbol(&mut self, symbol: Symbol) -> bool {
+        match self.entries.get_mut(&symbol.addr) {
+            Some(entry) => {
+                entry.labels.push(symbol.name.clone());
+                self.symbols.push(symbol);
+                self.functions.take();
+                true
             }
+            None => false,
         }
+    }
 
-        Ok(Disassembly { entries })
+    /// Every symbol that references an instruction
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
     }
 
+    /// Set the address that execution begins at
+    pub fn set_entry_point(&mut self, addr: u32) {
+        self.entry_point = Some(addr);
+        self.functions.take();
+    }
+
+    /// The address that execution begins at, e.g. `e_entry` from an elf
+    pub fn entry_point(&self) -> Option<u32> {
+        self.entry_point
+    }
+
+    /// Look up the entry for the instruction at `addr`
+    pub fn get(&self, addr: u32) -> Option<&Entry> {
+        self.entries.get(&addr)
+    }
+
+    /// Find the address of the first instruction labeled `label`
+    pub fn find_label(&self, label: &str) -> Option<u32> {
+        self.disassembly()
+            .find(|entry| entry.labels.iter().any(|l| l == label))
+            .map(|entry| entry.addr)
+    }
+
+    /// Every instruction, in address order
     pub fn disassembly(&self) -> impl Iterator<Item = &Entry> {
-        #![allow(unreachable_code)]
+        self.entries.values()
+    }
+
+    /// Every section that instructions were loaded from, in address order
+    ///
+    /// This is empty for disassemblies that weren't loaded from an elf.
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    /// Look up the section that contains the instruction at `addr`
+    pub fn section_containing(&self, addr: u32) -> Option<&Section> {
+        self.sections.iter().find(|section| section.contains(addr))
+    }
+
+    /// Find the ranges of every function, in address order
+    ///
+    /// Functions with a sized symbol (`st_size`) are used as-is. Other
+    /// functions are found from:
+    ///     1. Function symbols without a size and global labels
+    ///     2. The entry point and installed trap handlers
+    ///     3. The targets of direct calls
+    ///     4. Prologues (`addi sp, sp, -N`) right after the end of other code
+    /// and run until the next function begins, excluding any padding.
+    /// This works on stripped binaries too, where only 2. through 4. apply.
+    pub fn functions(&self) -> &[FunctionRange] {
+        self.functions.get_or_init(|| self.find_functions())
+    }
+
+    fn find_functions(&self) -> Vec<FunctionRange> {
+        use crate::callgraph::{call_site, ends_flow, find_roots, CallKind};
+
+        // Functions can't run past a gap in the code, like the end of a
+        // section. These are the ends of each contiguous run of code.
+        let mut runs: BTreeMap<u32, u32> = BTreeMap::new();
+        for entry in self.disassembly() {
+            match runs.values_mut().next_back() {
+                Some(end) if *end == entry.addr => *end += 4,
+                _ => {
+                    runs.insert(entry.addr, entry.addr + 4);
+                }
+            }
+        }
+        let run_end = |addr: u32| -> u32 {
+            runs.range(..=addr)
+                .next_back()
+                .map_or(addr, |(_start, &end)| end)
+        };
+
+        // The symbol table is the best source of information we have, so
+        // those ranges are never split or overlapped.
+        let mut sized: BTreeMap<u32, FunctionRange> = BTreeMap::new();
+        for sym in self
+            .symbols
+            .iter()
+            .filter(|sym| sym.is_function && sym.size != 0)
+        {
+            // A bad `st_size` can run past the end of the address space
+            let end = match sym.addr.checked_add(sym.size) {
+                Some(end) => end,
+                None => continue,
+            };
+            sized.entry(sym.addr).or_insert(FunctionRange {
+                addr: sym.addr,
+                end,
+                name: Some(sym.name.clone()),
+                from_symbol: true,
+            });
+        }
+        let in_sized = |addr: u32| {
+            sized
+                .range(..=addr)
+                .next_back()
+                .is_some_and(|(_addr, func)| func.contains(addr))
+        };
+
+        let mut starts: BTreeSet<u32> = find_roots(self).into_iter().collect();
+
+        for sym in &self.symbols {
+            if sym.is_function || sym.is_global {
+                starts.insert(sym.addr);
+            }
+        }
+
+        let mut prev: Option<&Entry> = None;
+        for entry in self.disassembly() {
+            if let Some(site) = call_site(prev, entry) {
+                if let (CallKind::Call, Some(target)) = (site.kind, site.target) {
+                    if self.get(target).is_some() {
+                        starts.insert(target);
+                    }
+                }
+            }
+
+            // Prologues that follow code which can't continue into them
+            if let Some(Instr::Addi {
+                rd: Reg::Sp,
+                rs1: Reg::Sp,
+                imm,
+            }) = entry.o_instr
+            {
+                let after_end = match prev {
+                    Some(prev) => ends_flow(prev) || is_padding(prev),
+                    None => true,
+                };
+                if imm < 0 && after_end {
+                    starts.insert(entry.addr);
+                }
+            }
+
+            prev = Some(entry);
+        }
+
+        let mut functions = sized.clone();
+        for &addr in starts.iter().filter(|&&addr| !in_sized(addr)) {
+            let next_start = starts.range(addr + 1..).next().cloned();
+            let next_sized = sized.range(addr + 1..).next().map(|(&addr, _func)| addr);
+            let mut end = match (next_start, next_sized) {
+                (Some(a), Some(b)) => a.min(b),
+                (Some(a), None) | (None, Some(a)) => a,
+                (None, None) => u32::MAX,
+            };
+            end = end.min(run_end(addr));
+
+            // Functions are often padded out to an alignment
+            while end > addr + 4 && self.get(end - 4).is_some_and(is_padding) {
+                end -= 4;
+            }
+
+            functions.insert(
+                addr,
+                FunctionRange {
+                    addr,
+                    end,
+                    name: self
+                        .get(addr)
+                        .and_then(|entry| entry.labels.first().cloned()),
+                    from_symbol: false,
+                },
+            );
+        }
+
+        functions.into_values().collect()
+    }
+
+    /// Find the function that contains the instruction at `addr`
+    pub fn function_containing(&self, addr: u32) -> Option<&FunctionRange> {
+        // Functions don't overlap, so only the last one to start at or before
+        // `addr` can contain it
+        let functions = self.functions();
+        let after = functions.partition_point(|func| func.addr <= addr);
+        functions[..after].last().filter(|func| func.contains(addr))
+    }
+
+    /// Find the function labeled `name`
+    pub fn function_named(&self, name: &str) -> Option<&FunctionRange> {
+        let addr = self.find_label(name)?;
+        let functions = self.functions();
+        functions
+            .binary_search_by_key(&addr, |func| func.addr)
+            .ok()
+            .map(|i| &functions[i])
+    }
+}
+
+/// Check whether `entry` is filler between functions: a `nop` or all zeroes
+fn is_padding(entry: &Entry) -> bool {
+    matches!(
+        entry.o_instr,
+        Some(Instr::Illegal)
+            | Some(Instr::Addi {
+                rd: Reg::Zero,
+                rs1: Reg::Zero,
+                imm: 0,
+            })
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    #[rustfmt::skip]
+    const WORDS: &[u32] = &[
+        // _start:
+        0xff01_0113, // addi    sp, sp, -16
+        0x00c0_00ef, // jal     foo
+        0x0200_00ef, // jal     bar
+        // loop:
+        0x0000_006f, // j       loop
+        // foo:
+        0xfe01_0113, // addi    sp, sp, -32
+        0x0140_00ef, // jal     bar
+        0x0201_0113, // addi    sp, sp, 32
+        0x0000_8067, // ret
+        0x0000_0013, // nop
+        0x0000_0013, // nop
+        // bar:
+        0xff81_0113, // addi    sp, sp, -8
+        0x0081_0113, // addi    sp, sp, 8
+        0x0000_8067, // ret
+        0x0000_0000, // .word   0
+    ];
+
+    #[test]
+    fn check_functions_stripped() {
+        let mut dis = Disassembly::from_words(0x0, WORDS);
+        dis.set_entry_point(0x0);
+
+        let ranges: Vec<(u32, u32)> = dis
+            .functions()
+            .iter()
+            .map(|func| (func.addr, func.end))
+            .collect();
+
+        assert_eq!(ranges, vec![(0x00, 0x10), (0x10, 0x20), (0x28, 0x34)]);
+    }
+
+    #[test]
+    fn check_functions_with_symbols() {
+        let mut dis = Disassembly::from_words(0x0, WORDS);
+        dis.add_label(0x00, "_start");
+        dis.add_symbol(Symbol {
+            name: "loop".to_string(),
+            addr: 0x0c,
+            size: 0,
+            is_function: false,
+            is_global: false,
+        });
+        dis.add_symbol(Symbol {
+            name: "foo".to_string(),
+            addr: 0x10,
+            size: 0x18,
+            is_function: true,
+            is_global: false,
+        });
+
+        assert_eq!(
+            dis.functions(),
+            vec![
+                FunctionRange {
+                    addr: 0x00,
+                    end: 0x10,
+                    name: Some("_start".to_string()),
+                    from_symbol: false,
+                },
+                FunctionRange {
+                    addr: 0x10,
+                    end: 0x28,
+                    name: Some("foo".to_string()),
+                    from_symbol: true,
+                },
+                FunctionRange {
+                    addr: 0x28,
+                    end: 0x34,
+                    name: None,
+                    from_symbol: false,
+                },
+            ]
+        );
+
+        assert_eq!(dis.function_containing(0x0c).unwrap().addr, 0x00);
+        assert_eq!(dis.function_named("foo").unwrap().size(), 0x18);
+        assert_eq!(dis.function_named("loop"), None);
+    }
+
+    #[test]
+    fn check_functions_after_changes() {
+        let mut dis = Disassembly::from_words(0x0, WORDS);
+        dis.set_entry_point(0x0);
+        assert_eq!(dis.function_containing(0x2c).unwrap().addr, 0x28);
+        assert_eq!(dis.function_containing(0x20), None);
+        assert_eq!(dis.function_named("bad"), None);
+
+        // This size runs past the end of the address space, so it's ignored
+        // and the range is found like any other function's
+        dis.add_symbol(Symbol {
+            name: "bad".to_string(),
+            addr: 0x28,
+            size: u32::MAX,
+            is_function: true,
+            is_global: false,
+        });
+        let bad = dis.function_named("bad").unwrap();
+        assert_eq!((bad.addr, bad.end), (0x28, 0x34));
+        assert!(!bad.from_symbol);
+        assert_eq!(dis.function_containing(0x2c), Some(bad));
+    }
+
+    /// Build a minimal riscv32 elf with an executable `.text` section for
+    /// each of `sections`, as its address, alignment, and number of `nop`s
+    fn build_elf(e_type: u16, sections: &[(u32, u32, u32)]) -> Vec<u8> {
+        const EHDR_SIZE: u32 = 52;
+        const SHDR_SIZE: u32 = 40;
+
+        let shstrtab = b"\0.text\0.shstrtab\0";
+        let text_size: u32 = sections.iter().map(|&(_, _, nops)| nops * 4).sum();
+        let shstrtab_off = EHDR_SIZE + text_size;
+        let shdr_off = shstrtab_off + shstrtab.len() as u32;
+        let shnum = sections.len() as u16 + 2;
+
+        let mut elf = vec![];
+        let u16s = |elf: &mut Vec<u8>, values: &[u16]| {
+            values
+                .iter()
+                .for_each(|v| elf.extend_from_slice(&v.to_le_bytes()))
+        };
+        let u32s = |elf: &mut Vec<u8>, values: &[u32]| {
+            values
+                .iter()
+                .for_each(|v| elf.extend_from_slice(&v.to_le_bytes()))
+        };
+
+        // ELFCLASS32, ELFDATA2LSB, EV_CURRENT, then EM_RISCV
+        elf.extend_from_slice(b"\x7fELF\x01\x01\x01\0\0\0\0\0\0\0\0\0");
+        u16s(&mut elf, &[e_type, 243]);
+        // e_version, e_entry, e_phoff, e_shoff, e_flags
+        u32s(&mut elf, &[1, 0, 0, shdr_off, 0]);
+        // e_ehsize, e_phentsize, e_phnum, e_shentsize, e_shnum, e_shstrndx
+        u16s(
+            &mut elf,
+            &[EHDR_SIZE as u16, 32, 0, SHDR_SIZE as u16, shnum, shnum - 1],
+        );
+
+        for &(_, _, nops) in sections {
+            (0..nops).for_each(|_| u32s(&mut elf, &[0x0000_0013]));
+        }
+        elf.extend_from_slice(shstrtab);
+
+        // The null section, then name, type, flags, addr, offset, size, link,
+        // info, addralign, and entsize of each section
+        u32s(&mut elf, &[0; 10]);
+        let mut offset = EHDR_SIZE;
+        for &(addr, align, nops) in sections {
+            // SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR
+            u32s(
+                &mut elf,
+                &[1, 1, 0x6, addr, offset, nops * 4, 0, 0, align, 0],
+            );
+            offset += nops * 4;
+        }
+        // SHT_STRTAB
+        let size = shstrtab.len() as u32;
+        u32s(&mut elf, &[7, 3, 0, 0, shstrtab_off, size, 0, 0, 1, 0]);
+
+        elf
+    }
+
+    fn parse(elf: &[u8]) -> Result<Disassembly> {
+        Disassembly::parse_from_elf(&Elf::parse(elf).unwrap(), elf)
+    }
+
+    #[test]
+    fn check_elf_layout() {
+        // ET_REL sections are laid out one after another, aligned
+        let elf = build_elf(ET_REL, &[(0, 4, 3), (0, 16, 1)]);
+        let dis = parse(&elf).unwrap();
+        let sections: Vec<(u32, u32)> = dis
+            .sections
+            .iter()
+            .map(|section| (section.addr, section.end))
+            .collect();
+        assert_eq!(sections, [(0x00, 0x0c), (0x10, 0x14)]);
+
+        // ET_EXEC sections are where their headers say
+        let elf = build_elf(2, &[(0x8000_0000, 4, 2)]);
+        let dis = parse(&elf).unwrap();
+        assert_eq!(dis.sections[0].addr, 0x8000_0000);
+        assert_eq!(dis.sections[0].end, 0x8000_0008);
+    }
+
+    #[test]
+    fn check_elf_layout_overflow() {
+        let error = |elf: &[u8]| parse(elf).err().unwrap().to_string();
+        let past_end = "Section runs past the end of the address space";
+
+        // The last word would be at 0x1_0000_0000
+        let elf = build_elf(2, &[(0xffff_fffc, 4, 2)]);
+        assert_eq!(error(&elf), past_end);
 
-        // The addresses stored are expected to be contiguous
-        let addr_min: u32 = *self.entries.keys().min().unwrap_or(&0);
-        let addr_max: u32 = *self.entries.keys().max().unwrap_or(&0);
+        // The third section would be aligned to 0x1_0000_0000
+        let align = 0x8000_0000;
+        let elf = build_elf(ET_REL, &[(0, 4, 1), (0, align, 1), (0, align, 1)]);
+        assert_eq!(error(&elf), past_end);
 
-        // So step 4 at a time - all instructions are 4 byte-aligned.
-        // (Are labels?)
-        (addr_min..=addr_max)
-            .step_by(4)
-            .map(move |addr| &self.entries[&addr])
+        let elf = build_elf(ET_REL, &[(0, 12, 1)]);
+        assert_eq!(error(&elf), "Section alignment isn't a 32-bit power of two");
     }
 }
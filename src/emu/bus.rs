@@ -0,0 +1,141 @@
+use std::fmt;
+
+use crate::dis::Disassembly;
+
+/// An access to an address that nothing responds to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BusFault {
+    pub addr: u32,
+}
+
+impl fmt::Display for BusFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Nothing on the bus at {:#010x}", self.addr)
+    }
+}
+
+impl std::error::Error for BusFault {}
+
+/// Something that a hart can load from and store to
+///
+/// Accesses are always 1, 2, or 4 bytes wide, and are naturally aligned:
+/// the hart raises misaligned exceptions before it gets to the bus.
+/// Values are little endian, and loads are zero-extended to 32-bits.
+pub trait Bus {
+    fn load(&mut self, addr: u32, width: u8) -> Result<u32, BusFault>;
+    fn store(&mut self, addr: u32, width: u8, value: u32) -> Result<(), BusFault>;
+
+    /// Advance any devices on the bus by one instruction
+    ///
+    /// Returns the interrupts that the devices are raising, as `mip` bits.
+    fn tick(&mut self) -> u32 {
+        0
+    }
+}
+
+impl<B: Bus + ?Sized> Bus for Box<B> {
+    fn load(&mut self, addr: u32, width: u8) -> Result<u32, BusFault> {
+        (**self).load(addr, width)
+    }
+
+    fn store(&mut self, addr: u32, width: u8, value: u32) -> Result<(), BusFault> {
+        (**self).store(addr, width, value)
+    }
+
+    fn tick(&mut self) -> u32 {
+        (**self).tick()
+    }
+}
+
+impl<B: Bus + ?Sized> Bus for &mut B {
+    fn load(&mut self, addr: u32, width: u8) -> Result<u32, BusFault> {
+        (**self).load(addr, width)
+    }
+
+    fn store(&mut self, addr: u32, width: u8, value: u32) -> Result<(), BusFault> {
+        (**self).store(addr, width, value)
+    }
+
+    fn tick(&mut self) -> u32 {
+        (**self).tick()
+    }
+}
+
+/// A single contiguous block of zero-initialized memory
+#[derive(Clone, Debug)]
+pub struct Ram {
+    base: u32,
+    bytes: Vec<u8>,
+}
+
+impl Ram {
+    /// Create `size` bytes of memory starting at the address `base`
+    pub fn new(base: u32, size: u32) -> Self {
+        Ram {
+            base,
+            bytes: vec![0; size as usize],
+        }
+    }
+
+    /// Create memory holding every instruction in `dis`, and nothing else
+    pub fn from_disassembly(dis: &Disassembly) -> Self {
+        let mut entries = dis.disassembly().peekable();
+
+        let base = entries.peek().map_or(0, |entry| entry.addr);
+        let mut ram = Ram::new(base, 0);
+        for entry in entries {
+            ram.bytes.extend_from_slice(&entry.bytes);
+        }
+
+        ram
+    }
+
+    /// The address of the first byte
+    pub fn base(&self) -> u32 {
+        self.base
+    }
+
+    /// The number of bytes
+    pub fn size(&self) -> u32 {
+        self.bytes.len() as u32
+    }
+
+    /// Check whether every byte in `[addr, addr + len)` is in this memory
+    pub fn contains(&self, addr: u32, len: u32) -> bool {
+        let end = self.base as u64 + self.bytes.len() as u64;
+        self.base <= addr && (addr as u64 + len as u64) <= end
+    }
+
+    /// Borrow `len` bytes starting at `addr`
+    pub fn read_bytes(&self, addr: u32, len: u32) -> Result<&[u8], BusFault> {
+        let offset = self.offset(addr, len)?;
+        Ok(&self.bytes[offset..offset + len as usize])
+    }
+
+    /// Copy `bytes` into memory starting at `addr`
+    pub fn write_bytes(&mut self, addr: u32, bytes: &[u8]) -> Result<(), BusFault> {
+        let offset = self.offset(addr, bytes.len() as u32)?;
+        self.bytes[offset..offset + bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    fn offset(&self, addr: u32, len: u32) -> Result<usize, BusFault> {
+        if self.contains(addr, len) {
+            Ok((addr - self.base) as usize)
+        } else {
+            Err(BusFault { addr })
+        }
+    }
+}
+
+impl Bus for Ram {
+    fn load(&mut self, addr: u32, width: u8) -> Result<u32, BusFault> {
+        let mut le_bytes = [0_u8; 4];
+        le_bytes[..width as usize].copy_from_slice(self.read_bytes(addr, width as u32)?);
+        Ok(u32::from_le_bytes(le_bytes))
+    }
+
+    fn store(&mut self, addr: u32, width: u8, value: u32) -> Result<(), BusFault> {
+        self.write_bytes(addr, &value.to_le_bytes()[..width as usize])
+    }
+}
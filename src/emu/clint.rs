@@ -0,0 +1,133 @@
+use super::device::{read_word_part, write_word_part};
+use super::{BusFault, Device, Interrupt};
+
+/// The size of the register block
+pub const CLINT_SIZE: u32 = 0x1_0000;
+
+const MSIP: u32 = 0x0;
+const MTIMECMP: u32 = 0x4000;
+const MTIMECMP_H: u32 = 0x4004;
+const MTIME: u32 = 0xbff8;
+const MTIME_H: u32 = 0xbffc;
+
+/// A core-local interruptor for a single hart, with SiFive's register layout
+///
+/// `mtime` counts instructions, going up by one on every tick.
+#[derive(Clone, Debug)]
+pub struct Clint {
+    msip: bool,
+    mtime: u64,
+    mtimecmp: u64,
+}
+
+impl Default for Clint {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clint {
+    pub fn new() -> Self {
+        Clint {
+            msip: false,
+            mtime: 0,
+            mtimecmp: u64::MAX,
+        }
+    }
+
+    pub fn mtime(&self) -> u64 {
+        self.mtime
+    }
+
+    fn read(&self, reg: u32) -> Option<u32> {
+        let value = match reg {
+            MSIP => self.msip as u32,
+            MTIMECMP => self.mtimecmp as u32,
+            MTIMECMP_H => (self.mtimecmp >> 32) as u32,
+            MTIME => self.mtime as u32,
+            MTIME_H => (self.mtime >> 32) as u32,
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    fn write(&mut self, reg: u32, value: u32) {
+        let low = |old: u64| (old & !0xffff_ffff) | value as u64;
+        let high = |old: u64| (old & 0xffff_ffff) | (value as u64) << 32;
+
+        match reg {
+            MSIP => self.msip = value & 1 != 0,
+            MTIMECMP => self.mtimecmp = low(self.mtimecmp),
+            MTIMECMP_H => self.mtimecmp = high(self.mtimecmp),
+            MTIME => self.mtime = low(self.mtime),
+            MTIME_H => self.mtime = high(self.mtime),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Device for Clint {
+    fn load(&mut self, offset: u32, width: u8) -> Result<u32, BusFault> {
+        self.read(offset & !0b11)
+            .map(|word| read_word_part(word, offset, width))
+            .ok_or(BusFault { addr: offset })
+    }
+
+    fn store(&mut self, offset: u32, width: u8, value: u32) -> Result<(), BusFault> {
+        let reg = offset & !0b11;
+        let word = self.read(reg).ok_or(BusFault { addr: offset })?;
+        self.write(reg, write_word_part(word, offset, width, value));
+        Ok(())
+    }
+
+    fn tick(&mut self, _sources: u32) {
+        self.mtime = self.mtime.wrapping_add(1);
+    }
+
+    fn mip(&self) -> u32 {
+        let mut mip = 0;
+        if self.msip {
+            mip |= Interrupt::MachineSoftware.bit();
+        }
+        if self.mtime >= self.mtimecmp {
+            mip |= Interrupt::MachineTimer.bit();
+        }
+        mip
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    #[test]
+    fn check_clint() {
+        let mut clint = Clint::new();
+        assert_eq!(clint.mip(), 0);
+
+        clint.store(MSIP, 4, 1).unwrap();
+        assert_eq!(clint.mip(), Interrupt::MachineSoftware.bit());
+        clint.store(MSIP, 1, 0).unwrap();
+        assert_eq!(clint.mip(), 0);
+
+        // The usual sequence for setting mtimecmp from RV32
+        clint.store(MTIMECMP_H, 4, u32::MAX).unwrap();
+        clint.store(MTIMECMP, 4, 3).unwrap();
+        clint.store(MTIMECMP_H, 4, 0).unwrap();
+
+        clint.tick(0);
+        clint.tick(0);
+        assert_eq!(clint.load(MTIME, 4), Ok(2));
+        assert_eq!(clint.mip(), 0);
+        clint.tick(0);
+        assert_eq!(clint.mip(), Interrupt::MachineTimer.bit());
+
+        clint.store(MTIME_H, 4, 0x1234).unwrap();
+        assert_eq!(clint.mtime(), 0x1234_0000_0003);
+        assert_eq!(clint.load(MTIME_H + 1, 1), Ok(0x12));
+        assert_eq!(clint.load(0x8, 4), Err(BusFault { addr: 0x8 }));
+    }
+}
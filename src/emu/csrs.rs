@@ -0,0 +1,399 @@
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+
+use super::{Interrupt, Trap};
+use crate::csr::{self, Csr, Mode, Privilage};
+
+/// `misa` for RV32I with user mode, and no other extensions
+const MISA_RV32IU: u32 =
+    (1 << 30) | (1 << ('I' as u32 - 'A' as u32)) | (1 << ('U' as u32 - 'A' as u32));
+
+/// `mstatus.MIE`: interrupts are enabled in M-mode
+const MSTATUS_MIE: u32 = 1 << 3;
+
+/// `mstatus.MPIE`: the value of `MIE` before the current trap
+const MSTATUS_MPIE: u32 = 1 << 7;
+
+/// `mstatus.MPP`: the privilege mode before the current trap
+///
+/// Only M-mode (`0b11`) and U-mode (`0b00`) are implemented.
+const MSTATUS_MPP: u32 = 0b11 << 11;
+
+/// `mtvec.MODE` for vectored interrupts
+const MTVEC_VECTORED: u32 = 0b01;
+
+/// The `cycle` bit of `mcountinhibit` and `mcounteren`
+const COUNTER_CY: u32 = 1 << 0;
+
+/// The `instret` bit of `mcountinhibit` and `mcounteren`
+const COUNTER_IR: u32 = 1 << 2;
+
+/// The control and status registers of a single hart
+///
+/// Only the machine-mode registers and the user counters are implemented.
+/// Accessing anything else is an illegal instruction.
+#[derive(Clone, Debug)]
+pub struct CsrFile {
+    /// The privilege of every implemented CSR
+    privilages: BTreeMap<u16, Privilage>,
+
+    /// Registers that simply hold whatever was last written to them
+    values: BTreeMap<u16, u32>,
+
+    /// Interrupts raised by devices, as `mip` bits
+    ///
+    /// These are kept apart from `mip` so that devices lowering their lines
+    /// don't clear interrupts made pending with [`CsrFile::set_pending`].
+    lines: u32,
+
+    /// Counters written by the instruction that's executing, as
+    /// `mcountinhibit` bits
+    written: u32,
+
+    /// The mode that the hart is currently executing in
+    pub mode: Mode,
+
+    /// The number of instructions retired by the hart
+    pub instret: u64,
+
+    /// The number of cycles the hart has run for, as estimated by its
+    /// [`Timing`](super::Timing)
+    pub cycle: u64,
+}
+
+impl Default for CsrFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CsrFile {
+    pub fn new() -> Self {
+        let stored = [
+            csr::MSTATUS,
+            csr::MEDELEG,
+            csr::MIDELEG,
+            csr::MIE,
+            csr::MTVEC,
+            csr::MCOUNTEREN,
+            csr::MSCRATCH,
+            csr::MEPC,
+            csr::MCAUSE,
+            csr::MTVAL,
+            csr::MIP,
+            csr::MCOUNTINHIBIT,
+        ];
+        let computed = [
+            csr::MVENDORID,
+            csr::MARCHID,
+            csr::MIMPID,
+            csr::MHARTID,
+            csr::MISA,
+            csr::CYCLE,
+            csr::TIME,
+            csr::INSTRET,
+            csr::CYCLE_H,
+            csr::TIME_H,
+            csr::INSTRET_H,
+            csr::MCYCLE,
+            csr::MINSTRET,
+            csr::MCYCLE_H,
+            csr::MINSTRET_H,
+        ];
+        // hpmcounter3 to hpmcounter31, their upper halves, and the machine
+        // counters and events behind them
+        let hpm_counters = (3..32).flat_map(|n| {
+            vec![
+                Csr::from_number_conventions(0xC00 + n),
+                Csr::from_number_conventions(0xC80 + n),
+                Csr::from_number_conventions(0xB00 + n),
+                Csr::from_number_conventions(0xB80 + n),
+                Csr::from_number_conventions(0x320 + n),
+            ]
+        });
+
+        let privilages = stored
+            .iter()
+            .chain(computed.iter())
+            .cloned()
+            .chain(hpm_counters)
+            .map(|csr| (csr.num(), csr.privilage()))
+            .collect();
+        let values = stored.iter().map(|csr| (csr.num(), 0)).collect();
+
+        let mut csrs = CsrFile {
+            privilages,
+            values,
+            lines: 0,
+            written: 0,
+            mode: Mode::Machine,
+            instret: 0,
+            cycle: 0,
+        };
+        csrs.set(csr::MSTATUS, Mode::Machine.mpp());
+        csrs
+    }
+
+    /// Implement `csr` as a register that holds whatever was last written
+    /// to it, like a custom CSR from [`csr::register`]
+    ///
+    /// CSRs that are already implemented are left alone.
+    pub fn add(&mut self, csr: Csr) {
+        if let Entry::Vacant(entry) = self.privilages.entry(csr.num()) {
+            entry.insert(csr.privilage());
+            self.values.insert(csr.num(), 0);
+        }
+    }
+
+    fn get(&self, csr: Csr) -> u32 {
+        self.values[&csr.num()]
+    }
+
+    fn set(&mut self, csr: Csr, value: u32) {
+        self.values.insert(csr.num(), value);
+    }
+
+    /// Check whether the current mode may access the CSR numbered `num`
+    ///
+    /// `write` is set for instructions that would write it.
+    /// Returns `false` for CSRs that aren't implemented.
+    pub fn allows(&self, num: u16, write: bool) -> bool {
+        let privilage = match self.privilages.get(&num) {
+            Some(privilage) => privilage,
+            None => return false,
+        };
+
+        // Lower modes can only read the counters that `mcounteren` exposes
+        let counter = matches!(num, 0xC00..=0xC1F | 0xC80..=0xC9F);
+        if counter
+            && self.mode < Mode::Machine
+            && self.get(csr::MCOUNTEREN) & (1 << (num & 0x1f)) == 0
+        {
+            return false;
+        }
+
+        privilage.allows(self.mode, write)
+    }
+
+    /// Read the CSR numbered `num`, or `None` if it isn't implemented
+    ///
+    /// This ignores privilege, see [`CsrFile::allows`].
+    pub fn read(&self, num: u16) -> Option<u32> {
+        let value = match num {
+            _ if !self.privilages.contains_key(&num) => return None,
+
+            _ if num == csr::MISA.num() => MISA_RV32IU,
+
+            // There's no real-time clock, so `time` counts cycles too
+            _ if num == csr::CYCLE.num() || num == csr::TIME.num() || num == csr::MCYCLE.num() => {
+                self.cycle as u32
+            }
+            _ if num == csr::CYCLE_H.num()
+                || num == csr::TIME_H.num()
+                || num == csr::MCYCLE_H.num() =>
+            {
+                (self.cycle >> 32) as u32
+            }
+            _ if num == csr::INSTRET.num() || num == csr::MINSTRET.num() => self.instret as u32,
+            _ if num == csr::INSTRET_H.num() || num == csr::MINSTRET_H.num() => {
+                (self.instret >> 32) as u32
+            }
+
+            _ if num == csr::MIP.num() => self.mip(),
+
+            // The ID registers, hpmcounters, and events are all zero
+            _ => self.values.get(&num).cloned().unwrap_or(0),
+        };
+
+        Some(value)
+    }
+
+    /// Write `value` to the CSR numbered `num`
+    ///
+    /// Returns `None` if the CSR isn't implemented.
+    /// This ignores privilege, see [`CsrFile::allows`]. Writes to read-only
+    /// CSRs and fields are ignored.
+    pub fn write(&mut self, num: u16, value: u32) -> Option<()> {
+        self.privilages.get(&num)?;
+
+        let value = match num {
+            _ if num == csr::MSTATUS.num() => {
+                // Modes that aren't implemented fall back to U-mode
+                let mpp = if value & MSTATUS_MPP == MSTATUS_MPP {
+                    Mode::Machine.mpp()
+                } else {
+                    Mode::User.mpp()
+                };
+                (value & (MSTATUS_MIE | MSTATUS_MPIE)) | mpp
+            }
+
+            // Reserved modes aren't supported, so fall back to direct mode
+            _ if num == csr::MTVEC.num() && value & 0b11 >= 2 => value & !0b11,
+
+            // Instructions are always 4-byte aligned
+            _ if num == csr::MEPC.num() => value & !0b11,
+
+            _ if num == csr::MIE.num() => value & Interrupt::ALL_BITS,
+
+            // Machine interrupts are only made pending by devices
+            _ if num == csr::MIP.num() => return Some(()),
+
+            // Only the counters that are implemented can be stopped
+            _ if num == csr::MCOUNTINHIBIT.num() => value & (COUNTER_CY | COUNTER_IR),
+
+            _ if num == csr::MCYCLE.num() => {
+                self.cycle = set_low(self.cycle, value);
+                self.written |= COUNTER_CY;
+                return Some(());
+            }
+            _ if num == csr::MCYCLE_H.num() => {
+                self.cycle = set_high(self.cycle, value);
+                self.written |= COUNTER_CY;
+                return Some(());
+            }
+            _ if num == csr::MINSTRET.num() => {
+                self.instret = set_low(self.instret, value);
+                self.written |= COUNTER_IR;
+                return Some(());
+            }
+            _ if num == csr::MINSTRET_H.num() => {
+                self.instret = set_high(self.instret, value);
+                self.written |= COUNTER_IR;
+                return Some(());
+            }
+
+            _ => value,
+        };
+
+        if let Some(reg) = self.values.get_mut(&num) {
+            *reg = value;
+        }
+
+        Some(())
+    }
+
+    /// Count an instruction that took `cycles` to retire
+    ///
+    /// Counters stopped by `mcountinhibit` don't count. Neither do counters
+    /// that the instruction wrote, because the write takes precedence, so
+    /// the next instruction reads the value that was written.
+    pub(crate) fn retire(&mut self, cycles: u64) {
+        let stopped = self.get(csr::MCOUNTINHIBIT) | self.written;
+        if stopped & COUNTER_CY == 0 {
+            self.cycle = self.cycle.wrapping_add(cycles);
+        }
+        if stopped & COUNTER_IR == 0 {
+            self.instret = self.instret.wrapping_add(1);
+        }
+        self.written = 0;
+    }
+
+    /// Count `cycles` that passed without retiring an instruction, like
+    /// while taking a trap
+    pub(crate) fn stall(&mut self, cycles: u64) {
+        if self.get(csr::MCOUNTINHIBIT) & COUNTER_CY == 0 {
+            self.cycle = self.cycle.wrapping_add(cycles);
+        }
+        self.written = 0;
+    }
+
+    /// Mark `interrupt` as pending in `mip`, or clear it
+    pub fn set_pending(&mut self, interrupt: Interrupt, pending: bool) {
+        let mip = self.get(csr::MIP);
+        if pending {
+            self.set(csr::MIP, mip | interrupt.bit());
+        } else {
+            self.set(csr::MIP, mip & !interrupt.bit());
+        }
+    }
+
+    /// Set the interrupts that devices are raising, as `mip` bits
+    pub fn set_lines(&mut self, lines: u32) {
+        self.lines = lines & Interrupt::ALL_BITS;
+    }
+
+    fn mip(&self) -> u32 {
+        self.get(csr::MIP) | self.lines
+    }
+
+    /// The highest priority interrupt that is pending and enabled, if any
+    pub fn pending_interrupt(&self) -> Option<Interrupt> {
+        // Lower modes are always interrupted by M-mode interrupts
+        if self.mode == Mode::Machine && self.get(csr::MSTATUS) & MSTATUS_MIE == 0 {
+            return None;
+        }
+
+        let pending = self.mip() & self.get(csr::MIE);
+        Interrupt::BY_PRIORITY
+            .iter()
+            .cloned()
+            .find(|interrupt| pending & interrupt.bit() != 0)
+    }
+
+    /// Update the trap CSRs for taking `trap` at `pc`, and enter M-mode
+    ///
+    /// Returns the address of the trap handler to jump to.
+    pub fn enter_trap(&mut self, trap: Trap, pc: u32) -> u32 {
+        self.set(csr::MEPC, pc);
+        self.set(csr::MCAUSE, trap.cause());
+        self.set(csr::MTVAL, trap.tval());
+
+        // Interrupts stay disabled until the handler returns
+        let mstatus = self.get(csr::MSTATUS);
+        let mpie = if mstatus & MSTATUS_MIE != 0 {
+            MSTATUS_MPIE
+        } else {
+            0
+        };
+        self.set(csr::MSTATUS, mpie | self.mode.mpp());
+        self.mode = Mode::Machine;
+
+        let mtvec = self.get(csr::MTVEC);
+        let base = mtvec & !0b11;
+        match trap {
+            Trap::Interrupt(interrupt) if mtvec & 0b11 == MTVEC_VECTORED => {
+                base.wrapping_add(4 * interrupt.code())
+            }
+            _ => base,
+        }
+    }
+
+    /// Update the trap CSRs for returning from a trap with `mret`, and enter
+    /// the mode that the trap was taken from
+    ///
+    /// Returns the address to return to.
+    pub fn leave_trap(&mut self) -> u32 {
+        let mstatus = self.get(csr::MSTATUS);
+        let mie = if mstatus & MSTATUS_MPIE != 0 {
+            MSTATUS_MIE
+        } else {
+            0
+        };
+        self.set(csr::MSTATUS, mie | MSTATUS_MPIE | Mode::User.mpp());
+
+        self.mode = if mstatus & MSTATUS_MPP == Mode::Machine.mpp() {
+            Mode::Machine
+        } else {
+            Mode::User
+        };
+
+        self.get(csr::MEPC)
+    }
+}
+
+impl Mode {
+    /// This mode in the `mstatus.MPP` field
+    fn mpp(self) -> u32 {
+        (self as u32) << 11
+    }
+}
+
+/// `counter` with its low 32 bits replaced by `value`
+fn set_low(counter: u64, value: u32) -> u64 {
+    (counter & !0xffff_ffff) | value as u64
+}
+
+/// `counter` with its high 32 bits replaced by `value`
+fn set_high(counter: u64, value: u32) -> u64 {
+    (counter & 0xffff_ffff) | ((value as u64) << 32)
+}
@@ -0,0 +1,323 @@
+use super::{Bus, BusFault, Ram};
+
+/// A memory-mapped peripheral
+///
+/// Devices see accesses relative to the start of the range that they're
+/// mapped at, and report faults the same way.
+pub trait Device {
+    fn load(&mut self, offset: u32, width: u8) -> Result<u32, BusFault>;
+    fn store(&mut self, offset: u32, width: u8, value: u32) -> Result<(), BusFault>;
+
+    /// Advance the device by one instruction
+    ///
+    /// `sources` has bit `n` set when interrupt source `n` is raised, which is
+    /// only interesting to interrupt controllers.
+    fn tick(&mut self, _sources: u32) {}
+
+    /// Whether the device's interrupt line is raised
+    ///
+    /// This is routed to an interrupt controller through the source that the
+    /// device is mapped with.
+    fn irq(&self) -> bool {
+        false
+    }
+
+    /// Interrupts that the device raises directly on the hart, as `mip` bits
+    fn mip(&self) -> u32 {
+        0
+    }
+}
+
+impl Device for Ram {
+    fn load(&mut self, offset: u32, width: u8) -> Result<u32, BusFault> {
+        let base = self.base();
+        Bus::load(self, base.wrapping_add(offset), width).map_err(|_| BusFault { addr: offset })
+    }
+
+    fn store(&mut self, offset: u32, width: u8, value: u32) -> Result<(), BusFault> {
+        let base = self.base();
+        Bus::store(self, base.wrapping_add(offset), width, value)
+            .map_err(|_| BusFault { addr: offset })
+    }
+}
+
+/// A device mapped into `[base, base + size)`
+struct Region {
+    base: u32,
+    size: u32,
+
+    /// The interrupt source that the device's line is wired to
+    source: Option<u32>,
+
+    device: Box<dyn Device>,
+}
+
+impl Region {
+    fn contains(&self, addr: u32) -> bool {
+        self.base <= addr && (addr - self.base) < self.size
+    }
+}
+
+/// A bus made of devices mapped at fixed address ranges
+///
+/// Accesses that don't land in a mapped range fault.
+#[derive(Default)]
+pub struct MemoryMap {
+    regions: Vec<Region>,
+}
+
+impl MemoryMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map `device` into `[base, base + size)`
+    ///
+    /// # Panics
+    /// If the range overlaps a device that is already mapped.
+    pub fn map(&mut self, base: u32, size: u32, device: impl Device + 'static) -> &mut Self {
+        self.insert(base, size, None, Box::new(device))
+    }
+
+    /// Map `device` like [`MemoryMap::map`], and wire its interrupt line to
+    /// interrupt source `source`
+    ///
+    /// Sources are numbered from 1 to 31, like the PLIC.
+    pub fn map_with_irq(
+        &mut self,
+        base: u32,
+        size: u32,
+        source: u32,
+        device: impl Device + 'static,
+    ) -> &mut Self {
+        assert!(
+            (1..32).contains(&source),
+            "Interrupt source {} is out of range",
+            source
+        );
+        self.insert(base, size, Some(source), Box::new(device))
+    }
+
+    /// Map `ram` at the addresses that it was created with
+    pub fn map_ram(&mut self, ram: Ram) -> &mut Self {
+        self.map(ram.base(), ram.size(), ram)
+    }
+
+    fn insert(
+        &mut self,
+        base: u32,
+        size: u32,
+        source: Option<u32>,
+        device: Box<dyn Device>,
+    ) -> &mut Self {
+        let end = base as u64 + size as u64;
+        for region in &self.regions {
+            let region_end = region.base as u64 + region.size as u64;
+            assert!(
+                end <= region.base as u64 || region_end <= base as u64,
+                "Device at {:#010x} overlaps the device at {:#010x}",
+                base,
+                region.base
+            );
+        }
+
+        self.regions.push(Region {
+            base,
+            size,
+            source,
+            device,
+        });
+        self
+    }
+
+    /// Find the device mapped at `addr`, and the offset of `addr` into it
+    fn region_mut(&mut self, addr: u32) -> Result<(&mut Region, u32), BusFault> {
+        self.regions
+            .iter_mut()
+            .find(|region| region.contains(addr))
+            .map(|region| {
+                let offset = addr - region.base;
+                (region, offset)
+            })
+            .ok_or(BusFault { addr })
+    }
+}
+
+impl Bus for MemoryMap {
+    fn load(&mut self, addr: u32, width: u8) -> Result<u32, BusFault> {
+        let (region, offset) = self.region_mut(addr)?;
+        let base = region.base;
+        region.device.load(offset, width).map_err(|fault| BusFault {
+            addr: base.wrapping_add(fault.addr),
+        })
+    }
+
+    fn store(&mut self, addr: u32, width: u8, value: u32) -> Result<(), BusFault> {
+        let (region, offset) = self.region_mut(addr)?;
+        let base = region.base;
+        region
+            .device
+            .store(offset, width, value)
+            .map_err(|fault| BusFault {
+                addr: base.wrapping_add(fault.addr),
+            })
+    }
+
+    fn tick(&mut self) -> u32 {
+        let sources = self
+            .regions
+            .iter()
+            .filter(|region| region.device.irq())
+            .filter_map(|region| region.source)
+            .fold(0, |sources, source| sources | (1 << source));
+
+        let mut mip = 0;
+        for region in &mut self.regions {
+            region.device.tick(sources);
+            mip |= region.device.mip();
+        }
+
+        mip
+    }
+}
+
+/// Read the `width` bytes at `offset` out of a block of 32-bit registers
+///
+/// `word` is the register that holds `offset`.
+pub(crate) fn read_word_part(word: u32, offset: u32, width: u8) -> u32 {
+    let value = word >> (8 * (offset & 0b11));
+    match width {
+        1 => value & 0xff,
+        2 => value & 0xffff,
+        _ => value,
+    }
+}
+
+/// Replace the `width` bytes at `offset` in a block of 32-bit registers
+///
+/// `word` is the current value of the register that holds `offset`.
+pub(crate) fn write_word_part(word: u32, offset: u32, width: u8, value: u32) -> u32 {
+    let mask = match width {
+        1 => 0xff,
+        2 => 0xffff,
+        _ => return value,
+    };
+    let shift = 8 * (offset & 0b11);
+    (word & !(mask << shift)) | ((value & mask) << shift)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::emu::test::SharedBuffer;
+    use crate::emu::{Clint, Hart, Interrupt, Plic, Trap, Uart, CLINT_SIZE, PLIC_SIZE, UART_SIZE};
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    const CLINT_BASE: u32 = 0x0200_0000;
+    const PLIC_BASE: u32 = 0x0c00_0000;
+    const UART_BASE: u32 = 0x1000_0000;
+
+    fn memory_map(words: &[u32], uart: Uart<SharedBuffer>) -> MemoryMap {
+        let mut ram = Ram::new(0x8000_0000, 0x1000);
+        for (i, word) in words.iter().enumerate() {
+            ram.write_bytes(0x8000_0000 + 4 * i as u32, &word.to_le_bytes())
+                .unwrap();
+        }
+
+        let mut map = MemoryMap::new();
+        map.map_ram(ram)
+            .map(CLINT_BASE, CLINT_SIZE, Clint::new())
+            .map(PLIC_BASE, PLIC_SIZE, Plic::new())
+            .map_with_irq(UART_BASE, UART_SIZE, 1, uart);
+        map
+    }
+
+    #[test]
+    fn check_memory_map() {
+        let mut map = memory_map(&[], Uart::new(SharedBuffer::default()));
+
+        map.store(0x8000_0ffc, 4, 0x1234_5678).unwrap();
+        assert_eq!(map.load(0x8000_0ffe, 2), Ok(0x1234));
+        assert_eq!(
+            map.load(0x8000_1000, 1),
+            Err(BusFault { addr: 0x8000_1000 })
+        );
+        assert_eq!(
+            map.load(UART_BASE + UART_SIZE, 1),
+            Err(BusFault {
+                addr: UART_BASE + UART_SIZE
+            })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "overlaps")]
+    fn check_memory_map_overlap() {
+        let mut map = MemoryMap::new();
+        map.map_ram(Ram::new(0x8000_0000, 0x1000))
+            .map_ram(Ram::new(0x8000_0ffc, 0x4));
+    }
+
+    #[test]
+    fn check_external_interrupt() {
+        let mut uart = Uart::new(SharedBuffer::default());
+        uart.push_input(b"x");
+        let mut map = memory_map(&[], uart);
+
+        // Route the UART's receive interrupt through the PLIC
+        map.store(PLIC_BASE + 4, 4, 1).unwrap();
+        map.store(PLIC_BASE + 0x2000, 4, 1 << 1).unwrap();
+        assert_eq!(map.tick(), 0);
+        map.store(UART_BASE + 1, 1, 1).unwrap();
+        map.tick();
+        assert_eq!(map.tick(), Interrupt::MachineExternal.bit());
+
+        assert_eq!(map.load(PLIC_BASE + 0x20_0004, 4), Ok(1));
+        assert_eq!(map.load(UART_BASE, 1), Ok(b'x' as u32));
+        map.store(PLIC_BASE + 0x20_0004, 4, 1).unwrap();
+        map.tick();
+        assert_eq!(map.tick(), 0);
+    }
+
+    #[test]
+    fn check_timer_interrupt() {
+        #[rustfmt::skip]
+        let words = [
+            // _start:
+            0x0000_0297, // auipc   t0, 0           ┐ la t0, handler
+            0x02c2_8293, // addi    t0, t0, 44      ┘
+            0x3052_9073, // csrw    mtvec, t0
+            0x0200_4437, // lui     s0, 0x2004
+            0x00a0_0313, // li      t1, 10
+            0x0064_2023, // sw      t1, 0(s0)
+            0x0004_2223, // sw      zero, 4(s0)
+            0x0800_0313, // li      t1, 0x80
+            0x3043_2073, // csrs    mie, t1
+            0x3004_6073, // csrsi   mstatus, 8
+            // spin:
+            0x0000_006f, // j       spin
+            // handler:
+            0x1000_03b7, // lui     t2, 0x10000
+            0x0540_0313, // li      t1, 'T'
+            0x0063_8023, // sb      t1, 0(t2)
+            0xfff0_0313, // li      t1, -1
+            0x0064_2223, // sw      t1, 4(s0)
+            0x3020_0073, // mret
+        ];
+        let out = SharedBuffer::default();
+        let map = memory_map(&words, Uart::new(out.clone()));
+        let mut hart = Hart::new(map, 0x8000_0000);
+
+        let mut traps = vec![];
+        for _ in 0..100 {
+            traps.extend(hart.tick());
+        }
+
+        assert_eq!(traps, vec![Trap::Interrupt(Interrupt::MachineTimer)]);
+        assert_eq!(out.0.borrow().as_slice(), b"T");
+        assert_eq!(hart.pc(), 0x8000_0028);
+    }
+}
@@ -0,0 +1,324 @@
+use std::fs;
+use std::path::Path;
+
+use goblin::elf::program_header::PT_LOAD;
+use goblin::elf::Elf;
+
+use super::{Bus, BusFault, Hart};
+use crate::prelude::*;
+
+/// Symbols that linker scripts commonly use for the initial stack pointer
+///
+/// The first one found in the symbol table is used.
+pub const STACK_TOP_SYMBOLS: &[&str] = &["_stack_top", "__stack_top", "_estack", "__stack"];
+
+/// A block of memory initialized by a `PT_LOAD` program header
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    /// Physical address of the first byte
+    pub addr: u32,
+
+    /// The initial contents, as stored in the file
+    pub data: Vec<u8>,
+
+    /// Total size in memory, which is at least `data.len()`
+    ///
+    /// Everything past `data` (e.g. `.bss`) is zeroed.
+    pub mem_size: u32,
+}
+
+/// Everything needed to start running an elf in the simulator
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    /// The address of the first instruction to run, from `e_entry`
+    pub entry: u32,
+
+    /// The initial value of `sp`, if the elf has one of [`STACK_TOP_SYMBOLS`]
+    pub stack_top: Option<u32>,
+
+    pub segments: Vec<Segment>,
+}
+
+impl Image {
+    /// Parse an image from an elf file on disk
+    pub fn parse_from_elf_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path: &Path = path.as_ref();
+        let buffer: Vec<u8> = fs::read(path)?;
+        Self::parse_from_elf_bytes(&buffer)
+            .map_err(|err| format!("{}: {}", path.to_string_lossy(), err).into())
+    }
+
+    /// Parse an image from the contents of an elf file
+    pub fn parse_from_elf_bytes(buffer: &[u8]) -> Result<Self> {
+        let elf = Elf::parse(buffer)?;
+
+        let mut segments = vec![];
+        for header in elf.program_headers.iter().filter(|h| h.p_type == PT_LOAD) {
+            if header.p_filesz > header.p_memsz {
+                return Err(format!(
+                    "Segment at {:#010x} has more bytes in the file ({}) than in memory ({})",
+                    header.p_paddr, header.p_filesz, header.p_memsz
+                )
+                .into());
+            }
+
+            let data = buffer
+                .get(header.file_range())
+                .ok_or_else(|| {
+                    format!(
+                        "Segment at {:#010x} runs past the end of the file",
+                        header.p_paddr
+                    )
+                })?
+                .to_vec();
+
+            segments.push(Segment {
+                addr: header.p_paddr as u32,
+                data,
+                mem_size: header.p_memsz as u32,
+            });
+        }
+
+        let stack_top = STACK_TOP_SYMBOLS.iter().find_map(|&wanted| {
+            elf.syms
+                .iter()
+                .find(|sym| elf.strtab.get(sym.st_name).and_then(|name| name.ok()) == Some(wanted))
+                .map(|sym| sym.st_value as u32)
+        });
+
+        Ok(Image {
+            entry: elf.entry as u32,
+            stack_top,
+            segments,
+        })
+    }
+
+    /// The address just past the last byte of every segment
+    ///
+    /// This is where the heap usually starts.
+    pub fn end(&self) -> u32 {
+        self.segments
+            .iter()
+            .map(|segment| segment.addr.wrapping_add(segment.mem_size))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Copy every segment onto `bus`, zeroing the parts not in the file
+    pub fn load<B: Bus>(&self, bus: &mut B) -> std::result::Result<(), BusFault> {
+        for segment in &self.segments {
+            let addrs = (segment.addr..).take(segment.mem_size as usize);
+            for (offset, addr) in addrs.enumerate() {
+                let byte = segment.data.get(offset).copied().unwrap_or(0);
+                bus.store(addr, 1, byte as u32)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<B: Bus> Hart<B> {
+    /// Load `image` onto `bus` and create a hart that is ready to run it
+    ///
+    /// `pc` starts at the entry point, and `sp` at the top of the stack if the
+    /// image knows where that is.
+    pub fn with_image(mut bus: B, image: &Image) -> std::result::Result<Self, BusFault> {
+        image.load(&mut bus)?;
+
+        let mut hart = Hart::new(bus, image.entry);
+        if let Some(stack_top) = image.stack_top {
+            hart.set_reg(Reg::Sp, stack_top);
+        }
+
+        Ok(hart)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::csr::Mode;
+    use crate::emu::{Exception, Ram};
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    /// Build a minimal riscv32 executable
+    ///
+    /// There's no riscv linker around during tests, so this lays out the
+    /// headers by hand. `text` loads at 0x8000_0000, `data` at 0x8000_1000,
+    /// and `bss_size` zeroed bytes follow `data`.
+    fn build_elf(text: &[u32], data: &[u8], bss_size: u32, symbols: &[(&str, u32)]) -> Vec<u8> {
+        const EHDR_SIZE: u32 = 52;
+        const PHDR_SIZE: u32 = 32;
+        const SHDR_SIZE: u32 = 40;
+        const SYM_SIZE: u32 = 16;
+
+        let text: Vec<u8> = text.iter().flat_map(|word| word.to_le_bytes()).collect();
+
+        let mut strtab = vec![0_u8];
+        let mut symtab = vec![0_u8; SYM_SIZE as usize];
+        for &(name, value) in symbols {
+            let st_name = strtab.len() as u32;
+            strtab.extend_from_slice(name.as_bytes());
+            strtab.push(0);
+
+            symtab.extend_from_slice(&st_name.to_le_bytes());
+            symtab.extend_from_slice(&value.to_le_bytes());
+            symtab.extend_from_slice(&0_u32.to_le_bytes()); // st_size
+            symtab.push(0x10); // STB_GLOBAL, STT_NOTYPE
+            symtab.push(0);
+            symtab.extend_from_slice(&0xfff1_u16.to_le_bytes()); // SHN_ABS
+        }
+        let shstrtab = b"\0.symtab\0.strtab\0.shstrtab\0";
+
+        let text_off = EHDR_SIZE + 2 * PHDR_SIZE;
+        let data_off = text_off + text.len() as u32;
+        let symtab_off = data_off + data.len() as u32;
+        let strtab_off = symtab_off + symtab.len() as u32;
+        let shstrtab_off = strtab_off + strtab.len() as u32;
+        let shdr_off = shstrtab_off + shstrtab.len() as u32;
+
+        let mut elf = vec![];
+        let u16s = |elf: &mut Vec<u8>, values: &[u16]| {
+            values
+                .iter()
+                .for_each(|v| elf.extend_from_slice(&v.to_le_bytes()))
+        };
+        let u32s = |elf: &mut Vec<u8>, values: &[u32]| {
+            values
+                .iter()
+                .for_each(|v| elf.extend_from_slice(&v.to_le_bytes()))
+        };
+
+        // ELFCLASS32, ELFDATA2LSB, EV_CURRENT
+        elf.extend_from_slice(b"\x7fELF\x01\x01\x01\0\0\0\0\0\0\0\0\0");
+        // ET_EXEC, EM_RISCV
+        u16s(&mut elf, &[2, 243]);
+        // e_version, e_entry, e_phoff, e_shoff, e_flags
+        u32s(&mut elf, &[1, 0x8000_0000, EHDR_SIZE, shdr_off, 0]);
+        // e_ehsize, e_phentsize, e_phnum, e_shentsize, e_shnum, e_shstrndx
+        u16s(
+            &mut elf,
+            &[
+                EHDR_SIZE as u16,
+                PHDR_SIZE as u16,
+                2,
+                SHDR_SIZE as u16,
+                4,
+                3,
+            ],
+        );
+
+        // Program headers: type, offset, vaddr, paddr, filesz, memsz, flags, align
+        let text_len = text.len() as u32;
+        let data_len = data.len() as u32;
+        u32s(
+            &mut elf,
+            &[
+                1,
+                text_off,
+                0x8000_0000,
+                0x8000_0000,
+                text_len,
+                text_len,
+                5,
+                4,
+            ],
+        );
+        u32s(
+            &mut elf,
+            &[
+                1,
+                data_off,
+                0x8000_1000,
+                0x8000_1000,
+                data_len,
+                data_len + bss_size,
+                6,
+                4,
+            ],
+        );
+
+        elf.extend_from_slice(&text);
+        elf.extend_from_slice(data);
+        elf.extend_from_slice(&symtab);
+        elf.extend_from_slice(&strtab);
+        elf.extend_from_slice(shstrtab);
+
+        // Section headers: name, type, flags, addr, offset, size, link, info, align, entsize
+        u32s(&mut elf, &[0; 10]);
+        let symtab_len = symtab.len() as u32;
+        u32s(
+            &mut elf,
+            &[1, 2, 0, 0, symtab_off, symtab_len, 2, 1, 4, SYM_SIZE],
+        );
+        let strtab_len = strtab.len() as u32;
+        u32s(&mut elf, &[9, 3, 0, 0, strtab_off, strtab_len, 0, 0, 1, 0]);
+        let shstrtab_len = shstrtab.len() as u32;
+        u32s(
+            &mut elf,
+            &[17, 3, 0, 0, shstrtab_off, shstrtab_len, 0, 0, 1, 0],
+        );
+
+        elf
+    }
+
+    #[test]
+    fn check_load_and_run() {
+        #[rustfmt::skip]
+        let text = [
+            0x8000_12b7, // lui     t0, 0x80001
+            0x0002_a503, // lw      a0, 0(t0)
+            0x0042_a583, // lw      a1, 4(t0)
+            0xfea1_2e23, // sw      a0, -4(sp)
+            0x0000_0073, // ecall
+        ];
+        let elf = build_elf(
+            &text,
+            &0xdead_beef_u32.to_le_bytes(),
+            4,
+            &[("_stack_top", 0x8000_2000)],
+        );
+
+        let image = Image::parse_from_elf_bytes(&elf).unwrap();
+        assert_eq!(image.entry, 0x8000_0000);
+        assert_eq!(image.stack_top, Some(0x8000_2000));
+        assert_eq!(image.segments.len(), 2);
+        assert_eq!(image.segments[1].mem_size, 8);
+
+        // Fill memory with garbage so that we can tell `.bss` was zeroed
+        let mut ram = Ram::new(0x8000_0000, 0x2000);
+        ram.write_bytes(0x8000_1000, &[0xff; 0x1000]).unwrap();
+
+        let mut hart = Hart::with_image(ram, &image).unwrap();
+        assert_eq!(hart.pc(), 0x8000_0000);
+        assert_eq!(hart.reg(Reg::Sp), 0x8000_2000);
+
+        assert_eq!(
+            hart.run(100),
+            Err(Exception::EnvironmentCall(Mode::Machine))
+        );
+        assert_eq!(hart.reg(Reg::A0), 0xdead_beef);
+        assert_eq!(hart.reg(Reg::A1), 0);
+        assert_eq!(
+            hart.bus.read_bytes(0x8000_1ffc, 4).unwrap(),
+            &0xdead_beef_u32.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn check_load_out_of_bounds() {
+        let elf = build_elf(&[0x0000_0073], &[], 0x2000, &[]);
+        let image = Image::parse_from_elf_bytes(&elf).unwrap();
+        assert_eq!(image.stack_top, None);
+
+        let ram = Ram::new(0x8000_0000, 0x2000);
+        assert_eq!(
+            Hart::with_image(ram, &image).err(),
+            Some(BusFault { addr: 0x8000_2000 })
+        );
+    }
+}
@@ -0,0 +1,780 @@
+//! A GDB remote serial protocol server
+//!
+//! This speaks just enough of the protocol for GDB to read and write
+//! registers and memory, set breakpoints, and step or continue a [`Hart`].
+//! See "Remote Protocol" in the GDB manual for details.
+
+use std::collections::{BTreeSet, VecDeque};
+use std::convert::{TryFrom, TryInto};
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+
+use super::{Bus, Exception, Hart, Host, Serviced, Trap};
+use crate::csr::{self, Csr, Mode};
+use crate::prelude::*;
+
+/// Signals reported in stop replies
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+/// The byte GDB sends to interrupt a running target
+const INTERRUPT: u8 = 0x03;
+
+/// GDB's register number for `pc`
+const PC_REGNUM: u32 = 32;
+
+/// GDB numbers CSRs from here, after the integer and float registers
+const CSR_REGNUM: u32 = 65;
+
+/// GDB's register number for the virtual `priv` register
+const PRIV_REGNUM: u32 = CSR_REGNUM + 0x1000;
+
+/// The longest packet that we advertise in `qSupported`, which also bounds
+/// the replies that we send
+const PACKET_SIZE: usize = 0x4000;
+
+/// How many instructions run between checks for an interrupt from GDB
+const POLL_INTERVAL: u64 = 0x1000;
+
+/// CSRs described to GDB
+const CSRS: &[Csr] = &[
+    csr::MSTATUS,
+    csr::MISA,
+    csr::MIE,
+    csr::MTVEC,
+    csr::MCOUNTEREN,
+    csr::MSCRATCH,
+    csr::MEPC,
+    csr::MCAUSE,
+    csr::MTVAL,
+    csr::MIP,
+    csr::MHARTID,
+    csr::CYCLE,
+    csr::INSTRET,
+];
+
+/// A byte stream that GDB is connected through
+pub trait Connection: Read + Write {
+    /// Read whatever bytes have arrived, without blocking
+    ///
+    /// This is how a running target notices GDB interrupting it.
+    /// Connections that can't poll never see interrupts.
+    fn poll(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Ok(0)
+    }
+}
+
+impl Connection for TcpStream {
+    fn poll(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.set_nonblocking(true)?;
+        let result = match self.read(buf) {
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(0),
+            result => result,
+        };
+        self.set_nonblocking(false)?;
+        result
+    }
+}
+
+/// A connection over this process's stdin and stdout
+///
+/// This is what GDB uses for `target remote | sim --gdb -`.
+pub struct Stdio {
+    stdin: io::Stdin,
+    stdout: io::Stdout,
+}
+
+impl Default for Stdio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stdio {
+    pub fn new() -> Self {
+        Stdio {
+            stdin: io::stdin(),
+            stdout: io::stdout(),
+        }
+    }
+}
+
+impl Read for Stdio {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stdin.read(buf)
+    }
+}
+
+impl Write for Stdio {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stdout.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()
+    }
+}
+
+impl Connection for Stdio {}
+
+/// Why the hart stopped running
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Stop {
+    Signal(u8),
+
+    /// A breakpoint that GDB set was hit
+    Breakpoint,
+
+    /// The program exited with a status code
+    Exited(i32),
+}
+
+/// Serves a single GDB session over `conn`
+pub struct GdbServer<C: Connection> {
+    conn: C,
+
+    /// Bytes read from `conn` but not handled yet
+    input: VecDeque<u8>,
+
+    /// Whether GDB asked to stop acknowledging packets
+    no_ack: bool,
+
+    /// Addresses of software breakpoints
+    breakpoints: BTreeSet<u32>,
+
+    last_stop: Stop,
+}
+
+impl<C: Connection> GdbServer<C> {
+    pub fn new(conn: C) -> Self {
+        GdbServer {
+            conn,
+            input: VecDeque::new(),
+            no_ack: false,
+            breakpoints: BTreeSet::new(),
+            last_stop: Stop::Signal(SIGTRAP),
+        }
+    }
+
+    /// Handle requests from GDB until it detaches or kills the target, or the
+    /// program exits
+    ///
+    /// System calls are serviced by `host`. A plain `ebreak` stops the hart
+    /// like a breakpoint, instead of trapping.
+    ///
+    /// Returns the exit status, if the program exited.
+    pub fn serve<B: Bus>(
+        &mut self,
+        hart: &mut Hart<B>,
+        host: &mut Host,
+    ) -> io::Result<Option<i32>> {
+        loop {
+            let packet = match self.read_packet()? {
+                Some(packet) => packet,
+                // GDB hung up
+                None => return Ok(None),
+            };
+
+            let (command, args) = match packet.first() {
+                Some(&command) => (command, &packet[1..]),
+                None => {
+                    self.send(b"")?;
+                    continue;
+                }
+            };
+
+            let reply: Vec<u8> = match command {
+                b'?' => self.stop_reply(self.last_stop),
+                b'q' | b'Q' => self.query(&packet),
+                b'H' => b"OK".to_vec(),
+                b'g' => {
+                    let mut regs = vec![];
+                    for n in 0..=PC_REGNUM {
+                        regs.extend_from_slice(&read_reg(hart, n).unwrap().to_le_bytes());
+                    }
+                    hex(&regs).into_bytes()
+                }
+                b'G' => {
+                    let bytes = unhex(args).unwrap_or_default();
+                    for (n, chunk) in (0..=PC_REGNUM).zip(bytes.chunks_exact(4)) {
+                        let value = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+                        write_reg(hart, n, value);
+                    }
+                    b"OK".to_vec()
+                }
+                b'p' => parse_hex(args)
+                    .and_then(|n| read_reg(hart, n))
+                    .map_or(b"E01".to_vec(), |value| {
+                        hex(&value.to_le_bytes()).into_bytes()
+                    }),
+                b'P' => {
+                    let written = split_once(args, b'=').and_then(|(n, value)| {
+                        let value = unhex(value)?;
+                        let value = u32::from_le_bytes(value.get(..4)?.try_into().ok()?);
+                        write_reg(hart, parse_hex(n)?, value)
+                    });
+                    ok_or_error(written.is_some())
+                }
+                b'm' => match parse_addr_len(args) {
+                    // Each byte takes two hex digits in the reply
+                    Some((_addr, len)) if len as usize > PACKET_SIZE / 2 => b"E01".to_vec(),
+                    Some((addr, len)) => {
+                        let bytes: Option<Vec<u8>> = (0..len)
+                            .map(|i| hart.bus.load(addr.wrapping_add(i), 1).ok())
+                            .map(|byte| byte.map(|byte| byte as u8))
+                            .collect();
+                        bytes.map_or(b"E14".to_vec(), |bytes| hex(&bytes).into_bytes())
+                    }
+                    None => b"E01".to_vec(),
+                },
+                b'M' => {
+                    let written = split_once(args, b':').and_then(|(range, data)| {
+                        let (addr, _len) = parse_addr_len(range)?;
+                        for (i, byte) in unhex(data)?.into_iter().enumerate() {
+                            hart.bus
+                                .store(addr.wrapping_add(i as u32), 1, byte as u32)
+                                .ok()?;
+                        }
+                        Some(())
+                    });
+                    ok_or_error(written.is_some())
+                }
+                b'Z' | b'z' => {
+                    let mut fields = args.split(|&b| b == b',');
+                    let kind = fields.next();
+                    let addr = fields.next().and_then(parse_hex);
+                    match (kind, addr) {
+                        // Software and hardware breakpoints work the same
+                        (Some(b"0"), Some(addr)) | (Some(b"1"), Some(addr)) => {
+                            if command == b'Z' {
+                                self.breakpoints.insert(addr);
+                            } else {
+                                self.breakpoints.remove(&addr);
+                            }
+                            b"OK".to_vec()
+                        }
+                        // Watchpoints aren't supported
+                        _ => vec![],
+                    }
+                }
+                b's' | b'c' => {
+                    if let Some(addr) = parse_hex(args) {
+                        hart.set_pc(addr);
+                    }
+
+                    let stop = self.resume(hart, host, command == b's')?;
+                    self.last_stop = stop;
+                    let reply = self.stop_reply(stop);
+                    if let Stop::Exited(status) = stop {
+                        self.send(&reply)?;
+                        return Ok(Some(status));
+                    }
+                    reply
+                }
+                b'D' => {
+                    self.send(b"OK")?;
+                    return Ok(None);
+                }
+                b'k' => return Ok(None),
+                _ => vec![],
+            };
+
+            self.send(&reply)?;
+        }
+    }
+
+    /// Answer a `q` or `Q` packet
+    fn query(&mut self, packet: &[u8]) -> Vec<u8> {
+        let packet = String::from_utf8_lossy(packet);
+        let (name, args) = packet.split_once(':').unwrap_or((&packet, ""));
+
+        match name {
+            "qSupported" => format!(
+                "PacketSize={:x};qXfer:features:read+;swbreak+;QStartNoAckMode+",
+                PACKET_SIZE
+            )
+            .into_bytes(),
+            "QStartNoAckMode" => {
+                self.no_ack = true;
+                b"OK".to_vec()
+            }
+            "qAttached" => b"1".to_vec(),
+            "qC" => b"QC1".to_vec(),
+            "qfThreadInfo" => b"m1".to_vec(),
+            "qsThreadInfo" => b"l".to_vec(),
+            "qXfer" => {
+                // features:read:target.xml:offset,length
+                let mut fields = args.splitn(4, ':');
+                let request = (fields.next(), fields.next(), fields.next());
+                let range = fields
+                    .next()
+                    .and_then(|range| parse_addr_len(range.as_bytes()));
+                match (request, range) {
+                    ((Some("features"), Some("read"), Some("target.xml")), Some((offset, len))) => {
+                        let xml = target_xml();
+                        let start = (offset as usize).min(xml.len());
+                        let end = (start + len as usize).min(xml.len());
+                        let marker = if end == xml.len() { b'l' } else { b'm' };
+                        let mut reply = vec![marker];
+                        reply.extend_from_slice(&xml.as_bytes()[start..end]);
+                        reply
+                    }
+                    _ => b"E00".to_vec(),
+                }
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Run the hart until it hits a breakpoint, GDB interrupts it, or it has
+    /// run one instruction if `step` is set
+    fn resume<B: Bus>(
+        &mut self,
+        hart: &mut Hart<B>,
+        host: &mut Host,
+        step: bool,
+    ) -> io::Result<Stop> {
+        for ticks in 0_u64.. {
+            // Don't stop on the breakpoint that we're resuming from
+            if ticks != 0 && self.breakpoints.contains(&hart.pc()) {
+                return Ok(Stop::Breakpoint);
+            }
+
+            if ticks != 0 && ticks % POLL_INTERVAL == 0 && self.interrupted()? {
+                return Ok(Stop::Signal(SIGINT));
+            }
+
+            if let Some(stop) = tick(hart, host) {
+                return Ok(stop);
+            }
+
+            if step {
+                break;
+            }
+        }
+
+        Ok(Stop::Signal(SIGTRAP))
+    }
+
+    /// Check for the interrupt byte from GDB
+    fn interrupted(&mut self) -> io::Result<bool> {
+        let mut buf = [0; 64];
+        let n = self.conn.poll(&mut buf)?;
+        self.input.extend(&buf[..n]);
+
+        // Nothing else should arrive while the target runs
+        let interrupted = self.input.contains(&INTERRUPT);
+        self.input.retain(|&b| b != INTERRUPT);
+        Ok(interrupted)
+    }
+
+    fn stop_reply(&self, stop: Stop) -> Vec<u8> {
+        match stop {
+            Stop::Signal(signal) => format!("T{:02x}thread:1;", signal).into_bytes(),
+            Stop::Breakpoint => format!("T{:02x}thread:1;swbreak:;", SIGTRAP).into_bytes(),
+            Stop::Exited(status) => format!("W{:02x}", status as u8).into_bytes(),
+        }
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        if let Some(byte) = self.input.pop_front() {
+            return Ok(Some(byte));
+        }
+
+        let mut buf = [0; 256];
+        let n = self.conn.read(&mut buf)?;
+        if n == 0 {
+            return Ok(None);
+        }
+        self.input.extend(&buf[1..n]);
+        Ok(Some(buf[0]))
+    }
+
+    /// Read the contents of the next packet, acknowledging it
+    ///
+    /// Returns `None` once the connection closes.
+    fn read_packet(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            // Skip acks and interrupts until the start of a packet
+            loop {
+                match self.read_byte()? {
+                    Some(b'$') => break,
+                    Some(_) => continue,
+                    None => return Ok(None),
+                }
+            }
+
+            let mut data = vec![];
+            let mut sum: u8 = 0;
+            loop {
+                let byte = match self.read_byte()? {
+                    Some(byte) => byte,
+                    None => return Ok(None),
+                };
+                if byte == b'#' {
+                    break;
+                }
+                sum = sum.wrapping_add(byte);
+                data.push(byte);
+            }
+
+            let mut checksum = [0; 2];
+            for digit in &mut checksum {
+                *digit = match self.read_byte()? {
+                    Some(byte) => byte,
+                    None => return Ok(None),
+                };
+            }
+
+            if self.no_ack {
+                return Ok(Some(unescape(&data)));
+            }
+
+            if parse_hex(&checksum) == Some(sum as u32) {
+                self.conn.write_all(b"+")?;
+                self.conn.flush()?;
+                return Ok(Some(unescape(&data)));
+            }
+
+            // Ask for the packet again
+            self.conn.write_all(b"-")?;
+            self.conn.flush()?;
+        }
+    }
+
+    /// Send a packet holding `data`, and wait for GDB to acknowledge it
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut packet = vec![b'$'];
+        for &byte in data {
+            if matches!(byte, b'$' | b'#' | b'}' | b'*') {
+                packet.push(b'}');
+                packet.push(byte ^ 0x20);
+            } else {
+                packet.push(byte);
+            }
+        }
+        let sum = packet[1..].iter().fold(0_u8, |sum, &b| sum.wrapping_add(b));
+        packet.extend_from_slice(format!("#{:02x}", sum).as_bytes());
+
+        loop {
+            self.conn.write_all(&packet)?;
+            self.conn.flush()?;
+
+            if self.no_ack {
+                return Ok(());
+            }
+
+            match self.read_byte()? {
+                Some(b'-') => continue,
+                Some(b'+') | None => return Ok(()),
+                // Not an ack, so leave it for the next packet
+                Some(byte) => {
+                    self.input.push_front(byte);
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Like [`Host::tick`], but `ebreak`s that aren't semihosting stop the hart
+/// instead of trapping
+fn tick<B: Bus>(hart: &mut Hart<B>, host: &mut Host) -> Option<Stop> {
+    let lines = hart.bus.tick();
+    hart.csrs.set_lines(lines);
+
+    let trap = match hart.csrs.pending_interrupt() {
+        Some(interrupt) => Trap::Interrupt(interrupt),
+        None => {
+            let exception = hart.step().err()?;
+            match host.service(hart, exception) {
+                Some(Serviced::Exit(status)) => return Some(Stop::Exited(status)),
+                Some(Serviced::Continue) => return None,
+                None => {}
+            }
+            if let Exception::Breakpoint(_) = exception {
+                return Some(Stop::Signal(SIGTRAP));
+            }
+            Trap::Exception(exception)
+        }
+    };
+
+    hart.take_trap(trap);
+    None
+}
+
+/// Read the register that GDB numbers `n`
+fn read_reg<B: Bus>(hart: &Hart<B>, n: u32) -> Option<u32> {
+    match n {
+        0..=31 => Some(hart.reg(Reg::try_from(n).unwrap())),
+        PC_REGNUM => Some(hart.pc()),
+        PRIV_REGNUM => Some(hart.csrs.mode as u32),
+        _ if (CSR_REGNUM..PRIV_REGNUM).contains(&n) => hart.csrs.read((n - CSR_REGNUM) as u16),
+        _ => None,
+    }
+}
+
+/// Write `value` to the register that GDB numbers `n`
+fn write_reg<B: Bus>(hart: &mut Hart<B>, n: u32, value: u32) -> Option<()> {
+    match n {
+        0..=31 => hart.set_reg(Reg::try_from(n).unwrap(), value),
+        PC_REGNUM => hart.set_pc(value),
+        PRIV_REGNUM => {
+            hart.csrs.mode = match value {
+                0 => Mode::User,
+                3 => Mode::Machine,
+                _ => return None,
+            }
+        }
+        _ if (CSR_REGNUM..PRIV_REGNUM).contains(&n) => {
+            hart.csrs.write((n - CSR_REGNUM) as u16, value)?
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+/// The target description, which tells GDB how the registers are numbered
+pub fn target_xml() -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?>\n\
+         <!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n\
+         <target version=\"1.0\">\n\
+         <architecture>riscv:rv32</architecture>\n\
+         <feature name=\"org.gnu.gdb.riscv.cpu\">\n",
+    );
+
+    for n in 0_u32..32 {
+        let reg = Reg::try_from(n).unwrap();
+        let ty = match reg {
+            Reg::Ra => "code_ptr",
+            Reg::Sp | Reg::Gp | Reg::Tp | Reg::S0 => "data_ptr",
+            _ => "int",
+        };
+        xml += &format!(
+            "<reg name=\"{}\" bitsize=\"32\" type=\"{}\" regnum=\"{}\"/>\n",
+            reg, ty, n
+        );
+    }
+    xml += &format!(
+        "<reg name=\"pc\" bitsize=\"32\" type=\"code_ptr\" regnum=\"{}\"/>\n",
+        PC_REGNUM
+    );
+    xml += "</feature>\n<feature name=\"org.gnu.gdb.riscv.csr\">\n";
+
+    // Custom CSRs are included too, so that they can be seen by name
+    let custom = csr::custom().into_iter().map(|(_name, csr)| csr);
+    for csr in CSRS.iter().cloned().chain(custom) {
+        xml += &format!(
+            "<reg name=\"{}\" bitsize=\"32\" type=\"int\" regnum=\"{}\" group=\"csr\"/>\n",
+            csr::name_or_number(csr.num()),
+            CSR_REGNUM + csr.num() as u32
+        );
+    }
+    xml += "</feature>\n<feature name=\"org.gnu.gdb.riscv.virtual\">\n";
+    xml += &format!(
+        "<reg name=\"priv\" bitsize=\"32\" type=\"int\" regnum=\"{}\"/>\n",
+        PRIV_REGNUM
+    );
+    xml += "</feature>\n</target>\n";
+
+    xml
+}
+
+fn ok_or_error(ok: bool) -> Vec<u8> {
+    if ok {
+        b"OK".to_vec()
+    } else {
+        b"E01".to_vec()
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(digits: &[u8]) -> Option<Vec<u8>> {
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+    digits
+        .chunks(2)
+        .map(|pair| parse_hex(pair).map(|byte| byte as u8))
+        .collect()
+}
+
+fn parse_hex(digits: &[u8]) -> Option<u32> {
+    let digits = std::str::from_utf8(digits).ok()?;
+    u32::from_str_radix(digits, 16).ok()
+}
+
+/// Parse `addr,len` in hex
+fn parse_addr_len(args: &[u8]) -> Option<(u32, u32)> {
+    let (addr, len) = split_once(args, b',')?;
+    Some((parse_hex(addr)?, parse_hex(len)?))
+}
+
+fn split_once(bytes: &[u8], separator: u8) -> Option<(&[u8], &[u8])> {
+    let i = bytes.iter().position(|&b| b == separator)?;
+    Some((&bytes[..i], &bytes[i + 1..]))
+}
+
+/// Undo the escaping of `$`, `#`, `}`, and `*` in packet data
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![];
+    let mut iter = data.iter();
+    while let Some(&byte) = iter.next() {
+        if byte == b'}' {
+            if let Some(&escaped) = iter.next() {
+                bytes.push(escaped ^ 0x20);
+            }
+        } else {
+            bytes.push(byte);
+        }
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::net::TcpListener;
+    use std::thread;
+
+    use crate::emu::test::load;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    /// The GDB end of a connection
+    struct Client {
+        stream: TcpStream,
+    }
+
+    impl Client {
+        /// Send `data` as a packet, and return the reply
+        fn request(&mut self, data: &str) -> String {
+            let sum = data.bytes().fold(0_u8, |sum, b| sum.wrapping_add(b));
+            write!(self.stream, "${}#{:02x}", data, sum).unwrap();
+
+            assert_eq!(self.read_byte(), b'+', "Request {:?} wasn't acked", data);
+            assert_eq!(self.read_byte(), b'$');
+            let mut reply = vec![];
+            loop {
+                match self.read_byte() {
+                    b'#' => break,
+                    byte => reply.push(byte),
+                }
+            }
+            let checksum = [self.read_byte(), self.read_byte()];
+            let sum = reply.iter().fold(0_u8, |sum, &b| sum.wrapping_add(b));
+            assert_eq!(parse_hex(&checksum), Some(sum as u32));
+            self.stream.write_all(b"+").unwrap();
+
+            String::from_utf8(unescape(&reply)).unwrap()
+        }
+
+        fn read_byte(&mut self) -> u8 {
+            let mut byte = [0];
+            self.stream.read_exact(&mut byte).unwrap();
+            byte[0]
+        }
+    }
+
+    /// Serve GDB for `words` on a loopback socket, and connect to it
+    fn connect(words: &'static [u32]) -> (Client, thread::JoinHandle<Option<i32>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _addr) = listener.accept().unwrap();
+            stream.set_nodelay(true).unwrap();
+            let mut hart = load(words);
+            let mut host = Host::new();
+            GdbServer::new(stream).serve(&mut hart, &mut host).unwrap()
+        });
+
+        let stream = TcpStream::connect(addr).unwrap();
+        stream.set_nodelay(true).unwrap();
+        (Client { stream }, server)
+    }
+
+    #[test]
+    fn check_gdb_session() {
+        #[rustfmt::skip]
+        static WORDS: [u32; 5] = [
+            0x0010_0513, // li      a0, 1
+            0x0015_0513, // addi    a0, a0, 1
+            0x0015_0513, // addi    a0, a0, 1
+            0x05d0_0893, // li      a7, 93
+            0x0000_0073, // ecall
+        ];
+        let (mut gdb, server) = connect(&WORDS);
+
+        let supported = gdb.request("qSupported:swbreak+");
+        assert!(supported.starts_with("PacketSize=4000;"));
+        assert!(supported.contains("qXfer:features:read+"));
+        assert_eq!(gdb.request("?"), "T05thread:1;");
+
+        let xml = gdb.request("qXfer:features:read:target.xml:0,4000");
+        assert!(xml.starts_with("l<?xml"));
+        assert!(xml.contains("<architecture>riscv:rv32</architecture>"));
+        assert!(xml.contains("<reg name=\"mcause\" bitsize=\"32\" type=\"int\" regnum=\"899\""));
+        assert!(gdb
+            .request("qXfer:features:read:target.xml:0,10")
+            .starts_with("m"));
+
+        let regs = gdb.request("g");
+        assert_eq!(regs.len(), 33 * 8);
+        assert!(regs.chars().all(|c| c == '0'));
+
+        // Memory is little endian
+        assert_eq!(gdb.request("m0,8"), "1305100013051500");
+        assert_eq!(gdb.request("m4000,4"), "E14");
+        assert_eq!(gdb.request("m0,2000").len(), 0x4000);
+        assert_eq!(gdb.request("m0,2001"), "E01");
+        assert_eq!(gdb.request("m0,ffffffff"), "E01");
+        assert_eq!(gdb.request("M100,4:efbeadde"), "OK");
+        assert_eq!(gdb.request("m100,4"), "efbeadde");
+
+        assert_eq!(gdb.request("Z0,8,4"), "OK");
+        assert_eq!(gdb.request("c"), "T05thread:1;swbreak:;");
+        assert_eq!(gdb.request("p20"), "08000000");
+        assert_eq!(gdb.request("pa"), "02000000");
+
+        assert_eq!(gdb.request("z0,8,4"), "OK");
+        assert_eq!(gdb.request("s"), "T05thread:1;");
+        assert_eq!(gdb.request("p20"), "0c000000");
+
+        // CSRs and the privilege mode
+        assert_eq!(gdb.request("p381"), "00000000");
+        assert_eq!(gdb.request("P381=34120000"), "OK");
+        assert_eq!(gdb.request("p381"), "34120000");
+        assert_eq!(gdb.request("p1041"), "03000000");
+
+        assert_eq!(gdb.request("Pa=2a000000"), "OK");
+        assert_eq!(gdb.request("c"), "W2a");
+        assert_eq!(server.join().unwrap(), Some(42));
+    }
+
+    #[test]
+    fn check_gdb_ebreak() {
+        #[rustfmt::skip]
+        static WORDS: [u32; 2] = [
+            0x0010_0073, // ebreak
+            0x0000_0013, // nop
+        ];
+        let (mut gdb, server) = connect(&WORDS);
+
+        // The ebreak stops without trapping, and GDB steps past it
+        assert_eq!(gdb.request("c"), "T05thread:1;");
+        assert_eq!(gdb.request("p20"), "00000000");
+        assert_eq!(gdb.request("P20=04000000"), "OK");
+        assert_eq!(gdb.request("s"), "T05thread:1;");
+        assert_eq!(gdb.request("p20"), "08000000");
+
+        assert_eq!(gdb.request("D"), "OK");
+        assert_eq!(server.join().unwrap(), None);
+    }
+}
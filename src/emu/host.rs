@@ -0,0 +1,791 @@
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use super::{Bus, Commit, Exception, Hart, Trap};
+use crate::prelude::*;
+
+/// `slli zero, zero, 0x1f`, which comes before a semihosting `ebreak`
+const SEMIHOSTING_ENTRY: u32 = 0x01f0_1013;
+
+/// `srai zero, zero, 7`, which comes after a semihosting `ebreak`
+const SEMIHOSTING_EXIT: u32 = 0x4070_5013;
+
+/// Syscall numbers, from the Linux generic ABI that newlib's libgloss uses
+mod nr {
+    pub const OPENAT: u32 = 56;
+    pub const CLOSE: u32 = 57;
+    pub const LSEEK: u32 = 62;
+    pub const READ: u32 = 63;
+    pub const WRITE: u32 = 64;
+    pub const EXIT: u32 = 93;
+    pub const EXIT_GROUP: u32 = 94;
+    pub const BRK: u32 = 214;
+
+    /// libgloss's own `open`, which takes newlib's flags
+    pub const OPEN: u32 = 1024;
+}
+
+/// Semihosting operation numbers
+mod sys {
+    pub const OPEN: u32 = 0x01;
+    pub const CLOSE: u32 = 0x02;
+    pub const WRITEC: u32 = 0x03;
+    pub const WRITE0: u32 = 0x04;
+    pub const WRITE: u32 = 0x05;
+    pub const READ: u32 = 0x06;
+    pub const READC: u32 = 0x07;
+    pub const ERRNO: u32 = 0x13;
+    pub const EXIT: u32 = 0x18;
+    pub const EXIT_EXTENDED: u32 = 0x20;
+
+    /// The `SYS_EXIT` reason for a normal exit
+    pub const ADP_STOPPED_APPLICATION_EXIT: u32 = 0x2_0026;
+}
+
+const EBADF: i32 = 9;
+const EFAULT: i32 = 14;
+const EINVAL: i32 = 22;
+const ESPIPE: i32 = 29;
+
+/// `openat`'s `dirfd` for paths relative to the working directory
+const AT_FDCWD: i32 = -100;
+
+/// Flags for `open`, as (newlib value, Linux value)
+const O_ACCMODE: (u32, u32) = (0o3, 0o3);
+const O_APPEND: (u32, u32) = (0x0008, 0o2000);
+const O_CREAT: (u32, u32) = (0x0200, 0o100);
+const O_TRUNC: (u32, u32) = (0x0400, 0o1000);
+const O_EXCL: (u32, u32) = (0x0800, 0o200);
+
+/// Something the host did while running a hart
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The hart took a trap that the host didn't service
+    Trap(Trap),
+
+    /// The program exited with a status code
+    Exit(i32),
+}
+
+/// The outcome of a system call that the host serviced
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Serviced {
+    /// The call returned to the program
+    Continue,
+
+    /// The program exited with a status code
+    Exit(i32),
+}
+
+/// An open file
+enum Handle {
+    Stdin,
+    Stdout,
+    Stderr,
+    File(File),
+}
+
+/// Services system calls from programs running on a hart, using the host's
+/// files and standard streams
+///
+/// Two conventions are supported:
+/// - `ecall` with a Linux/newlib syscall number in `a7` and arguments in
+///   `a0` to `a5`, e.g. from newlib's libgloss
+/// - RISC-V semihosting, where an `ebreak` between `slli zero, zero, 0x1f`
+///   and `srai zero, zero, 7` passes an operation in `a0` and its argument
+///   in `a1`
+///
+/// Calls that aren't recognized trap as usual, so firmware that uses `ecall`
+/// for its own purposes still works.
+pub struct Host {
+    stdin: Box<dyn Read>,
+    stdout: Box<dyn Write>,
+    stderr: Box<dyn Write>,
+
+    /// Open files, by descriptor
+    handles: BTreeMap<u32, Handle>,
+
+    /// The current program break
+    brk: u32,
+
+    /// The error from the last failed semihosting call
+    errno: i32,
+}
+
+impl Default for Host {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Host {
+    /// Create a host that uses the process's standard streams
+    pub fn new() -> Self {
+        let mut handles = BTreeMap::new();
+        handles.insert(0, Handle::Stdin);
+        handles.insert(1, Handle::Stdout);
+        handles.insert(2, Handle::Stderr);
+
+        Host {
+            stdin: Box::new(io::stdin()),
+            stdout: Box::new(io::stdout()),
+            stderr: Box::new(io::stderr()),
+            handles,
+            brk: 0,
+            errno: 0,
+        }
+    }
+
+    pub fn stdin(mut self, stdin: impl Read + 'static) -> Self {
+        self.stdin = Box::new(stdin);
+        self
+    }
+
+    pub fn stdout(mut self, stdout: impl Write + 'static) -> Self {
+        self.stdout = Box::new(stdout);
+        self
+    }
+
+    pub fn stderr(mut self, stderr: impl Write + 'static) -> Self {
+        self.stderr = Box::new(stderr);
+        self
+    }
+
+    /// Start the program break at `brk`, which is usually the end of the
+    /// image (see [`super::Image::end`])
+    pub fn brk(mut self, brk: u32) -> Self {
+        self.brk = brk;
+        self
+    }
+
+    /// Like [`Hart::tick`], but services any system call before it traps
+    ///
+    /// Returns the trap that was taken, or the exit status if the program
+    /// exited.
+    pub fn tick<B: Bus>(&mut self, hart: &mut Hart<B>) -> Option<Event> {
+        let lines = hart.bus.tick();
+        hart.csrs.set_lines(lines);
+
+        let trap = match hart.csrs.pending_interrupt() {
+            Some(interrupt) => Trap::Interrupt(interrupt),
+            None => {
+                let exception = hart.step().err()?;
+                match self.service(hart, exception) {
+                    Some(Serviced::Exit(status)) => return Some(Event::Exit(status)),
+                    Some(Serviced::Continue) => return None,
+                    None => Trap::Exception(exception),
+                }
+            }
+        };
+
+        hart.take_trap(trap);
+        Some(Event::Trap(trap))
+    }
+
+    /// Run `hart` until the program exits, or `max_steps` ticks have passed
+    ///
+    /// Returns the exit status, if the program exited.
+    pub fn run<B: Bus>(&mut self, hart: &mut Hart<B>, max_steps: u64) -> Option<i32> {
+        for _ in 0..max_steps {
+            if let Some(Event::Exit(status)) = self.tick(hart) {
+                return Some(status);
+            }
+        }
+        None
+    }
+
+    /// Service the system call that raised `exception`, if it is one
+    ///
+    /// `hart` must still be on the instruction that raised the exception.
+    /// On success, the instruction retires and the result is written back.
+    ///
+    /// Returns `None` if `exception` isn't a system call that the host
+    /// knows.
+    pub fn service<B: Bus>(
+        &mut self,
+        hart: &mut Hart<B>,
+        exception: Exception,
+    ) -> Option<Serviced> {
+        let result = match exception {
+            Exception::EnvironmentCall(_) => self.syscall(hart)?,
+            Exception::Breakpoint(pc) if is_semihosting(hart, pc) => self.semihosting(hart)?,
+            _ => return None,
+        };
+
+        match result {
+            Ok(value) => {
+                let pc = hart.pc();
+                let fetched = hart.fetch().ok();
+                hart.set_reg(Reg::A0, value);
+                hart.set_pc(pc.wrapping_add(4));
+
+                // The call retires like any other instruction
+                if let Some((word, instr)) = fetched {
+                    let mode = hart.csrs.mode;
+                    hart.retire(Commit {
+                        pc,
+                        word,
+                        instr,
+                        mode,
+                        reg: Some((Reg::A0, value)),
+                        csr: None,
+                        mem: None,
+                        next_pc: pc.wrapping_add(4),
+                    });
+                }
+                Some(Serviced::Continue)
+            }
+            Err(status) => Some(Serviced::Exit(status)),
+        }
+    }
+
+    /// Service an `ecall`
+    ///
+    /// Returns `Ok` with the value for `a0`, or `Err` with an exit status.
+    fn syscall<B: Bus>(&mut self, hart: &mut Hart<B>) -> Option<std::result::Result<u32, i32>> {
+        let args = [
+            hart.reg(Reg::A0),
+            hart.reg(Reg::A1),
+            hart.reg(Reg::A2),
+            hart.reg(Reg::A3),
+        ];
+
+        // Errors are returned as negated errno values
+        let errno = |result: std::result::Result<u32, i32>| match result {
+            Ok(value) => value,
+            Err(errno) => (-errno) as u32,
+        };
+
+        let value = match hart.reg(Reg::A7) {
+            nr::EXIT | nr::EXIT_GROUP => return Some(Err(args[0] as i32)),
+            nr::READ => errno(self.read(&mut hart.bus, args[0], args[1], args[2])),
+            nr::WRITE => errno(self.write(&mut hart.bus, args[0], args[1], args[2])),
+            nr::OPEN => errno(self.open(&mut hart.bus, args[0], args[1], false)),
+            nr::OPENAT if args[0] as i32 == AT_FDCWD => {
+                errno(self.open(&mut hart.bus, args[1], args[2], true))
+            }
+            nr::OPENAT => errno(Err(EINVAL)),
+            nr::CLOSE => errno(self.close(args[0]).map(|_| 0)),
+            nr::LSEEK => errno(self.lseek(args[0], args[1] as i32, args[2])),
+            nr::BRK => {
+                // Memory is all there already, so the break can go anywhere
+                // past where it started
+                if args[0] >= self.brk {
+                    self.brk = args[0];
+                }
+                self.brk
+            }
+            _ => return None,
+        };
+
+        Some(Ok(value))
+    }
+
+    /// Service a semihosting `ebreak`
+    ///
+    /// Returns `Ok` with the value for `a0`, or `Err` with an exit status.
+    fn semihosting<B: Bus>(&mut self, hart: &mut Hart<B>) -> Option<std::result::Result<u32, i32>> {
+        let op = hart.reg(Reg::A0);
+        let arg = hart.reg(Reg::A1);
+        let bus = &mut hart.bus;
+
+        // Most operations take a block of words
+        let param = |bus: &mut B, n: u32| bus.load(arg.wrapping_add(4 * n), 4).unwrap_or(0);
+
+        let result = match op {
+            sys::OPEN => {
+                let (name, mode) = (param(bus, 0), param(bus, 1));
+                self.semihosting_open(bus, name, mode)
+            }
+            sys::CLOSE => self.close(param(bus, 0)).map(|_| 0),
+            sys::WRITEC => {
+                let bytes = read_bytes(bus, arg, 1).unwrap_or_default();
+                let _ = self.stdout.write_all(&bytes);
+                Ok(0)
+            }
+            sys::WRITE0 => {
+                let bytes = read_c_str(bus, arg).unwrap_or_default();
+                let _ = self.stdout.write_all(&bytes);
+                Ok(0)
+            }
+            // Reads and writes return the number of bytes that weren't
+            // transferred
+            sys::WRITE => {
+                let (handle, addr, len) = (param(bus, 0), param(bus, 1), param(bus, 2));
+                self.write(bus, handle, addr, len).map(|n| len - n)
+            }
+            sys::READ => {
+                let (handle, addr, len) = (param(bus, 0), param(bus, 1), param(bus, 2));
+                self.read(bus, handle, addr, len).map(|n| len - n)
+            }
+            sys::READC => {
+                let mut byte = [0];
+                match self.stdin.read(&mut byte) {
+                    Ok(1) => Ok(byte[0] as u32),
+                    _ => Ok(u32::MAX),
+                }
+            }
+            sys::ERRNO => Ok(self.errno as u32),
+            // On RV32 the argument is the reason itself, which only says
+            // whether the program succeeded
+            sys::EXIT => {
+                let status = if arg == sys::ADP_STOPPED_APPLICATION_EXIT {
+                    0
+                } else {
+                    1
+                };
+                return Some(Err(status));
+            }
+            sys::EXIT_EXTENDED => return Some(Err(param(bus, 1) as i32)),
+            _ => return None,
+        };
+
+        // Errors are returned as -1, with the reason saved for SYS_ERRNO
+        let value = result.unwrap_or_else(|errno| {
+            self.errno = errno;
+            u32::MAX
+        });
+
+        Some(Ok(value))
+    }
+
+    /// Read up to `len` bytes from `fd` into memory at `addr`
+    ///
+    /// Returns the number of bytes read.
+    fn read<B: Bus>(
+        &mut self,
+        bus: &mut B,
+        fd: u32,
+        addr: u32,
+        len: u32,
+    ) -> std::result::Result<u32, i32> {
+        // The guest picks `len`, so this goes through a fixed-size buffer
+        // instead of allocating `len` bytes up front
+        let mut buffer = [0; 4096];
+        let mut total = 0;
+        while total < len {
+            let want = (len - total).min(buffer.len() as u32) as usize;
+            let read = match self.handles.get_mut(&fd).ok_or(EBADF)? {
+                Handle::Stdin => self.stdin.read(&mut buffer[..want]),
+                Handle::File(file) => file.read(&mut buffer[..want]),
+                Handle::Stdout | Handle::Stderr => return Err(EBADF),
+            };
+
+            // Like Linux, an error after some bytes were copied ends the read
+            // early instead of failing it
+            let n = match read.map_err(errno) {
+                Ok(n) => n,
+                Err(_) if total != 0 => break,
+                Err(err) => return Err(err),
+            };
+            match write_bytes(bus, addr.wrapping_add(total), &buffer[..n]) {
+                Ok(()) => {}
+                Err(_) if total != 0 => break,
+                Err(err) => return Err(err),
+            }
+            total += n as u32;
+
+            // Short reads mean that there's nothing more for now, like at the
+            // end of a file or a line typed into a terminal
+            if n < want {
+                break;
+            }
+        }
+        Ok(total)
+    }
+
+    /// Write `len` bytes from memory at `addr` to `fd`
+    ///
+    /// Returns the number of bytes written.
+    fn write<B: Bus>(
+        &mut self,
+        bus: &mut B,
+        fd: u32,
+        addr: u32,
+        len: u32,
+    ) -> std::result::Result<u32, i32> {
+        let bytes = read_bytes(bus, addr, len)?;
+        match self.handles.get_mut(&fd).ok_or(EBADF)? {
+            Handle::Stdout => self.stdout.write_all(&bytes),
+            Handle::Stderr => self.stderr.write_all(&bytes),
+            Handle::File(file) => file.write_all(&bytes),
+            Handle::Stdin => return Err(EBADF),
+        }
+        .map_err(errno)?;
+
+        Ok(len)
+    }
+
+    /// Open the file named by the string at `name`
+    ///
+    /// `flags` are Linux's if `linux` is set, and otherwise newlib's.
+    fn open<B: Bus>(
+        &mut self,
+        bus: &mut B,
+        name: u32,
+        flags: u32,
+        linux: bool,
+    ) -> std::result::Result<u32, i32> {
+        let path = read_c_str(bus, name)?;
+        let path = String::from_utf8(path).map_err(|_| EINVAL)?;
+
+        let flag = |(newlib, linux_value): (u32, u32)| {
+            let value = if linux { linux_value } else { newlib };
+            flags & value == value
+        };
+        let access = flags & O_ACCMODE.0;
+
+        let mut options = OpenOptions::new();
+        options
+            .read(access == 0 || access == 2)
+            .write(access == 1 || access == 2)
+            .append(flag(O_APPEND))
+            .truncate(flag(O_TRUNC));
+        if flag(O_EXCL) {
+            options.create_new(flag(O_CREAT));
+        } else {
+            options.create(flag(O_CREAT));
+        }
+
+        let file = options.open(path).map_err(errno)?;
+        Ok(self.insert(Handle::File(file)))
+    }
+
+    /// Open a file for semihosting's `SYS_OPEN`
+    ///
+    /// `mode` is an index into the `fopen` modes:
+    /// `r`, `rb`, `r+`, `r+b`, `w`, `wb`, `w+`, `w+b`, `a`, `ab`, `a+`, `a+b`.
+    /// The special name `:tt` opens stdin, stdout, or stderr for the
+    /// `r`, `w`, and `a` modes.
+    fn semihosting_open<B: Bus>(
+        &mut self,
+        bus: &mut B,
+        name: u32,
+        mode: u32,
+    ) -> std::result::Result<u32, i32> {
+        let path = read_c_str(bus, name)?;
+        let path = String::from_utf8(path).map_err(|_| EINVAL)?;
+
+        let kind = mode / 4;
+        let plus = mode & 0b10 != 0;
+
+        if path == ":tt" {
+            let handle = match kind {
+                0 => Handle::Stdin,
+                1 => Handle::Stdout,
+                _ => Handle::Stderr,
+            };
+            return Ok(self.insert(handle));
+        }
+
+        let mut options = OpenOptions::new();
+        match kind {
+            0 => options.read(true).write(plus),
+            1 => options.read(plus).write(true).create(true).truncate(true),
+            2 => options.read(plus).append(true).create(true),
+            _ => return Err(EINVAL),
+        };
+
+        let file = options.open(path).map_err(errno)?;
+        Ok(self.insert(Handle::File(file)))
+    }
+
+    /// Add `handle` to the table with the lowest free descriptor
+    fn insert(&mut self, handle: Handle) -> u32 {
+        let fd = (0..).find(|fd| !self.handles.contains_key(fd)).unwrap();
+        self.handles.insert(fd, handle);
+        fd
+    }
+
+    fn close(&mut self, fd: u32) -> std::result::Result<(), i32> {
+        self.handles.remove(&fd).map(|_| ()).ok_or(EBADF)
+    }
+
+    fn lseek(&mut self, fd: u32, offset: i32, whence: u32) -> std::result::Result<u32, i32> {
+        let pos = match whence {
+            0 => SeekFrom::Start(offset as u32 as u64),
+            1 => SeekFrom::Current(offset as i64),
+            2 => SeekFrom::End(offset as i64),
+            _ => return Err(EINVAL),
+        };
+
+        match self.handles.get_mut(&fd).ok_or(EBADF)? {
+            Handle::File(file) => file.seek(pos).map(|pos| pos as u32).map_err(errno),
+            _ => Err(ESPIPE),
+        }
+    }
+}
+
+/// Whether the `ebreak` at `pc` is surrounded by the semihosting sequence
+fn is_semihosting<B: Bus>(hart: &mut Hart<B>, pc: u32) -> bool {
+    let mut word = |addr: u32| hart.bus.load(addr, 4).ok();
+    word(pc.wrapping_sub(4)) == Some(SEMIHOSTING_ENTRY)
+        && word(pc.wrapping_add(4)) == Some(SEMIHOSTING_EXIT)
+}
+
+/// The errno that the guest sees for an error on the host
+fn errno(err: io::Error) -> i32 {
+    match err.raw_os_error() {
+        Some(errno) => errno,
+        None => EINVAL,
+    }
+}
+
+fn read_bytes<B: Bus>(bus: &mut B, addr: u32, len: u32) -> std::result::Result<Vec<u8>, i32> {
+    (0..len)
+        .map(|i| {
+            bus.load(addr.wrapping_add(i), 1)
+                .map(|byte| byte as u8)
+                .map_err(|_| EFAULT)
+        })
+        .collect()
+}
+
+fn write_bytes<B: Bus>(bus: &mut B, addr: u32, bytes: &[u8]) -> std::result::Result<(), i32> {
+    for (i, &byte) in bytes.iter().enumerate() {
+        bus.store(addr.wrapping_add(i as u32), 1, byte as u32)
+            .map_err(|_| EFAULT)?;
+    }
+    Ok(())
+}
+
+/// Read a NUL-terminated string from memory at `addr`
+fn read_c_str<B: Bus>(bus: &mut B, addr: u32) -> std::result::Result<Vec<u8>, i32> {
+    let mut bytes = vec![];
+    for i in 0.. {
+        let byte = bus.load(addr.wrapping_add(i), 1).map_err(|_| EFAULT)? as u8;
+        if byte == 0 {
+            break;
+        }
+        bytes.push(byte);
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::csr::Mode;
+    use crate::emu::test::{load, SharedBuffer};
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    #[test]
+    fn check_syscalls() {
+        #[rustfmt::skip]
+        let mut hart = load(&[
+            0x0010_0513, // li      a0, 1
+            0x0000_25b7, // lui     a1, 0x2         ┐ li a1, 0x1800
+            0x8005_8593, // addi    a1, a1, -2048   ┘
+            0x0060_0613, // li      a2, 6
+            0x0400_0893, // li      a7, 64
+            0x0000_0073, // ecall                   # write(1, 0x1800, 6)
+            0x0005_0413, // mv      s0, a0
+            0x0000_0513, // li      a0, 0
+            0x0d60_0893, // li      a7, 214
+            0x0000_0073, // ecall                   # brk(0)
+            0x0005_0493, // mv      s1, a0
+            0x1005_0513, // addi    a0, a0, 256
+            0x0000_0073, // ecall                   # brk(a0)
+            0x0005_0913, // mv      s2, a0
+            0x0000_0513, // li      a0, 0
+            0x0000_25b7, // lui     a1, 0x2         ┐ li a1, 0x1c00
+            0xc005_8593, // addi    a1, a1, -1024   ┘
+            0x0100_0613, // li      a2, 16
+            0x03f0_0893, // li      a7, 63
+            0x0000_0073, // ecall                   # read(0, 0x1c00, 16)
+            0x0005_0993, // mv      s3, a0
+            0x0070_0513, // li      a0, 7
+            0x0400_0893, // li      a7, 64
+            0x0000_0073, // ecall                   # write(7, 0x1c00, 16)
+            0x0005_0a13, // mv      s4, a0
+            0x02a0_0513, // li      a0, 42
+            0x05d0_0893, // li      a7, 93
+            0x0000_0073, // ecall                   # exit(42)
+        ]);
+        hart.bus.write_bytes(0x1800, b"hello\n").unwrap();
+
+        let stdout = SharedBuffer::default();
+        let mut host = Host::new()
+            .stdin(&b"input"[..])
+            .stdout(stdout.clone())
+            .brk(0x1000);
+
+        assert_eq!(host.run(&mut hart, 100), Some(42));
+        assert_eq!(stdout.0.borrow().as_slice(), b"hello\n");
+        assert_eq!(hart.reg(Reg::S0), 6);
+        assert_eq!(hart.reg(Reg::S1), 0x1000);
+        assert_eq!(hart.reg(Reg::S2), 0x1100);
+        assert_eq!(hart.reg(Reg::S3), 5);
+        assert_eq!(hart.bus.read_bytes(0x1c00, 5).unwrap(), b"input");
+        assert_eq!(hart.reg(Reg::S4), -EBADF as u32);
+
+        // The exit itself doesn't retire
+        assert_eq!(hart.pc(), 0x6c);
+        assert_eq!(hart.csrs.instret, 27);
+    }
+
+    #[test]
+    fn check_files() {
+        #[rustfmt::skip]
+        let mut hart = load(&[
+            0xf9c0_0513, // li      a0, -100
+            0x0000_25b7, // lui     a1, 0x2         ┐ li a1, 0x1900
+            0x9005_8593, // addi    a1, a1, -1792   ┘
+            0x2410_0613, // li      a2, 0x241
+            0x0380_0893, // li      a7, 56
+            0x0000_0073, // ecall                   # openat(AT_FDCWD, 0x1900, O_WRONLY | O_CREAT | O_TRUNC)
+            0x0005_0413, // mv      s0, a0
+            0x0000_25b7, // lui     a1, 0x2         ┐ li a1, 0x1800
+            0x8005_8593, // addi    a1, a1, -2048   ┘
+            0x0060_0613, // li      a2, 6
+            0x0400_0893, // li      a7, 64
+            0x0000_0073, // ecall                   # write(s0, 0x1800, 6)
+            0x0004_0513, // mv      a0, s0
+            0x0390_0893, // li      a7, 57
+            0x0000_0073, // ecall                   # close(s0)
+            0x0000_2537, // lui     a0, 0x2         ┐ li a0, 0x1900
+            0x9005_0513, // addi    a0, a0, -1792   ┘
+            0x0000_0593, // li      a1, 0
+            0x4000_0893, // li      a7, 1024
+            0x0000_0073, // ecall                   # open(0x1900, O_RDONLY)
+            0x0005_0493, // mv      s1, a0
+            0x0000_25b7, // lui     a1, 0x2         ┐ li a1, 0x1c00
+            0xc005_8593, // addi    a1, a1, -1024   ┘
+            0x0100_0613, // li      a2, 16
+            0x03f0_0893, // li      a7, 63
+            0x0000_0073, // ecall                   # read(s1, 0x1c00, 16)
+            0x0005_0913, // mv      s2, a0
+            0x0000_2537, // lui     a0, 0x2         ┐ li a0, 0x1a00
+            0xa005_0513, // addi    a0, a0, -1536   ┘
+            0x0000_0593, // li      a1, 0
+            0x4000_0893, // li      a7, 1024
+            0x0000_0073, // ecall                   # open(0x1a00, O_RDONLY)
+            0x0005_0993, // mv      s3, a0
+            0x05e0_0893, // li      a7, 94
+            0x0000_0073, // ecall                   # exit_group(a0)
+        ]);
+
+        let dir = std::env::temp_dir().join(format!("riscv-asm-host-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.txt");
+        let mut name = path.to_str().unwrap().as_bytes().to_vec();
+        name.push(0);
+        hart.bus.write_bytes(0x1800, b"hello\n").unwrap();
+        hart.bus.write_bytes(0x1900, &name).unwrap();
+        hart.bus
+            .write_bytes(0x1a00, b"/nonexistent/file\0")
+            .unwrap();
+
+        let mut host = Host::new();
+        let status = host.run(&mut hart, 100);
+        let contents = std::fs::read(&path);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(contents.unwrap(), b"hello\n");
+        assert_eq!(hart.reg(Reg::S0), 3);
+        assert_eq!(hart.reg(Reg::S1), 3);
+        assert_eq!(hart.reg(Reg::S2), 6);
+        assert_eq!(hart.bus.read_bytes(0x1c00, 6).unwrap(), b"hello\n");
+
+        // ENOENT
+        assert_eq!(hart.reg(Reg::S3), -2_i32 as u32);
+        assert_eq!(status, Some(-2));
+    }
+
+    #[test]
+    fn check_read_bounds() {
+        #[rustfmt::skip]
+        let mut hart = load(&[
+            0x0000_0513, // li      a0, 0
+            0x0000_25b7, // lui     a1, 0x2         ┐ li a1, 0x1800
+            0x8005_8593, // addi    a1, a1, -2048   ┘
+            0xfff0_0613, // li      a2, -1
+            0x03f0_0893, // li      a7, 63
+            0x0000_0073, // ecall                   # read(0, 0x1800, 0xffffffff)
+            0x0005_0413, // mv      s0, a0
+            0x0000_0513, // li      a0, 0
+            0x8000_05b7, // lui     a1, 0x80000
+            0x0100_0613, // li      a2, 16
+            0x0000_0073, // ecall                   # read(0, 0x80000000, 16)
+            0x0005_0493, // mv      s1, a0
+            0x05e0_0893, // li      a7, 94
+            0x0000_0073, // ecall                   # exit_group(a0)
+        ]);
+
+        // Each of these is a separate read from the host
+        let stdin = (&b"abc"[..]).chain(&b"def"[..]);
+        let mut host = Host::new().stdin(stdin);
+
+        assert_eq!(host.run(&mut hart, 100), Some(-EFAULT));
+        assert_eq!(hart.reg(Reg::S0), 3);
+        assert_eq!(hart.bus.read_bytes(0x1800, 4).unwrap(), b"abc\0");
+        assert_eq!(hart.reg(Reg::S1), -EFAULT as u32);
+    }
+
+    #[test]
+    fn check_semihosting() {
+        #[rustfmt::skip]
+        let mut hart = load(&[
+            0x0040_0513, // li      a0, 4
+            0x0000_25b7, // lui     a1, 0x2         ┐ li a1, 0x1800
+            0x8005_8593, // addi    a1, a1, -2048   ┘
+            0x01f0_1013, // slli    zero, zero, 31
+            0x0010_0073, // ebreak                  # SYS_WRITE0
+            0x4070_5013, // srai    zero, zero, 7
+            0x0010_0513, // li      a0, 1
+            0x0000_25b7, // lui     a1, 0x2         ┐ li a1, 0x1900
+            0x9005_8593, // addi    a1, a1, -1792   ┘
+            0x01f0_1013, // slli    zero, zero, 31
+            0x0010_0073, // ebreak                  # SYS_OPEN
+            0x4070_5013, // srai    zero, zero, 7
+            0x0005_0413, // mv      s0, a0
+            0x0200_0513, // li      a0, 32
+            0x0000_25b7, // lui     a1, 0x2         ┐ li a1, 0x1a00
+            0xa005_8593, // addi    a1, a1, -1536   ┘
+            0x01f0_1013, // slli    zero, zero, 31
+            0x0010_0073, // ebreak                  # SYS_EXIT_EXTENDED
+            0x4070_5013, // srai    zero, zero, 7
+        ]);
+        let words =
+            |words: &[u32]| -> Vec<u8> { words.iter().flat_map(|w| w.to_le_bytes()).collect() };
+        hart.bus.write_bytes(0x1800, b"hi\n\0").unwrap();
+        hart.bus
+            .write_bytes(0x1900, &words(&[0x1980, 4, 3]))
+            .unwrap();
+        hart.bus.write_bytes(0x1980, b":tt\0").unwrap();
+        hart.bus
+            .write_bytes(0x1a00, &words(&[0x2_0026, 3]))
+            .unwrap();
+
+        let stdout = SharedBuffer::default();
+        let mut host = Host::new().stdout(stdout.clone());
+
+        assert_eq!(host.run(&mut hart, 100), Some(3));
+        assert_eq!(stdout.0.borrow().as_slice(), b"hi\n");
+        assert_eq!(hart.reg(Reg::S0), 3);
+    }
+
+    #[test]
+    fn check_unserviced() {
+        #[rustfmt::skip]
+        let mut hart = load(&[
+            0x3e80_0893, // li      a7, 1000
+            0x0000_0073, // ecall
+        ]);
+        hart.csrs.write(crate::csr::MTVEC.num(), 0x100).unwrap();
+
+        let mut host = Host::new();
+        assert_eq!(host.tick(&mut hart), None);
+        assert_eq!(
+            host.tick(&mut hart),
+            Some(Event::Trap(Trap::Exception(Exception::EnvironmentCall(
+                Mode::Machine
+            ))))
+        );
+        assert_eq!(hart.pc(), 0x100);
+    }
+}
@@ -0,0 +1,949 @@
+//! An instruction-set simulator for RV32I
+//!
+//! A [`Hart`] fetches words from a [`Bus`], decodes them into [`Instr`]s, and
+//! executes them one at a time.
+//! Anything that would trap is returned from [`Hart::step`] as an
+//! [`Exception`], leaving `pc` on the instruction that caused it.
+//! [`Hart::tick`] instead behaves like the hardware would, and takes
+//! exceptions and interrupts through `mtvec`.
+
+use std::fmt;
+
+use crate::csr::Mode;
+use crate::prelude::*;
+
+mod bus;
+mod clint;
+mod csrs;
+mod device;
+mod elf;
+pub mod gdb;
+mod host;
+mod plic;
+mod profile;
+mod timing;
+mod trace;
+mod uart;
+
+pub use bus::*;
+pub use clint::*;
+pub use csrs::*;
+pub use device::*;
+pub use elf::*;
+pub use host::*;
+pub use plic::*;
+pub use profile::*;
+pub use timing::*;
+pub use trace::*;
+pub use uart::*;
+
+/// A synchronous exception raised by an instruction
+///
+/// The values held by each variant are what the hardware would write to
+/// `mtval`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Exception {
+    /// A jump or branch to an address that isn't 4-byte aligned
+    InstructionAddressMisaligned(u32),
+    InstructionAccessFault(u32),
+
+    /// The word that failed to decode or execute
+    IllegalInstruction(u32),
+
+    /// The address of the `ebreak`
+    Breakpoint(u32),
+
+    LoadAddressMisaligned(u32),
+    LoadAccessFault(u32),
+    StoreAddressMisaligned(u32),
+    StoreAccessFault(u32),
+
+    /// An `ecall` from the given mode
+    EnvironmentCall(Mode),
+}
+
+impl Exception {
+    /// The exception code, as written to `mcause`
+    pub fn code(&self) -> u32 {
+        use Exception::*;
+
+        match *self {
+            InstructionAddressMisaligned(_) => 0,
+            InstructionAccessFault(_) => 1,
+            IllegalInstruction(_) => 2,
+            Breakpoint(_) => 3,
+            LoadAddressMisaligned(_) => 4,
+            LoadAccessFault(_) => 5,
+            StoreAddressMisaligned(_) => 6,
+            StoreAccessFault(_) => 7,
+            EnvironmentCall(mode) => 8 + mode as u32,
+        }
+    }
+
+    /// The trap value, as written to `mtval`
+    pub fn tval(&self) -> u32 {
+        use Exception::*;
+
+        match *self {
+            InstructionAddressMisaligned(tval)
+            | InstructionAccessFault(tval)
+            | IllegalInstruction(tval)
+            | Breakpoint(tval)
+            | LoadAddressMisaligned(tval)
+            | LoadAccessFault(tval)
+            | StoreAddressMisaligned(tval)
+            | StoreAccessFault(tval) => tval,
+            EnvironmentCall(_) => 0,
+        }
+    }
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Exception::*;
+
+        match *self {
+            InstructionAddressMisaligned(addr) => {
+                write!(f, "instruction address misaligned ({:#010x})", addr)
+            }
+            InstructionAccessFault(addr) => write!(f, "instruction access fault ({:#010x})", addr),
+            IllegalInstruction(word) => write!(f, "illegal instruction ({:#010x})", word),
+            Breakpoint(addr) => write!(f, "breakpoint ({:#010x})", addr),
+            LoadAddressMisaligned(addr) => write!(f, "load address misaligned ({:#010x})", addr),
+            LoadAccessFault(addr) => write!(f, "load access fault ({:#010x})", addr),
+            StoreAddressMisaligned(addr) => write!(f, "store address misaligned ({:#010x})", addr),
+            StoreAccessFault(addr) => write!(f, "store access fault ({:#010x})", addr),
+            EnvironmentCall(mode) => write!(f, "environment call from {} mode", mode),
+        }
+    }
+}
+
+impl std::error::Error for Exception {}
+
+/// An asynchronous interrupt to M-mode
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Interrupt {
+    MachineSoftware,
+    MachineTimer,
+    MachineExternal,
+}
+
+impl Interrupt {
+    /// Interrupts in the order that they're taken when several are pending
+    pub const BY_PRIORITY: [Interrupt; 3] = [
+        Interrupt::MachineExternal,
+        Interrupt::MachineSoftware,
+        Interrupt::MachineTimer,
+    ];
+
+    /// The bits of `mie` and `mip` for every interrupt
+    pub const ALL_BITS: u32 = (1 << 3) | (1 << 7) | (1 << 11);
+
+    /// The interrupt code, as written to `mcause`
+    pub fn code(&self) -> u32 {
+        match *self {
+            Interrupt::MachineSoftware => 3,
+            Interrupt::MachineTimer => 7,
+            Interrupt::MachineExternal => 11,
+        }
+    }
+
+    /// The bit for this interrupt in `mie` and `mip`
+    pub fn bit(&self) -> u32 {
+        1 << self.code()
+    }
+}
+
+/// Anything that transfers control to a trap handler
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Trap {
+    Exception(Exception),
+    Interrupt(Interrupt),
+}
+
+impl Trap {
+    /// The value written to `mcause`
+    ///
+    /// The top bit is set for interrupts.
+    pub fn cause(&self) -> u32 {
+        match self {
+            Trap::Exception(exception) => exception.code(),
+            Trap::Interrupt(interrupt) => (1 << 31) | interrupt.code(),
+        }
+    }
+
+    /// The value written to `mtval`
+    pub fn tval(&self) -> u32 {
+        match self {
+            Trap::Exception(exception) => exception.tval(),
+            Trap::Interrupt(_) => 0,
+        }
+    }
+}
+
+impl From<Exception> for Trap {
+    fn from(exception: Exception) -> Trap {
+        Trap::Exception(exception)
+    }
+}
+
+impl From<Interrupt> for Trap {
+    fn from(interrupt: Interrupt) -> Trap {
+        Trap::Interrupt(interrupt)
+    }
+}
+
+/// A single RV32I hardware thread
+#[derive(Clone, Debug)]
+pub struct Hart<B: Bus> {
+    regs: [u32; 32],
+    pc: u32,
+    pub csrs: CsrFile,
+    pub bus: B,
+
+    /// Estimates the cycles counted by `cycle`
+    pub timing: Timing,
+
+    /// The effects of the last instruction executed, if it retired
+    last_commit: Option<Commit>,
+}
+
+impl<B: Bus> Hart<B> {
+    /// Create a hart that will start executing at `pc`
+    ///
+    /// All registers start out as zero.
+    pub fn new(bus: B, pc: u32) -> Self {
+        Hart {
+            regs: [0; 32],
+            pc,
+            csrs: CsrFile::new(),
+            bus,
+            timing: Timing::default(),
+            last_commit: None,
+        }
+    }
+
+    pub fn reg(&self, reg: Reg) -> u32 {
+        self.regs[reg as usize]
+    }
+
+    /// Write `value` into `reg`, unless `reg` is `zero`
+    pub fn set_reg(&mut self, reg: Reg, value: u32) {
+        if reg != Reg::Zero {
+            self.regs[reg as usize] = value;
+        }
+    }
+
+    pub fn pc(&self) -> u32 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: u32) {
+        self.pc = pc;
+    }
+
+    /// Fetch and decode the instruction at `pc`
+    pub fn fetch(&mut self) -> std::result::Result<(u32, Instr), Exception> {
+        if self.pc % 4 != 0 {
+            return Err(Exception::InstructionAddressMisaligned(self.pc));
+        }
+
+        let word = self
+            .bus
+            .load(self.pc, 4)
+            .map_err(|fault| Exception::InstructionAccessFault(fault.addr))?;
+
+        match decode_opcode(word) {
+            Some(Instr::Illegal) | None => Err(Exception::IllegalInstruction(word)),
+            Some(instr) => Ok((word, instr)),
+        }
+    }
+
+    /// Execute a single instruction
+    ///
+    /// If the instruction raises an exception, none of its effects happen and
+    /// `pc` still points at it.
+    pub fn step(&mut self) -> std::result::Result<(), Exception> {
+        self.last_commit = None;
+
+        let (word, instr) = self.fetch()?;
+        let pc = self.pc;
+        let mode = self.csrs.mode;
+
+        // Registers that the instruction overwrites are needed to log what it did
+        let mem = instr.mem_access().map(|access| {
+            let addr = self.reg(access.base).wrapping_add(access.offset as u32);
+            match access.op {
+                MemOp::Load => MemCommit::Load {
+                    addr,
+                    width: access.width,
+                },
+                MemOp::Store => {
+                    let src = match instr {
+                        Instr::Sb { rs2, .. } | Instr::Sh { rs2, .. } | Instr::Sw { rs2, .. } => {
+                            self.reg(rs2)
+                        }
+                        _ => 0,
+                    };
+                    MemCommit::Store {
+                        addr,
+                        width: access.width,
+                        value: src & (u32::MAX >> (32 - 8 * access.width.min(4) as u32)),
+                    }
+                }
+            }
+        });
+
+        self.execute(word, instr)?;
+
+        let reg = instr.writes().iter().next().map(|reg| (reg, self.reg(reg)));
+        let written_csr = match instr {
+            Instr::Mret {} => Some(crate::csr::MSTATUS.num()),
+            _ => instr
+                .csr_access()
+                .filter(|access| access.write)
+                .map(|access| access.csr),
+        };
+        let csr = written_csr.and_then(|csr| self.csrs.read(csr).map(|value| (csr, value)));
+
+        self.retire(Commit {
+            pc,
+            word,
+            instr,
+            mode,
+            reg,
+            csr,
+            mem,
+            next_pc: self.pc,
+        });
+
+        Ok(())
+    }
+
+    /// Count `commit` in `instret` and `cycle`, and keep it as the last commit
+    pub(crate) fn retire(&mut self, commit: Commit) {
+        let cycles = self.timing.retire(&commit);
+        self.csrs.retire(cycles);
+        self.last_commit = Some(commit);
+    }
+
+    /// The effects of the last call to [`Hart::step`], if it retired an
+    /// instruction
+    pub fn last_commit(&self) -> Option<&Commit> {
+        self.last_commit.as_ref()
+    }
+
+    /// Execute instructions until one raises an exception, or `max_steps`
+    /// instructions have executed
+    pub fn run(&mut self, max_steps: u64) -> std::result::Result<(), Exception> {
+        for _ in 0..max_steps {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Transfer control to the trap handler for `trap`
+    ///
+    /// For exceptions, `pc` must still be on the instruction that raised it.
+    /// For interrupts, `pc` is the instruction that will run after the handler
+    /// returns.
+    pub fn take_trap(&mut self, trap: impl Into<Trap>) {
+        self.last_commit = None;
+        self.csrs.stall(self.timing.trap());
+        self.pc = self.csrs.enter_trap(trap.into(), self.pc);
+    }
+
+    /// Take a pending interrupt, or execute one instruction and take any
+    /// exception that it raises
+    ///
+    /// Devices on the bus are advanced first, so that their interrupts can
+    /// be taken.
+    ///
+    /// Returns the trap that was taken, if there was one.
+    pub fn tick(&mut self) -> Option<Trap> {
+        let lines = self.bus.tick();
+        self.csrs.set_lines(lines);
+
+        let trap = match self.csrs.pending_interrupt() {
+            Some(interrupt) => Trap::Interrupt(interrupt),
+            None => Trap::Exception(self.step().err()?),
+        };
+
+        self.take_trap(trap);
+        Some(trap)
+    }
+
+    /// Execute `instr`, which was decoded from `word` at `pc`
+    fn execute(&mut self, word: u32, instr: Instr) -> std::result::Result<(), Exception> {
+        use Instr::*;
+
+        let pc = self.pc;
+        let mut next_pc = pc.wrapping_add(4);
+
+        // Registers are read before any are written
+        let regs = self.regs;
+        let x = |reg: Reg| regs[reg as usize];
+        let shamt = |value: u32| value & 0b1_1111;
+
+        match instr {
+            Lb { rd, rs1, imm } => {
+                let value = self.load(x(rs1).wrapping_add(imm as u32), 1)?;
+                self.set_reg(rd, value as u8 as i8 as u32);
+            }
+            Lh { rd, rs1, imm } => {
+                let value = self.load(x(rs1).wrapping_add(imm as u32), 2)?;
+                self.set_reg(rd, value as u16 as i16 as u32);
+            }
+            Lw { rd, rs1, imm } => {
+                let value = self.load(x(rs1).wrapping_add(imm as u32), 4)?;
+                self.set_reg(rd, value);
+            }
+            Lbu { rd, rs1, imm } => {
+                let value = self.load(x(rs1).wrapping_add(imm), 1)?;
+                self.set_reg(rd, value);
+            }
+            Lhu { rd, rs1, imm } => {
+                let value = self.load(x(rs1).wrapping_add(imm), 2)?;
+                self.set_reg(rd, value);
+            }
+            Sb { rs1, rs2, imm } => self.store(x(rs1).wrapping_add(imm as u32), 1, x(rs2))?,
+            Sh { rs1, rs2, imm } => self.store(x(rs1).wrapping_add(imm as u32), 2, x(rs2))?,
+            Sw { rs1, rs2, imm } => self.store(x(rs1).wrapping_add(imm as u32), 4, x(rs2))?,
+
+            // Every access is already in order and there are no caches
+            Fence { .. } | FenceI { .. } => {}
+
+            Addi { rd, rs1, imm } => self.set_reg(rd, x(rs1).wrapping_add(imm as u32)),
+            Slti { rd, rs1, imm12 } => self.set_reg(rd, ((x(rs1) as i32) < imm12) as u32),
+            Sltiu { rd, rs1, imm12 } => self.set_reg(rd, (x(rs1) < imm12 as u32) as u32),
+            Xori { rd, rs1, imm12 } => self.set_reg(rd, x(rs1) ^ imm12 as u32),
+            Ori { rd, rs1, imm12 } => self.set_reg(rd, x(rs1) | imm12 as u32),
+            Andi { rd, rs1, imm } => self.set_reg(rd, x(rs1) & imm as u32),
+            Slli { rd, rs1, imm5 } => self.set_reg(rd, x(rs1) << shamt(imm5 as u32)),
+            Srli { rd, rs1, imm5 } => self.set_reg(rd, x(rs1) >> shamt(imm5 as u32)),
+            Srai { rd, rs1, imm5 } => {
+                self.set_reg(rd, ((x(rs1) as i32) >> shamt(imm5 as u32)) as u32)
+            }
+
+            Add { rd, rs1, rs2 } => self.set_reg(rd, x(rs1).wrapping_add(x(rs2))),
+            Sub { rd, rs1, rs2 } => self.set_reg(rd, x(rs1).wrapping_sub(x(rs2))),
+            Sll { rd, rs1, rs2 } => self.set_reg(rd, x(rs1) << shamt(x(rs2))),
+            Slt { rd, rs1, rs2 } => self.set_reg(rd, ((x(rs1) as i32) < (x(rs2) as i32)) as u32),
+            Sltu { rd, rs1, rs2 } => self.set_reg(rd, (x(rs1) < x(rs2)) as u32),
+            Xor { rd, rs1, rs2 } => self.set_reg(rd, x(rs1) ^ x(rs2)),
+            Srl { rd, rs1, rs2 } => self.set_reg(rd, x(rs1) >> shamt(x(rs2))),
+            Sra { rd, rs1, rs2 } => self.set_reg(rd, ((x(rs1) as i32) >> shamt(x(rs2))) as u32),
+            Or { rd, rs1, rs2 } => self.set_reg(rd, x(rs1) | x(rs2)),
+            And { rd, rs1, rs2 } => self.set_reg(rd, x(rs1) & x(rs2)),
+
+            Lui { rd, imm } => self.set_reg(rd, imm << 12),
+            Auipc { rd, imm } => self.set_reg(rd, pc.wrapping_add(imm << 12)),
+
+            Beq { rs1, rs2, imm }
+            | Bne { rs1, rs2, imm }
+            | Blt { rs1, rs2, imm }
+            | Bge { rs1, rs2, imm }
+            | Bltu { rs1, rs2, imm }
+            | Bgeu { rs1, rs2, imm } => {
+                let (a, b) = (x(rs1), x(rs2));
+                let taken = match instr {
+                    Beq { .. } => a == b,
+                    Bne { .. } => a != b,
+                    Blt { .. } => (a as i32) < (b as i32),
+                    Bge { .. } => (a as i32) >= (b as i32),
+                    Bltu { .. } => a < b,
+                    _ => a >= b,
+                };
+                if taken {
+                    next_pc = jump_target(pc.wrapping_add(imm as u32))?;
+                }
+            }
+            Jal { rd, imm } => {
+                next_pc = jump_target(pc.wrapping_add(imm as u32))?;
+                self.set_reg(rd, pc.wrapping_add(4));
+            }
+            Jalr { rd, rs1, imm } => {
+                // `rs1` is read before `rd` is written, in case they're the same
+                next_pc = jump_target(x(rs1).wrapping_add(imm as u32) & !1)?;
+                self.set_reg(rd, pc.wrapping_add(4));
+            }
+
+            Ecall { .. } => return Err(Exception::EnvironmentCall(self.csrs.mode)),
+            Ebreak { .. } => return Err(Exception::Breakpoint(pc)),
+
+            // There are no interrupts to wait for
+            Wfi {} => {}
+            Mret {} if self.csrs.mode == Mode::Machine => next_pc = self.csrs.leave_trap(),
+
+            Csrrw { rd, rs1, csr } => {
+                let value = x(rs1);
+                self.csr_op(word, rd, csr, rd != Reg::Zero, Some(|_| value))?
+            }
+            Csrrs { rd, rs1, csr } => {
+                let mask = x(rs1);
+                let write = rs1 != Reg::Zero;
+                self.csr_op(word, rd, csr, true, write.then_some(|old| old | mask))?
+            }
+            Csrrc { rd, rs1, csr } => {
+                let mask = x(rs1);
+                let write = rs1 != Reg::Zero;
+                self.csr_op(word, rd, csr, true, write.then_some(|old| old & !mask))?
+            }
+            Csrrwi { rd, src, csr } => {
+                let value = src as u32;
+                self.csr_op(word, rd, csr, rd != Reg::Zero, Some(|_| value))?
+            }
+            Csrrsi { rd, src, csr } => {
+                let mask = src as u32;
+                let write = src != 0;
+                self.csr_op(word, rd, csr, true, write.then_some(|old| old | mask))?
+            }
+            Csrrci { rd, src, csr } => {
+                let mask = src as u32;
+                let write = src != 0;
+                self.csr_op(word, rd, csr, true, write.then_some(|old| old & !mask))?
+            }
+
+            // Architecturally, hints do nothing
+            Hint { .. } => {}
+
+            // RV64 only, or not implemented
+            Ld { .. }
+            | Lwu { .. }
+            | Sd { .. }
+            | Uret {}
+            | Sret {}
+            | Mret {}
+            | Custom { .. }
+            | Illegal => return Err(Exception::IllegalInstruction(word)),
+        }
+
+        self.pc = next_pc;
+        Ok(())
+    }
+
+    /// Read, and then maybe write, the CSR numbered `csr`
+    ///
+    /// The old value is only read if `read` is set, which matters for CSRs
+    /// with side effects. The new value is computed from the old value by
+    /// `write`, if it is given.
+    fn csr_op(
+        &mut self,
+        word: u32,
+        rd: Reg,
+        csr: u16,
+        read: bool,
+        write: Option<impl FnOnce(u32) -> u32>,
+    ) -> std::result::Result<(), Exception> {
+        let illegal = Exception::IllegalInstruction(word);
+
+        if !self.csrs.allows(csr, write.is_some()) {
+            return Err(illegal);
+        }
+
+        let old = self.csrs.read(csr).ok_or(illegal)?;
+
+        if let Some(write) = write {
+            self.csrs.write(csr, write(old)).ok_or(illegal)?;
+        }
+        if read {
+            self.set_reg(rd, old);
+        }
+
+        Ok(())
+    }
+
+    fn load(&mut self, addr: u32, width: u8) -> std::result::Result<u32, Exception> {
+        if addr % width as u32 != 0 {
+            return Err(Exception::LoadAddressMisaligned(addr));
+        }
+        self.bus
+            .load(addr, width)
+            .map_err(|fault| Exception::LoadAccessFault(fault.addr))
+    }
+
+    fn store(&mut self, addr: u32, width: u8, value: u32) -> std::result::Result<(), Exception> {
+        if addr % width as u32 != 0 {
+            return Err(Exception::StoreAddressMisaligned(addr));
+        }
+        self.bus
+            .store(addr, width, value)
+            .map_err(|fault| Exception::StoreAccessFault(fault.addr))
+    }
+}
+
+/// Check that a jump or branch to `target` is allowed
+///
+/// Without the C extension, every instruction is 4-byte aligned.
+fn jump_target(target: u32) -> std::result::Result<u32, Exception> {
+    if target % 4 == 0 {
+        Ok(target)
+    } else {
+        Err(Exception::InstructionAddressMisaligned(target))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::io::{self, Write};
+    use std::rc::Rc;
+
+    use crate::csr;
+    use crate::dis::Disassembly;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    /// A writer that can still be read after being moved into a device
+    #[derive(Clone, Default)]
+    pub(super) struct SharedBuffer(pub Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Load `words` at address 0, with RAM up to 0x2000
+    pub(super) fn load(words: &[u32]) -> Hart<Ram> {
+        let mut ram = Ram::new(0, 0x2000);
+        let dis = Disassembly::from_words(0, words);
+        for entry in dis.disassembly() {
+            ram.write_bytes(entry.addr, &entry.bytes).unwrap();
+        }
+        Hart::new(ram, 0)
+    }
+
+    #[test]
+    fn check_rv32i() {
+        use Reg::*;
+
+        #[rustfmt::skip]
+        let mut hart = load(&[
+            0x0000_0513, // li      a0, 0
+            0x0010_0593, // li      a1, 1
+            0x00b0_0613, // li      a2, 11
+            // loop:
+            0x00b5_0533, // add     a0, a0, a1
+            0x0015_8593, // addi    a1, a1, 1
+            0xfec5_cce3, // blt     a1, a2, loop
+            0x8000_02b7, // lui     t0, 0x80000
+            0x4042_d313, // srai    t1, t0, 4
+            0x0042_d393, // srli    t2, t0, 4
+            0x0035_9e13, // slli    t3, a1, 3
+            0x0002_ae93, // slti    t4, t0, 0
+            0xfff2_bf13, // sltiu   t5, t0, -1
+            0xfff5_4f93, // not     t6, a0
+            0x7f00_6413, // ori     s0, zero, 0x7f0
+            0x0ff4_7413, // andi    s0, s0, 0xff
+            0x40a0_04b3, // neg     s1, a0
+            0x00b5_9933, // sll     s2, a1, a1
+            0x00a4_a9b3, // slt     s3, s1, a0
+            0x00a4_ba33, // sltu    s4, s1, a0
+            0x00b5_4ab3, // xor     s5, a0, a1
+            0x00b2_db33, // srl     s6, t0, a1
+            0x40b2_dbb3, // sra     s7, t0, a1
+            0x00b5_6c33, // or      s8, a0, a1
+            0x00b5_7cb3, // and     s9, a0, a1
+            0x0000_1d17, // auipc   s10, 1
+            0x0480_00ef, // jal     func
+            0x0050_0013, // addi    zero, zero, 5
+            0x0000_1137, // lui     sp, 1
+            0xfe51_2e23, // sw      t0, -4(sp)
+            0xfff1_0683, // lb      a3, -1(sp)
+            0xfff1_4703, // lbu     a4, -1(sp)
+            0xffe1_1783, // lh      a5, -2(sp)
+            0xffe1_5803, // lhu     a6, -2(sp)
+            0xfea1_0e23, // sb      a0, -4(sp)
+            0xfea1_1c23, // sh      a0, -8(sp)
+            0xffc1_2883, // lw      a7, -4(sp)
+            0x00a5_1463, // bne     a0, a0, fail
+            0x00a5_0463, // beq     a0, a0, 1f
+            // fail:
+            0x0010_0073, // ebreak
+            // 1:
+            0xfea4_dee3, // bge     s1, a0, fail
+            0xfe95_7ce3, // bgeu    a0, s1, fail
+            0xfea4_eae3, // bltu    s1, a0, fail
+            0x0000_0073, // ecall
+            // func:
+            0x0000_8d93, // mv      s11, ra
+            0x0000_8067, // ret
+        ]);
+
+        assert_eq!(
+            hart.run(1000),
+            Err(Exception::EnvironmentCall(Mode::Machine))
+        );
+        assert_eq!(hart.pc(), 0xa8);
+
+        let expected = [
+            (Zero, 0),
+            (Ra, 0x68),
+            (Sp, 0x1000),
+            (A0, 55),
+            (A1, 11),
+            (A2, 11),
+            (A3, 0xffff_ff80),
+            (A4, 0x80),
+            (A5, 0xffff_8000),
+            (A6, 0x8000),
+            (A7, 0x8000_0037),
+            (T0, 0x8000_0000),
+            (T1, 0xf800_0000),
+            (T2, 0x0800_0000),
+            (T3, 88),
+            (T4, 1),
+            (T5, 1),
+            (T6, !55),
+            (S0, 0xf0),
+            (S1, -55_i32 as u32),
+            (S2, 11 << 11),
+            (S3, 1),
+            (S4, 0),
+            (S5, 55 ^ 11),
+            (S6, 0x0010_0000),
+            (S7, 0xfff0_0000),
+            (S8, 55 | 11),
+            (S9, 55 & 11),
+            (S10, 0x1060),
+            (S11, 0x68),
+        ];
+        for &(reg, value) in expected.iter() {
+            assert_eq!((reg, hart.reg(reg)), (reg, value));
+        }
+    }
+
+    #[test]
+    fn check_exceptions() {
+        // lw a0, 2(zero)
+        let mut hart = load(&[0x0020_2503]);
+        assert_eq!(hart.step(), Err(Exception::LoadAddressMisaligned(2)));
+        assert_eq!(hart.pc(), 0);
+
+        // sw a0, 0(a1) with a1 past the end of memory
+        let mut hart = load(&[0x00a5_a023]);
+        hart.set_reg(Reg::A1, 0x4000);
+        assert_eq!(hart.step(), Err(Exception::StoreAccessFault(0x4000)));
+
+        // jalr 2(zero)
+        let mut hart = load(&[0x0020_0067]);
+        assert_eq!(hart.step(), Err(Exception::InstructionAddressMisaligned(2)));
+
+        // jal 2, which the assembler won't produce
+        let mut hart = load(&[0x0020_006f]);
+        assert_eq!(hart.step(), Err(Exception::InstructionAddressMisaligned(2)));
+
+        let mut hart = load(&[0xffff_ffff]);
+        assert_eq!(hart.step(), Err(Exception::IllegalInstruction(0xffff_ffff)));
+
+        // ld a0, 0(zero) is RV64 only
+        let mut hart = load(&[0x0000_3503]);
+        assert_eq!(hart.step(), Err(Exception::IllegalInstruction(0x0000_3503)));
+
+        let mut hart = load(&[]);
+        hart.set_pc(0x2000);
+        assert_eq!(hart.step(), Err(Exception::InstructionAccessFault(0x2000)));
+    }
+
+    #[test]
+    fn check_csrs() {
+        #[rustfmt::skip]
+        let mut hart = load(&[
+            0x3400_5073, // csrwi   mscratch, 0
+            0x3402_a573, // csrrs   a0, mscratch, t0
+            0x3402_95f3, // csrrw   a1, mscratch, t0
+            0x3406_f673, // csrrci  a2, mscratch, 13
+            0x3400_26f3, // csrr    a3, mscratch
+            0xc020_2773, // rdinstret a4
+            0x3010_27f3, // csrr    a5, misa
+            0xc000_1073, // csrw    cycle, zero
+        ]);
+        hart.set_reg(Reg::T0, 0xff);
+
+        assert_eq!(hart.run(7), Ok(()));
+        assert_eq!(hart.reg(Reg::A0), 0);
+        assert_eq!(hart.reg(Reg::A1), 0xff);
+        assert_eq!(hart.reg(Reg::A2), 0xff);
+        assert_eq!(hart.reg(Reg::A3), 0xf2);
+        assert_eq!(hart.reg(Reg::A4), 5);
+        assert_eq!(hart.reg(Reg::A5), 0x4010_0100);
+
+        // `cycle` is read-only
+        assert_eq!(hart.step(), Err(Exception::IllegalInstruction(0xc000_1073)));
+    }
+
+    #[test]
+    fn check_mtvec_modes() {
+        let mut hart = load(&[]);
+        let mtvec = crate::csr::MTVEC.num();
+
+        // Direct and vectored are kept, and the reserved modes become direct
+        for &(mode, kept) in &[(0, 0), (1, 1), (2, 0), (3, 0)] {
+            assert_eq!(hart.csrs.write(mtvec, 0x8000_0100 | mode), Some(()));
+            assert_eq!(hart.csrs.read(mtvec), Some(0x8000_0100 | kept), "{}", mode);
+        }
+    }
+
+    #[test]
+    fn check_added_csrs() {
+        #[rustfmt::skip]
+        let mut hart = load(&[
+            0x7c02_9073, // csrw    0x7c0, t0
+            0x7c00_2573, // csrr    a0, 0x7c0
+            0x7c10_2573, // csrr    a0, 0x7c1
+        ]);
+        hart.set_reg(Reg::T0, 0x1234);
+        hart.csrs.add(crate::csr::Csr(0x7c0, crate::csr::Mrw));
+
+        assert_eq!(hart.run(2), Ok(()));
+        assert_eq!(hart.reg(Reg::A0), 0x1234);
+        assert_eq!(hart.step(), Err(Exception::IllegalInstruction(0x7c10_2573)));
+    }
+
+    #[test]
+    fn check_traps() {
+        #[rustfmt::skip]
+        let mut hart = load(&[
+            // _start:
+            0x0000_0297, // auipc   t0, 0           ┐ la t0, handler
+            0x03c2_8293, // addi    t0, t0, 60      ┘
+            0x3052_9073, // csrw    mtvec, t0
+            0x0000_0413, // li      s0, 0
+            0x0000_0073, // ecall
+            0xffff_ffff, // .word   0xffffffff
+            0x0020_2503, // lw      a0, 2(zero)
+            0x0000_0297, // auipc   t0, 0           ┐ la t0, vectors
+            0x03c2_8293, // addi    t0, t0, 60      ┘
+            0x0012_e293, // ori     t0, t0, 1
+            0x3052_9073, // csrw    mtvec, t0
+            0x0800_0313, // li      t1, 0x80
+            0x3043_2073, // csrs    mie, t1
+            0x3004_6073, // csrsi   mstatus, 8
+            // spin:
+            0x0000_006f, // j       spin
+            // handler:
+            0x3420_25f3, // csrr    a1, mcause
+            0x3430_2673, // csrr    a2, mtval
+            0x3410_23f3, // csrr    t2, mepc
+            0x0043_8393, // addi    t2, t2, 4
+            0x3413_9073, // csrw    mepc, t2
+            0x0014_0413, // addi    s0, s0, 1
+            0x3020_0073, // mret
+            // vectors:
+            0xfe5f_f06f, // j       handler
+            0xfe1f_f06f, // j       handler
+            0xfddf_f06f, // j       handler
+            0xfd9f_f06f, // j       handler
+            0xfd5f_f06f, // j       handler
+            0xfd1f_f06f, // j       handler
+            0xfcdf_f06f, // j       handler
+            0x0040_006f, // j       timer
+            // timer:
+            0x3420_26f3, // csrr    a3, mcause
+            0x3000_2773, // csrr    a4, mstatus
+            0x3020_0073, // mret
+        ]);
+        let spin = 0x38;
+
+        let mut traps = vec![];
+        while hart.pc() != spin {
+            traps.extend(hart.tick());
+            assert!(traps.len() < 10, "Never reached spin: {:?}", traps);
+        }
+        assert_eq!(
+            traps,
+            vec![
+                Trap::Exception(Exception::EnvironmentCall(Mode::Machine)),
+                Trap::Exception(Exception::IllegalInstruction(0xffff_ffff)),
+                Trap::Exception(Exception::LoadAddressMisaligned(2)),
+            ]
+        );
+
+        // Every handler ran, and the last one saw the misaligned load
+        assert_eq!(hart.reg(Reg::S0), 3);
+        assert_eq!(hart.reg(Reg::A1), 4);
+        assert_eq!(hart.reg(Reg::A2), 2);
+
+        // Nothing happens until the timer goes off
+        assert_eq!(hart.tick(), None);
+        assert_eq!(hart.pc(), spin);
+
+        hart.csrs.set_pending(Interrupt::MachineTimer, true);
+        assert_eq!(hart.tick(), Some(Trap::Interrupt(Interrupt::MachineTimer)));
+        assert_eq!(hart.pc(), 0x58 + 4 * 7);
+        assert_eq!(hart.csrs.read(csr::MEPC.num()), Some(spin));
+
+        // The handler is not interrupted again, even with the timer pending
+        for _ in 0..4 {
+            assert_eq!(hart.tick(), None);
+        }
+        assert_eq!(hart.pc(), spin);
+        assert_eq!(hart.reg(Reg::A3), 0x8000_0007);
+        assert_eq!(hart.reg(Reg::A4), 0x1880);
+        assert_eq!(hart.csrs.read(csr::MSTATUS.num()), Some(0x0088));
+
+        // And once it's back, the timer interrupts again
+        assert_eq!(hart.tick(), Some(Trap::Interrupt(Interrupt::MachineTimer)));
+    }
+
+    #[test]
+    fn check_user_mode() {
+        #[rustfmt::skip]
+        let mut hart = load(&[
+            // _start:
+            0x0000_0297, // auipc   t0, 0           ┐ la t0, handler
+            0x0382_8293, // addi    t0, t0, 56      ┘
+            0x3052_9073, // csrw    mtvec, t0
+            0x0000_0297, // auipc   t0, 0           ┐ la t0, user
+            0x01c2_8293, // addi    t0, t0, 28      ┘
+            0x3412_9073, // csrw    mepc, t0
+            0x0000_22b7, // lui     t0, 2           ┐ li t0, 0x1800
+            0x8002_8293, // addi    t0, t0, -2048   ┘
+            0x3002_b073, // csrc    mstatus, t0
+            0x3020_0073, // mret
+            // user:
+            0x3000_2573, // csrr    a0, mstatus
+            0xc000_25f3, // rdcycle a1
+            0x3020_0073, // mret
+            0x0000_0073, // ecall
+            // handler:
+            0x3410_23f3, // csrr    t2, mepc
+            0x0043_8393, // addi    t2, t2, 4
+            0x3413_9073, // csrw    mepc, t2
+            0x3020_0073, // mret
+        ]);
+
+        let mut traps = vec![];
+        for _ in 0..100 {
+            traps.extend(hart.tick());
+            if traps.len() == 4 {
+                break;
+            }
+        }
+
+        // Machine CSRs, counters that `mcounteren` hides, and `mret` are all
+        // off limits to user mode.
+        assert_eq!(
+            traps,
+            vec![
+                Trap::Exception(Exception::IllegalInstruction(0x3000_2573)),
+                Trap::Exception(Exception::IllegalInstruction(0xc000_25f3)),
+                Trap::Exception(Exception::IllegalInstruction(0x3020_0073)),
+                Trap::Exception(Exception::EnvironmentCall(Mode::User)),
+            ]
+        );
+        assert_eq!(hart.csrs.read(csr::MCAUSE.num()), Some(8));
+        assert_eq!(hart.csrs.mode, Mode::Machine);
+
+        // `mstatus.MPP` remembers that the trap came from user mode
+        assert_eq!(hart.csrs.read(csr::MSTATUS.num()).unwrap() & 0x1800, 0);
+    }
+}
@@ -0,0 +1,178 @@
+use super::device::{read_word_part, write_word_part};
+use super::{BusFault, Device, Interrupt};
+
+/// The size of the register block
+pub const PLIC_SIZE: u32 = 0x40_0000;
+
+/// The number of interrupt sources, including source 0 which doesn't exist
+const SOURCES: u32 = 32;
+
+const PRIORITY: u32 = 0x0;
+const PENDING: u32 = 0x1000;
+const ENABLE: u32 = 0x2000;
+const THRESHOLD: u32 = 0x20_0000;
+const CLAIM: u32 = 0x20_0004;
+
+/// Priorities are 3 bits wide
+const PRIORITY_MASK: u32 = 0b111;
+
+/// A platform-level interrupt controller, with sources 1 to 31 and a single
+/// M-mode context
+///
+/// Sources are level-triggered. A raised source becomes pending, and stays
+/// quiet after being claimed until its handler writes the claim back to
+/// complete it.
+#[derive(Clone, Debug, Default)]
+pub struct Plic {
+    priorities: [u32; SOURCES as usize],
+    pending: u32,
+    enabled: u32,
+    threshold: u32,
+
+    /// Sources that have been claimed, but not completed
+    claimed: u32,
+}
+
+impl Plic {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The pending source that should be claimed next, if any
+    ///
+    /// Ties between priorities go to the lowest numbered source.
+    fn best(&self) -> Option<u32> {
+        let candidates = self.pending & self.enabled;
+        (1..SOURCES)
+            .filter(|&source| candidates & (1 << source) != 0)
+            .filter(|&source| self.priorities[source as usize] > self.threshold)
+            .max_by_key(|&source| (self.priorities[source as usize], SOURCES - source))
+    }
+
+    fn read(&mut self, reg: u32) -> Option<u32> {
+        let value = match reg {
+            PRIORITY..=0xffc => self.priorities[(reg / 4) as usize % SOURCES as usize],
+            PENDING => self.pending,
+            ENABLE => self.enabled,
+            THRESHOLD => self.threshold,
+            CLAIM => match self.best() {
+                Some(source) => {
+                    self.pending &= !(1 << source);
+                    self.claimed |= 1 << source;
+                    source
+                }
+                None => 0,
+            },
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    fn write(&mut self, reg: u32, value: u32) -> Option<()> {
+        match reg {
+            PRIORITY..=0xffc => {
+                let source = (reg / 4) as usize;
+                if source != 0 && source < SOURCES as usize {
+                    self.priorities[source] = value & PRIORITY_MASK;
+                }
+            }
+            // Sources only become pending through their gateways
+            PENDING => {}
+            ENABLE => self.enabled = value & !1,
+            THRESHOLD => self.threshold = value & PRIORITY_MASK,
+            CLAIM => {
+                if value < SOURCES {
+                    self.claimed &= !(1 << value);
+                }
+            }
+            _ => return None,
+        }
+
+        Some(())
+    }
+}
+
+impl Device for Plic {
+    fn load(&mut self, offset: u32, width: u8) -> Result<u32, BusFault> {
+        // Claims only happen on reads of the whole register
+        let reg = offset & !0b11;
+        if reg == CLAIM && width != 4 {
+            return Err(BusFault { addr: offset });
+        }
+
+        self.read(reg)
+            .map(|word| read_word_part(word, offset, width))
+            .ok_or(BusFault { addr: offset })
+    }
+
+    fn store(&mut self, offset: u32, width: u8, value: u32) -> Result<(), BusFault> {
+        let reg = offset & !0b11;
+        let value = if width == 4 {
+            value
+        } else if reg == CLAIM {
+            return Err(BusFault { addr: offset });
+        } else {
+            let word = self.read(reg).ok_or(BusFault { addr: offset })?;
+            write_word_part(word, offset, width, value)
+        };
+
+        self.write(reg, value).ok_or(BusFault { addr: offset })
+    }
+
+    fn tick(&mut self, sources: u32) {
+        self.pending |= sources & !self.claimed & !1;
+    }
+
+    fn mip(&self) -> u32 {
+        if self.best().is_some() {
+            Interrupt::MachineExternal.bit()
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    #[test]
+    fn check_plic() {
+        let mut plic = Plic::new();
+        plic.store(PRIORITY + 4 * 2, 4, 1).unwrap();
+        plic.store(PRIORITY + 4 * 5, 4, 3).unwrap();
+        plic.store(ENABLE, 4, (1 << 2) | (1 << 5)).unwrap();
+
+        // Disabled and zero priority sources are pending, but not claimable
+        plic.tick(1 << 3);
+        assert_eq!(plic.load(PENDING, 4), Ok(1 << 3));
+        assert_eq!(plic.mip(), 0);
+        assert_eq!(plic.load(CLAIM, 4), Ok(0));
+
+        plic.tick((1 << 2) | (1 << 5));
+        assert_eq!(plic.mip(), Interrupt::MachineExternal.bit());
+
+        // The threshold masks lower priorities
+        plic.store(THRESHOLD, 4, 1).unwrap();
+        assert_eq!(plic.load(CLAIM, 4), Ok(5));
+        assert_eq!(plic.mip(), 0);
+
+        // A claimed source doesn't become pending again until it completes
+        plic.tick(1 << 5);
+        assert_eq!(plic.load(CLAIM, 4), Ok(0));
+        plic.store(CLAIM, 4, 5).unwrap();
+        plic.tick(1 << 5);
+        assert_eq!(plic.load(CLAIM, 4), Ok(5));
+
+        plic.store(THRESHOLD, 4, 0).unwrap();
+        assert_eq!(plic.load(CLAIM, 4), Ok(2));
+        assert_eq!(plic.load(PENDING, 4), Ok(1 << 3));
+
+        assert_eq!(plic.load(CLAIM, 1), Err(BusFault { addr: CLAIM }));
+        assert_eq!(plic.load(0x3000, 4), Err(BusFault { addr: 0x3000 }));
+    }
+}
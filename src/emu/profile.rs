@@ -0,0 +1,360 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use super::Commit;
+use crate::dis::{Disassembly, FunctionRange};
+use crate::listing::Annotation;
+use crate::prelude::*;
+
+/// How often a conditional branch went each way
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BranchCount {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+/// Executions of the instructions in a single function
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionProfile {
+    pub func: FunctionRange,
+
+    /// Instructions retired in the function, counting repeats
+    pub hits: u64,
+
+    /// Instructions in the function that retired at least once
+    pub covered: u32,
+
+    /// All instructions in the function
+    pub instructions: u32,
+}
+
+/// Counts of retired instructions, collected from [`Commit`]s
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    hits: BTreeMap<u32, u64>,
+    branches: BTreeMap<u32, BranchCount>,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a retired instruction
+    pub fn record(&mut self, commit: &Commit) {
+        *self.hits.entry(commit.pc).or_insert(0) += 1;
+
+        if commit.instr.control_flow() == ControlFlow::ConditionalBranch {
+            let count = self.branches.entry(commit.pc).or_default();
+            if commit.next_pc == commit.pc.wrapping_add(4) {
+                count.not_taken += 1;
+            } else {
+                count.taken += 1;
+            }
+        }
+    }
+
+    /// The number of times the instruction at `addr` retired
+    pub fn hits(&self, addr: u32) -> u64 {
+        self.hits.get(&addr).cloned().unwrap_or(0)
+    }
+
+    /// How the conditional branch at `addr` went, if it ever retired
+    pub fn branch(&self, addr: u32) -> Option<BranchCount> {
+        self.branches.get(&addr).cloned()
+    }
+
+    /// The number of instructions retired
+    pub fn total(&self) -> u64 {
+        self.hits.values().sum()
+    }
+
+    /// Executions in each function of `dis`, in address order
+    pub fn functions(&self, dis: &Disassembly) -> Vec<FunctionProfile> {
+        let mut profiles: Vec<FunctionProfile> = dis
+            .functions()
+            .iter()
+            .map(|func| FunctionProfile {
+                func: func.clone(),
+                hits: 0,
+                covered: 0,
+                instructions: 0,
+            })
+            .collect();
+
+        // Functions are in address order and don't overlap, so each
+        // instruction is in the last one that starts at or before it
+        for entry in dis.disassembly() {
+            let after = profiles.partition_point(|profile| profile.func.addr <= entry.addr);
+            let profile = match profiles[..after].last_mut() {
+                Some(profile) if profile.func.contains(entry.addr) => profile,
+                _ => continue,
+            };
+
+            let hits = self.hits(entry.addr);
+            profile.hits += hits;
+            profile.covered += (hits != 0) as u32;
+            profile.instructions += 1;
+        }
+
+        profiles
+    }
+
+    /// Write `dis` as a listing with each instruction's hit count in the
+    /// margin, followed by a summary of each function
+    ///
+    /// Instructions that never retired are marked with `#####`, like `gcov`.
+    /// Branches are commented with how often they were taken.
+    ///
+    /// Returns the line that each instruction was written on, for
+    /// [`Profile::write_lcov`].
+    pub fn write_listing(
+        &self,
+        dis: &Disassembly,
+        out: &mut dyn Write,
+    ) -> io::Result<BTreeMap<u32, usize>> {
+        let lines = dis.write_listing(
+            out,
+            |_entry| true,
+            |entry| {
+                let margin = match self.hits(entry.addr) {
+                    0 => format!("{:>10} | ", "#####"),
+                    hits => format!("{:>10} | ", hits),
+                };
+                let comment = self
+                    .branch(entry.addr)
+                    .map(|count| format!("taken {}, not taken {}", count.taken, count.not_taken));
+                Annotation { margin, comment }
+            },
+        )?;
+
+        writeln!(out)?;
+        writeln!(out, "Executions per function:")?;
+        for profile in self.functions(dis) {
+            let name = match &profile.func.name {
+                Some(name) => name.clone(),
+                None => format!("{:#010x}", profile.func.addr),
+            };
+            writeln!(
+                out,
+                "{:>10} {:>5}/{:<5} {}",
+                profile.hits, profile.covered, profile.instructions, name
+            )?;
+        }
+
+        Ok(lines)
+    }
+
+    /// Write coverage of `dis` in lcov's tracefile format
+    ///
+    /// There's usually no source to point at, so the "source file" is a
+    /// listing at `listing_path`, and `lines` gives the line of each
+    /// instruction in it (see [`Profile::write_listing`]).
+    pub fn write_lcov(
+        &self,
+        dis: &Disassembly,
+        listing_path: &str,
+        lines: &BTreeMap<u32, usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        writeln!(out, "TN:")?;
+        writeln!(out, "SF:{}", listing_path)?;
+
+        let functions = self.functions(dis);
+        let name = |func: &FunctionRange| match &func.name {
+            Some(name) => name.clone(),
+            None => format!("fn_{:08x}", func.addr),
+        };
+        for profile in &functions {
+            if let Some(line) = lines.get(&profile.func.addr) {
+                writeln!(out, "FN:{},{}", line, name(&profile.func))?;
+            }
+        }
+        for profile in &functions {
+            // A function runs as often as its first instruction does
+            let calls = self.hits(profile.func.addr);
+            writeln!(out, "FNDA:{},{}", calls, name(&profile.func))?;
+        }
+        writeln!(out, "FNF:{}", functions.len())?;
+        let hit = functions
+            .iter()
+            .filter(|profile| self.hits(profile.func.addr) != 0)
+            .count();
+        writeln!(out, "FNH:{}", hit)?;
+
+        let mut branches = 0;
+        let mut branches_hit = 0;
+        for entry in dis.disassembly() {
+            let line = match lines.get(&entry.addr) {
+                Some(&line) => line,
+                None => continue,
+            };
+            let is_branch = entry
+                .o_instr
+                .is_some_and(|instr| instr.control_flow() == ControlFlow::ConditionalBranch);
+            if !is_branch {
+                continue;
+            }
+
+            // Branches that never ran are reported with "-"
+            let (taken, not_taken) = match self.branch(entry.addr) {
+                Some(count) => (count.taken.to_string(), count.not_taken.to_string()),
+                None => ("-".to_string(), "-".to_string()),
+            };
+            writeln!(out, "BRDA:{},0,0,{}", line, taken)?;
+            writeln!(out, "BRDA:{},0,1,{}", line, not_taken)?;
+
+            branches += 2;
+            if let Some(count) = self.branch(entry.addr) {
+                branches_hit += (count.taken != 0) as u32 + (count.not_taken != 0) as u32;
+            }
+        }
+        writeln!(out, "BRF:{}", branches)?;
+        writeln!(out, "BRH:{}", branches_hit)?;
+
+        let mut found = 0;
+        let mut lines_hit = 0;
+        for (&addr, &line) in lines {
+            let hits = self.hits(addr);
+            writeln!(out, "DA:{},{}", line, hits)?;
+            found += 1;
+            lines_hit += (hits != 0) as u32;
+        }
+        writeln!(out, "LF:{}", found)?;
+        writeln!(out, "LH:{}", lines_hit)?;
+
+        writeln!(out, "end_of_record")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::dis::Symbol;
+    use crate::emu::test::load;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    #[rustfmt::skip]
+    const WORDS: [u32; 10] = [
+        // _start:
+        0x0030_0513, // li      a0, 3
+        0x0000_0097, // auipc   ra, 0           ┐ call count
+        0x00c0_80e7, // jalr    12(ra)          ┘
+        0xff5f_f06f, // j       _start
+        // count:
+        0x0000_0593, // li      a1, 0
+        // loop:
+        0x0015_8593, // addi    a1, a1, 1
+        0xfea5_cee3, // blt     a1, a0, loop
+        0x0005_0463, // beqz    a0, done
+        0x0000_8067, // ret
+        // done:
+        0x0000_8067, // ret
+    ];
+
+    fn profile() -> (Disassembly, Profile) {
+        let mut dis = Disassembly::from_words(0, &WORDS);
+        dis.add_label(0x0, "_start");
+        dis.add_label(0x10, "count");
+        for &(addr, name) in &[(0x14, "loop"), (0x24, "done")] {
+            dis.add_symbol(Symbol {
+                name: name.to_string(),
+                addr,
+                size: 0,
+                is_function: false,
+                is_global: false,
+            });
+        }
+
+        // Twice through _start
+        let mut hart = load(&WORDS);
+        let mut profile = Profile::new();
+        for _ in 0..26 {
+            hart.step().unwrap();
+            profile.record(hart.last_commit().unwrap());
+        }
+
+        (dis, profile)
+    }
+
+    #[test]
+    fn check_profile() {
+        let (dis, profile) = profile();
+
+        assert_eq!(profile.total(), 26);
+        assert_eq!(profile.hits(0x0), 2);
+        assert_eq!(profile.hits(0x14), 6);
+        assert_eq!(profile.hits(0x24), 0);
+        assert_eq!(
+            profile.branch(0x18),
+            Some(BranchCount {
+                taken: 4,
+                not_taken: 2
+            })
+        );
+        assert_eq!(
+            profile.branch(0x1c),
+            Some(BranchCount {
+                taken: 0,
+                not_taken: 2
+            })
+        );
+        assert_eq!(profile.branch(0x20), None);
+
+        let functions: Vec<(Option<String>, u64, u32, u32)> = profile
+            .functions(&dis)
+            .into_iter()
+            .map(|p| (p.func.name, p.hits, p.covered, p.instructions))
+            .collect();
+        assert_eq!(
+            functions,
+            vec![
+                (Some("_start".to_string()), 8, 4, 4),
+                (Some("count".to_string()), 18, 5, 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_profile_listing() {
+        let (dis, profile) = profile();
+
+        let mut listing = vec![];
+        let lines = profile.write_listing(&dis, &mut listing).unwrap();
+        let listing = String::from_utf8(listing).unwrap();
+
+        let listing_lines: Vec<&str> = listing.lines().collect();
+        assert_eq!(
+            listing_lines[lines[&0x18] - 1],
+            "         6 |       18: e3 ce a5 fe                  \tblt\ta1, a0, -4\t; taken 4, not taken 2"
+        );
+        assert_eq!(
+            listing_lines[lines[&0x24] - 1],
+            "     ##### |       24: 67 80 00 00                  \tjalr\tzero, 0(ra)"
+        );
+        assert!(listing.ends_with(
+            "Executions per function:\n         8     4/4     _start\n        18     5/6     count\n"
+        ));
+
+        let mut lcov = vec![];
+        profile
+            .write_lcov(&dis, "test.s", &lines, &mut lcov)
+            .unwrap();
+        let lcov = String::from_utf8(lcov).unwrap();
+
+        let expected = format!(
+            "TN:\nSF:test.s\nFN:{start},_start\nFN:{count},count\nFNDA:2,_start\nFNDA:2,count\nFNF:2\nFNH:2\n\
+             BRDA:{blt},0,0,4\nBRDA:{blt},0,1,2\nBRDA:{beqz},0,0,0\nBRDA:{beqz},0,1,2\nBRF:4\nBRH:3\n",
+            start = lines[&0x0],
+            count = lines[&0x10],
+            blt = lines[&0x18],
+            beqz = lines[&0x1c],
+        );
+        assert!(lcov.starts_with(&expected), "{}", lcov);
+        assert!(lcov.contains(&format!("DA:{},0\n", lines[&0x24])));
+        assert!(lcov.ends_with("LF:10\nLH:9\nend_of_record\n"));
+    }
+}
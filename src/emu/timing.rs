@@ -0,0 +1,416 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::Commit;
+use crate::prelude::*;
+
+/// Groups of instructions that take the same number of cycles
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum InstrClass {
+    /// Integer arithmetic and logic, `lui`, and `auipc`
+    Alu,
+    Load,
+    Store,
+    /// A conditional branch that jumped
+    BranchTaken,
+    /// A conditional branch that fell through to the next instruction
+    BranchNotTaken,
+    /// `jal` and `jalr`
+    Jump,
+    /// Multiplication
+    ///
+    /// The M extension isn't decoded yet, so nothing retires as this.
+    Mul,
+    /// Division and remainder
+    ///
+    /// The M extension isn't decoded yet, so nothing retires as this.
+    Div,
+    /// The `csrr*` instructions
+    Csr,
+    /// Fences, `ecall`, `ebreak`, trap returns, and `wfi`
+    System,
+}
+
+impl InstrClass {
+    pub const ALL: [InstrClass; 10] = [
+        InstrClass::Alu,
+        InstrClass::Load,
+        InstrClass::Store,
+        InstrClass::BranchTaken,
+        InstrClass::BranchNotTaken,
+        InstrClass::Jump,
+        InstrClass::Mul,
+        InstrClass::Div,
+        InstrClass::Csr,
+        InstrClass::System,
+    ];
+
+    /// The class of the instruction that retired in `commit`
+    ///
+    /// Branches are classed by whether they jumped, so this needs the whole
+    /// commit rather than just the instruction.
+    pub fn of(commit: &Commit) -> Self {
+        use Instr::*;
+
+        let instr = commit.instr;
+        if let Some(access) = instr.mem_access() {
+            return match access.op {
+                MemOp::Load => InstrClass::Load,
+                MemOp::Store => InstrClass::Store,
+            };
+        }
+        if instr.csr_access().is_some() {
+            return InstrClass::Csr;
+        }
+
+        match instr.control_flow() {
+            ControlFlow::ConditionalBranch if commit.next_pc == commit.pc.wrapping_add(4) => {
+                InstrClass::BranchNotTaken
+            }
+            ControlFlow::ConditionalBranch => InstrClass::BranchTaken,
+            ControlFlow::DirectJump
+            | ControlFlow::Call
+            | ControlFlow::Return
+            | ControlFlow::IndirectJump => InstrClass::Jump,
+            ControlFlow::Trap | ControlFlow::TrapReturn => InstrClass::System,
+            ControlFlow::Sequential => match instr {
+                Fence { .. } | FenceI { .. } | Wfi {} => InstrClass::System,
+                _ => InstrClass::Alu,
+            },
+        }
+    }
+
+    /// The name used for this class by [`FromStr`] and [`fmt::Display`]
+    pub fn name(&self) -> &'static str {
+        match self {
+            InstrClass::Alu => "alu",
+            InstrClass::Load => "load",
+            InstrClass::Store => "store",
+            InstrClass::BranchTaken => "branch-taken",
+            InstrClass::BranchNotTaken => "branch-not-taken",
+            InstrClass::Jump => "jump",
+            InstrClass::Mul => "mul",
+            InstrClass::Div => "div",
+            InstrClass::Csr => "csr",
+            InstrClass::System => "system",
+        }
+    }
+}
+
+impl fmt::Display for InstrClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for InstrClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        InstrClass::ALL
+            .iter()
+            .find(|class| class.name() == s)
+            .cloned()
+            .ok_or_else(|| {
+                let names: Vec<&str> = InstrClass::ALL.iter().map(|class| class.name()).collect();
+                format!(
+                    "unknown instruction class {:?}, expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// How many cycles each class of instruction takes to retire, and the
+/// penalties on top of that
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Latencies {
+    pub alu: u32,
+    pub load: u32,
+    pub store: u32,
+    pub branch_taken: u32,
+    pub branch_not_taken: u32,
+    pub jump: u32,
+    pub mul: u32,
+    pub div: u32,
+    pub csr: u32,
+    pub system: u32,
+
+    /// Extra cycles when an instruction reads the register loaded by the
+    /// instruction just before it
+    pub load_use: u32,
+
+    /// Extra cycles to flush the pipeline and enter a trap handler
+    pub trap: u32,
+}
+
+impl Default for Latencies {
+    /// Every instruction takes a single cycle, with no stalls
+    fn default() -> Self {
+        Latencies {
+            alu: 1,
+            load: 1,
+            store: 1,
+            branch_taken: 1,
+            branch_not_taken: 1,
+            jump: 1,
+            mul: 1,
+            div: 1,
+            csr: 1,
+            system: 1,
+            load_use: 0,
+            trap: 0,
+        }
+    }
+}
+
+impl Latencies {
+    /// Every instruction takes a single cycle, with no stalls
+    pub fn single_cycle() -> Self {
+        Self::default()
+    }
+
+    /// A classic five-stage in-order pipeline
+    ///
+    /// Branches are predicted not taken and resolved in execute, so taken
+    /// branches and jumps flush the instructions fetched behind them.
+    /// Loads forward from the memory stage, which costs a cycle when the
+    /// next instruction needs the result.
+    pub fn in_order() -> Self {
+        Latencies {
+            alu: 1,
+            load: 1,
+            store: 1,
+            branch_taken: 3,
+            branch_not_taken: 1,
+            jump: 3,
+            mul: 3,
+            div: 34,
+            csr: 1,
+            system: 3,
+            load_use: 1,
+            trap: 3,
+        }
+    }
+
+    /// The latency of `class`
+    pub fn get(&self, class: InstrClass) -> u32 {
+        *self.field(class)
+    }
+
+    /// Change the latency of `class`
+    pub fn set(&mut self, class: InstrClass, cycles: u32) {
+        *self.field_mut(class) = cycles;
+    }
+
+    fn field(&self, class: InstrClass) -> &u32 {
+        match class {
+            InstrClass::Alu => &self.alu,
+            InstrClass::Load => &self.load,
+            InstrClass::Store => &self.store,
+            InstrClass::BranchTaken => &self.branch_taken,
+            InstrClass::BranchNotTaken => &self.branch_not_taken,
+            InstrClass::Jump => &self.jump,
+            InstrClass::Mul => &self.mul,
+            InstrClass::Div => &self.div,
+            InstrClass::Csr => &self.csr,
+            InstrClass::System => &self.system,
+        }
+    }
+
+    fn field_mut(&mut self, class: InstrClass) -> &mut u32 {
+        match class {
+            InstrClass::Alu => &mut self.alu,
+            InstrClass::Load => &mut self.load,
+            InstrClass::Store => &mut self.store,
+            InstrClass::BranchTaken => &mut self.branch_taken,
+            InstrClass::BranchNotTaken => &mut self.branch_not_taken,
+            InstrClass::Jump => &mut self.jump,
+            InstrClass::Mul => &mut self.mul,
+            InstrClass::Div => &mut self.div,
+            InstrClass::Csr => &mut self.csr,
+            InstrClass::System => &mut self.system,
+        }
+    }
+}
+
+/// Estimates how many cycles an in-order core would take to run the
+/// instructions that a hart retires
+#[derive(Clone, Debug, Default)]
+pub struct Timing {
+    pub latencies: Latencies,
+
+    /// The register written by the last instruction, if it was a load
+    pending_load: Option<Reg>,
+
+    /// Cycles spent stalled on loads
+    stalls: u64,
+}
+
+impl Timing {
+    pub fn new(latencies: Latencies) -> Self {
+        Timing {
+            latencies,
+            pending_load: None,
+            stalls: 0,
+        }
+    }
+
+    /// The number of cycles that the instruction in `commit` took, including
+    /// any stall waiting for the instruction before it
+    pub fn retire(&mut self, commit: &Commit) -> u64 {
+        let class = InstrClass::of(commit);
+
+        let mut cycles = self.latencies.get(class) as u64;
+        if let Some(reg) = self.pending_load {
+            if commit.instr.reads().contains(reg) {
+                cycles += self.latencies.load_use as u64;
+                self.stalls += self.latencies.load_use as u64;
+            }
+        }
+
+        self.pending_load = match class {
+            InstrClass::Load => commit.instr.writes().iter().next(),
+            _ => None,
+        };
+
+        cycles
+    }
+
+    /// The number of cycles taken to enter a trap handler
+    ///
+    /// This flushes the pipeline, so nothing stalls on an earlier load.
+    pub fn trap(&mut self) -> u64 {
+        self.pending_load = None;
+        self.latencies.trap as u64
+    }
+
+    /// The number of cycles spent stalled on loads so far
+    pub fn stalls(&self) -> u64 {
+        self.stalls
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::csr::Mode;
+    use crate::emu::test::load;
+    use crate::emu::{Exception, Trap};
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    #[test]
+    fn check_class_names() {
+        for &class in &InstrClass::ALL {
+            assert_eq!(class.name().parse(), Ok(class));
+        }
+        assert!("branch".parse::<InstrClass>().is_err());
+    }
+
+    #[test]
+    fn check_single_cycle() {
+        #[rustfmt::skip]
+        let mut hart = load(&[
+            0x0030_0513, // li      a0, 3
+            0x0000_2583, // lw      a1, 0(zero)
+            0x00b5_0633, // add     a2, a0, a1
+            0xfe05_1ae3, // bnez    a0, -12
+        ]);
+        hart.run(4).unwrap();
+
+        assert_eq!(hart.csrs.instret, 4);
+        assert_eq!(hart.csrs.cycle, 4);
+    }
+
+    #[test]
+    fn check_in_order() {
+        #[rustfmt::skip]
+        let mut hart = load(&[
+            0x0030_0513, // li      a0, 3               alu         1
+            0x0000_2583, // lw      a1, 0(zero)         load        1
+            0x00b5_0633, // add     a2, a0, a1          alu         1 + 1 stall
+            0x0000_2683, // lw      a3, 0(zero)         load        1
+            0x00a5_0733, // add     a4, a0, a0          alu         1
+            0x0005_0463, // beqz    a0, 8               not taken   1
+            0x0080_006f, // j       8                   jump        3
+            0x0000_0013, // nop
+            0x0000_0073, // ecall                       trap        3
+        ]);
+        hart.timing = Timing::new(Latencies::in_order());
+
+        hart.run(7).unwrap();
+        assert_eq!(hart.csrs.instret, 7);
+        assert_eq!(hart.csrs.cycle, 10);
+        assert_eq!(hart.timing.stalls(), 1);
+
+        assert_eq!(
+            hart.tick(),
+            Some(Trap::Exception(Exception::EnvironmentCall(Mode::Machine)))
+        );
+        assert_eq!(hart.csrs.instret, 7);
+        assert_eq!(hart.csrs.cycle, 13);
+    }
+
+    #[test]
+    fn check_taken_branch() {
+        #[rustfmt::skip]
+        let mut hart = load(&[
+            0x0020_0513, // li      a0, 2
+            0xfff5_0513, // addi    a0, a0, -1      ┐ loop
+            0xfe05_1ee3, // bnez    a0, -4          ┘
+        ]);
+        let mut latencies = Latencies::in_order();
+        latencies.set(InstrClass::BranchTaken, 5);
+        hart.timing = Timing::new(latencies);
+
+        hart.run(5).unwrap();
+        // li, 2 addis, a taken branch, and one that falls through
+        assert_eq!(hart.csrs.cycle, 1 + 2 + 5 + 1);
+    }
+
+    #[test]
+    fn check_machine_counters() {
+        #[rustfmt::skip]
+        let mut hart = load(&[
+            0x0030_0513, // li      a0, 3
+            0x0000_2583, // lw      a1, 0(zero)
+            0x00b5_0633, // add     a2, a0, a1          1 stall
+            0xb000_2573, // csrr    a0, mcycle
+            0xc000_25f3, // csrr    a1, cycle
+            0xb000_1073, // csrw    mcycle, zero
+            0xb000_2673, // csrr    a2, mcycle
+            0x3202_d073, // csrwi   mcountinhibit, 5
+            0x0000_0013, // nop
+            0xb020_26f3, // csrr    a3, minstret
+            0xb000_2773, // csrr    a4, mcycle
+        ]);
+        hart.timing = Timing::new(Latencies::in_order());
+
+        hart.run(3).unwrap();
+        assert_eq!(hart.csrs.instret, 3);
+        assert_eq!(hart.csrs.cycle, 4);
+
+        // Both read the same counter, as of the instruction that reads it
+        hart.run(1).unwrap();
+        assert_eq!(hart.reg(Reg::A0), 4);
+        let cycle = hart.csrs.cycle;
+        hart.run(1).unwrap();
+        assert_eq!(hart.reg(Reg::A1) as u64, cycle);
+
+        // The instruction after a write reads what was written
+        hart.run(2).unwrap();
+        assert_eq!(hart.reg(Reg::A2), 0);
+
+        // Nothing counts once both counters are inhibited
+        hart.run(1).unwrap();
+        let (instret, cycle) = (hart.csrs.instret, hart.csrs.cycle);
+        hart.run(3).unwrap();
+        assert_eq!(hart.reg(Reg::A3) as u64, instret);
+        assert_eq!(hart.reg(Reg::A4) as u64, cycle);
+        assert_eq!((hart.csrs.instret, hart.csrs.cycle), (instret, cycle));
+    }
+}
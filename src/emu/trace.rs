@@ -0,0 +1,557 @@
+use std::io::{self, Write};
+
+use crate::csr::{self, Mode};
+use crate::prelude::*;
+
+/// The effects of a single retired instruction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Commit {
+    pub pc: u32,
+    pub word: u32,
+    pub instr: Instr,
+
+    /// The mode that the instruction executed in
+    pub mode: Mode,
+
+    /// The register written and its new value, unless it was `zero`
+    pub reg: Option<(Reg, u32)>,
+
+    /// The CSR written and its new value
+    pub csr: Option<(u16, u32)>,
+
+    pub mem: Option<MemCommit>,
+
+    /// The address of the next instruction to execute
+    pub next_pc: u32,
+}
+
+/// A memory access made by a retired instruction
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MemCommit {
+    Load { addr: u32, width: u8 },
+    Store { addr: u32, width: u8, value: u32 },
+}
+
+/// Writes commits in the format of Spike's `-l --log-commits`
+///
+/// Each retired instruction becomes a line with its disassembly, followed by
+/// a line with its effects:
+/// ```text
+/// core   0: 0x80000004 (0x0002a503) lw      a0, 0(t0)
+/// core   0: 3 0x80000004 (0x0002a503) x10 0xdeadbeef mem 0x80001000
+/// ```
+/// The second line holds the mode, pc, instruction word, and then any register
+/// writes followed by memory accesses. Like Spike, the disassembly line is
+/// left out when the same instruction runs again in a row, and a line saying
+/// how many times it ran comes before the next one.
+///
+/// Instructions that raise an exception don't retire, so unlike Spike they
+/// aren't logged at all.
+pub struct CommitLog<W: Write> {
+    out: W,
+    hart_id: u32,
+    disassembly: bool,
+
+    /// The pc and word of the last instruction disassembled
+    last: Option<(u32, u32)>,
+    executions: u64,
+}
+
+impl<W: Write> CommitLog<W> {
+    pub fn new(out: W) -> Self {
+        CommitLog {
+            out,
+            hart_id: 0,
+            disassembly: true,
+            last: None,
+            executions: 0,
+        }
+    }
+
+    /// Label lines with `hart_id` instead of 0
+    pub fn hart_id(mut self, hart_id: u32) -> Self {
+        self.hart_id = hart_id;
+        self
+    }
+
+    /// Write the line that Spike's `-l` adds before each commit, which is on
+    /// by default
+    ///
+    /// Without it, the log matches Spike's `--log-commits` on its own.
+    pub fn disassembly(mut self, disassembly: bool) -> Self {
+        self.disassembly = disassembly;
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Write the lines for a single commit
+    pub fn log(&mut self, commit: &Commit) -> io::Result<()> {
+        if self.disassembly {
+            self.write_disassembly(commit)?;
+        }
+
+        write!(
+            self.out,
+            "core {:3}: {} 0x{:08x} (0x{:08x})",
+            self.hart_id, commit.mode as u8, commit.pc, commit.word
+        )?;
+
+        // Spike orders register writes by `(number << 4) | kind`, where CSRs
+        // have a kind of 4 and integer registers 0.
+        let reg_key = commit.reg.map(|(reg, _value)| (reg as u32) << 4);
+        let csr_key = commit.csr.map(|(csr, _value)| ((csr as u32) << 4) | 4);
+        let csr_first = matches!((reg_key, csr_key), (Some(reg), Some(csr)) if csr < reg);
+
+        if csr_first {
+            self.write_csr(commit)?;
+        }
+        if let Some((reg, value)) = commit.reg {
+            write!(self.out, " x{:<2} 0x{:08x}", reg as u32, value)?;
+        }
+        if !csr_first {
+            self.write_csr(commit)?;
+        }
+
+        match commit.mem {
+            Some(MemCommit::Load { addr, .. }) => write!(self.out, " mem 0x{:08x}", addr)?,
+            Some(MemCommit::Store { addr, width, value }) => write!(
+                self.out,
+                " mem 0x{:08x} 0x{:0width$x}",
+                addr,
+                value,
+                width = 2 * width as usize
+            )?,
+            None => {}
+        }
+
+        writeln!(self.out)
+    }
+
+    fn write_disassembly(&mut self, commit: &Commit) -> io::Result<()> {
+        if self.last == Some((commit.pc, commit.word)) {
+            self.executions += 1;
+            return Ok(());
+        }
+
+        if self.last.is_some() && self.executions != 1 {
+            writeln!(
+                self.out,
+                "core {:3}: Executed {} times",
+                self.hart_id, self.executions
+            )?;
+        }
+        self.last = Some((commit.pc, commit.word));
+        self.executions = 1;
+
+        writeln!(
+            self.out,
+            "core {:3}: 0x{:08x} (0x{:08x}) {}",
+            self.hart_id,
+            commit.pc,
+            commit.word,
+            spike_disassembly(&commit.instr)
+        )
+    }
+
+    fn write_csr(&mut self, commit: &Commit) -> io::Result<()> {
+        if let Some((num, value)) = commit.csr {
+            let name = csr::by_number(num).map_or("unknown-csr", |(name, _csr)| name);
+            write!(self.out, " c{}_{} 0x{:08x}", num, name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Disassemble `instr` the way that Spike does
+///
+/// This differs from `Instr::args` in a few ways: Spike prefers
+/// pseudo-instructions, shows branch and jump targets relative to `pc`, shows
+/// upper immediates in hex, and pads the mnemonic to 8 columns.
+fn spike_disassembly(instr: &Instr) -> String {
+    use Instr::*;
+    use Reg::*;
+
+    let address = |base: Reg, offset: i32| format!("{}({})", offset, base);
+    let target = |offset: i32| {
+        let sign = if offset < 0 { '-' } else { '+' };
+        format!("pc {} 0x{:x}", sign, offset.unsigned_abs())
+    };
+    let csr = |num: u16| match csr::by_number(num) {
+        Some((name, _csr)) => name.to_string(),
+        None => format!("unknown_{:03x}", num),
+    };
+    let fence_set = |bits: u8| {
+        "iorw"
+            .chars()
+            .enumerate()
+            .filter(|(i, _c)| bits & (0b1000 >> i) != 0)
+            .map(|(_i, c)| c)
+            .collect::<String>()
+    };
+
+    let (name, args): (&str, Vec<String>) = match *instr {
+        Illegal | Hint { .. } => ("unknown", vec![]),
+        Custom { .. } => (
+            instr.name(),
+            instr.args().iter().map(|arg| arg.to_string()).collect(),
+        ),
+
+        Lb { rd, rs1, imm } | Lh { rd, rs1, imm } | Lw { rd, rs1, imm } | Ld { rd, rs1, imm } => {
+            (instr.name(), vec![rd.to_string(), address(rs1, imm)])
+        }
+        Lbu { rd, rs1, imm } | Lhu { rd, rs1, imm } | Lwu { rd, rs1, imm } => {
+            (instr.name(), vec![rd.to_string(), address(rs1, imm as i32)])
+        }
+        Sb { rs1, rs2, imm }
+        | Sh { rs1, rs2, imm }
+        | Sw { rs1, rs2, imm }
+        | Sd { rs1, rs2, imm } => (instr.name(), vec![rs2.to_string(), address(rs1, imm)]),
+
+        Jal { rd: Zero, imm } => ("j", vec![target(imm)]),
+        Jal { rd: Ra, imm } => ("jal", vec![target(imm)]),
+        Jal { rd, imm } => ("jal", vec![rd.to_string(), target(imm)]),
+        Jalr {
+            rd: Zero,
+            rs1: Ra,
+            imm: 0,
+        } => ("ret", vec![]),
+        Jalr {
+            rd: Zero,
+            rs1,
+            imm: 0,
+        } => ("jr", vec![rs1.to_string()]),
+        Jalr {
+            rd: Ra,
+            rs1,
+            imm: 0,
+        } => ("jalr", vec![rs1.to_string()]),
+        Jalr { rd, rs1, imm } => ("jalr", vec![rd.to_string(), address(rs1, imm)]),
+
+        Beq {
+            rs1,
+            rs2: Zero,
+            imm,
+        } => ("beqz", vec![rs1.to_string(), target(imm)]),
+        Bne {
+            rs1,
+            rs2: Zero,
+            imm,
+        } => ("bnez", vec![rs1.to_string(), target(imm)]),
+        Blt {
+            rs1,
+            rs2: Zero,
+            imm,
+        } => ("bltz", vec![rs1.to_string(), target(imm)]),
+        Bge {
+            rs1,
+            rs2: Zero,
+            imm,
+        } => ("bgez", vec![rs1.to_string(), target(imm)]),
+        Beq { rs1, rs2, imm }
+        | Bne { rs1, rs2, imm }
+        | Blt { rs1, rs2, imm }
+        | Bge { rs1, rs2, imm }
+        | Bltu { rs1, rs2, imm }
+        | Bgeu { rs1, rs2, imm } => (
+            instr.name(),
+            vec![rs1.to_string(), rs2.to_string(), target(imm)],
+        ),
+
+        Lui { rd, imm } | Auipc { rd, imm } => {
+            (instr.name(), vec![rd.to_string(), format!("0x{:x}", imm)])
+        }
+
+        Addi {
+            rd: Zero,
+            rs1: Zero,
+            imm: 0,
+        } => ("nop", vec![]),
+        Addi { rd, rs1: Zero, imm } => ("li", vec![rd.to_string(), imm.to_string()]),
+        Addi { rd, rs1, imm: 0 } => ("mv", vec![rd.to_string(), rs1.to_string()]),
+        Sltiu { rd, rs1, imm12: 1 } => ("seqz", vec![rd.to_string(), rs1.to_string()]),
+        Xori { rd, rs1, imm12: -1 } => ("not", vec![rd.to_string(), rs1.to_string()]),
+        Addi { rd, rs1, imm } | Andi { rd, rs1, imm } => (
+            instr.name(),
+            vec![rd.to_string(), rs1.to_string(), imm.to_string()],
+        ),
+        Slti { rd, rs1, imm12 }
+        | Sltiu { rd, rs1, imm12 }
+        | Xori { rd, rs1, imm12 }
+        | Ori { rd, rs1, imm12 } => (
+            instr.name(),
+            vec![rd.to_string(), rs1.to_string(), imm12.to_string()],
+        ),
+        Slli { rd, rs1, imm5 } | Srli { rd, rs1, imm5 } | Srai { rd, rs1, imm5 } => (
+            instr.name(),
+            vec![rd.to_string(), rs1.to_string(), imm5.to_string()],
+        ),
+
+        // Spike shows `xor zero, zero, zero` as a bubble in the pipeline
+        Xor {
+            rd: Zero,
+            rs1: Zero,
+            rs2: Zero,
+        } => (" - ", vec![]),
+        Sltu { rd, rs1: Zero, rs2 } => ("snez", vec![rd.to_string(), rs2.to_string()]),
+        Add { rd, rs1, rs2 }
+        | Sub { rd, rs1, rs2 }
+        | Sll { rd, rs1, rs2 }
+        | Slt { rd, rs1, rs2 }
+        | Sltu { rd, rs1, rs2 }
+        | Xor { rd, rs1, rs2 }
+        | Srl { rd, rs1, rs2 }
+        | Sra { rd, rs1, rs2 }
+        | Or { rd, rs1, rs2 }
+        | And { rd, rs1, rs2 } => (
+            instr.name(),
+            vec![rd.to_string(), rs1.to_string(), rs2.to_string()],
+        ),
+
+        Fence {
+            fm: 0b1000,
+            predecessor: 0b0011,
+            successor: 0b0011,
+            ..
+        } => ("fence.tso", vec![]),
+        Fence {
+            predecessor,
+            successor,
+            ..
+        } => (
+            "fence",
+            vec![format!(
+                "{},{}",
+                fence_set(predecessor),
+                fence_set(successor)
+            )],
+        ),
+        FenceI { .. } | Ecall { .. } | Ebreak { .. } | Wfi {} | Uret {} | Sret {} | Mret {} => {
+            (instr.name(), vec![])
+        }
+
+        Csrrs {
+            rd,
+            rs1: Zero,
+            csr: num,
+        } => ("csrr", vec![rd.to_string(), csr(num)]),
+        Csrrw {
+            rd: Zero,
+            rs1,
+            csr: num,
+        } => ("csrw", vec![csr(num), rs1.to_string()]),
+        Csrrs {
+            rd: Zero,
+            rs1,
+            csr: num,
+        } => ("csrs", vec![csr(num), rs1.to_string()]),
+        Csrrc {
+            rd: Zero,
+            rs1,
+            csr: num,
+        } => ("csrc", vec![csr(num), rs1.to_string()]),
+        Csrrwi {
+            rd: Zero,
+            src,
+            csr: num,
+        } => ("csrwi", vec![csr(num), src.to_string()]),
+        Csrrsi {
+            rd: Zero,
+            src,
+            csr: num,
+        } => ("csrsi", vec![csr(num), src.to_string()]),
+        Csrrci {
+            rd: Zero,
+            src,
+            csr: num,
+        } => ("csrci", vec![csr(num), src.to_string()]),
+        Csrrw { rd, rs1, csr: num } | Csrrs { rd, rs1, csr: num } | Csrrc { rd, rs1, csr: num } => {
+            (
+                instr.name(),
+                vec![rd.to_string(), csr(num), rs1.to_string()],
+            )
+        }
+        Csrrwi { rd, src, csr: num }
+        | Csrrsi { rd, src, csr: num }
+        | Csrrci { rd, src, csr: num } => (
+            instr.name(),
+            vec![rd.to_string(), csr(num), src.to_string()],
+        ),
+    };
+
+    if args.is_empty() {
+        name.to_string()
+    } else {
+        format!("{:<7} {}", name, args.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::emu::{Hart, Ram};
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    #[test]
+    fn check_commit_log() {
+        #[rustfmt::skip]
+        let words: [u32; 7] = [
+            0x8000_12b7, // lui     t0, 0x80001
+            0x0372_8513, // addi    a0, t0, 55
+            0x00a2_8023, // sb      a0, 0(t0)
+            0x0002_a503, // lw      a0, 0(t0)
+            0x3405_15f3, // csrrw   a1, mscratch, a0
+            0x0000_0013, // nop
+            0x0000_0073, // ecall
+        ];
+        let mut ram = Ram::new(0x8000_0000, 0x2000);
+        for (i, word) in words.iter().enumerate() {
+            ram.write_bytes(0x8000_0000 + 4 * i as u32, &word.to_le_bytes())
+                .unwrap();
+        }
+        let mut hart = Hart::new(ram, 0x8000_0000);
+
+        let mut log = CommitLog::new(vec![]).disassembly(false);
+        while hart.step().is_ok() {
+            log.log(hart.last_commit().unwrap()).unwrap();
+        }
+        assert_eq!(hart.last_commit(), None);
+
+        let log = String::from_utf8(log.into_inner()).unwrap();
+        assert_eq!(
+            log,
+            "\
+core   0: 3 0x80000000 (0x800012b7) x5  0x80001000
+core   0: 3 0x80000004 (0x03728513) x10 0x80001037
+core   0: 3 0x80000008 (0x00a28023) mem 0x80001000 0x37
+core   0: 3 0x8000000c (0x0002a503) x10 0x00000037 mem 0x80001000
+core   0: 3 0x80000010 (0x340515f3) x11 0x00000000 c832_mscratch 0x00000037
+core   0: 3 0x80000014 (0x00000013)
+"
+        );
+    }
+
+    /// The start of a run of `spike --isa=rv32i -l --log-commits`, which
+    /// jumps from the reset vector to the ELF's entry point
+    #[test]
+    fn check_spike_reset_vector() {
+        #[rustfmt::skip]
+        let words: [u32; 8] = [
+            0x0000_0297,
+            0x0202_8593,
+            0xf140_2573,
+            0x0182_a283,
+            0x0002_8067,
+            0x0000_0000,
+            0x8000_0000,
+            0x0000_0000,
+        ];
+        let mut ram = Ram::new(0x1000, 0x1000);
+        for (i, word) in words.iter().enumerate() {
+            ram.write_bytes(0x1000 + 4 * i as u32, &word.to_le_bytes())
+                .unwrap();
+        }
+        let mut hart = Hart::new(ram, 0x1000);
+
+        let mut log = CommitLog::new(vec![]);
+        while hart.step().is_ok() {
+            log.log(hart.last_commit().unwrap()).unwrap();
+        }
+        assert_eq!(hart.pc(), 0x8000_0000);
+
+        let log = String::from_utf8(log.into_inner()).unwrap();
+        assert_eq!(
+            log,
+            "\
+core   0: 0x00001000 (0x00000297) auipc   t0, 0x0
+core   0: 3 0x00001000 (0x00000297) x5  0x00001000
+core   0: 0x00001004 (0x02028593) addi    a1, t0, 32
+core   0: 3 0x00001004 (0x02028593) x11 0x00001020
+core   0: 0x00001008 (0xf1402573) csrr    a0, mhartid
+core   0: 3 0x00001008 (0xf1402573) x10 0x00000000
+core   0: 0x0000100c (0x0182a283) lw      t0, 24(t0)
+core   0: 3 0x0000100c (0x0182a283) x5  0x80000000 mem 0x00001018
+core   0: 0x00001010 (0x00028067) jr      t0
+core   0: 3 0x00001010 (0x00028067)
+"
+        );
+    }
+
+    #[test]
+    fn check_spike_repeats() {
+        let mut ram = Ram::new(0x8000_0000, 0x1000);
+        // j       pc + 0x0
+        // nop
+        ram.write_bytes(0x8000_0000, &0x0000_006f_u32.to_le_bytes())
+            .unwrap();
+        ram.write_bytes(0x8000_0004, &0x0000_0013_u32.to_le_bytes())
+            .unwrap();
+        let mut hart = Hart::new(ram, 0x8000_0000);
+
+        let mut log = CommitLog::new(vec![]);
+        for _ in 0..3 {
+            hart.step().unwrap();
+            log.log(hart.last_commit().unwrap()).unwrap();
+        }
+        hart.set_pc(0x8000_0004);
+        hart.step().unwrap();
+        log.log(hart.last_commit().unwrap()).unwrap();
+
+        let log = String::from_utf8(log.into_inner()).unwrap();
+        assert_eq!(
+            log,
+            "\
+core   0: 0x80000000 (0x0000006f) j       pc + 0x0
+core   0: 3 0x80000000 (0x0000006f)
+core   0: 3 0x80000000 (0x0000006f)
+core   0: 3 0x80000000 (0x0000006f)
+core   0: Executed 3 times
+core   0: 0x80000004 (0x00000013) nop
+core   0: 3 0x80000004 (0x00000013)
+"
+        );
+    }
+
+    #[test]
+    fn check_spike_disassembly() {
+        #[rustfmt::skip]
+        let cases: [(u32, &str); 27] = [
+            (0x0000_0013, "nop"),
+            (0xfff0_0513, "li      a0, -1"),
+            (0x0005_8513, "mv      a0, a1"),
+            (0x0085_8513, "addi    a0, a1, 8"),
+            (0xfff5_c513, "not     a0, a1"),
+            (0x0015_b513, "seqz    a0, a1"),
+            (0x00b0_3533, "snez    a0, a1"),
+            (0x40b5_0533, "sub     a0, a0, a1"),
+            (0x0000_4033, " - "),
+            (0x4035_d513, "srai    a0, a1, 3"),
+            (0x8000_1537, "lui     a0, 0x80001"),
+            (0x00a1_2423, "sw      a0, 8(sp)"),
+            (0xffc5_c503, "lbu     a0, -4(a1)"),
+            (0x0000_8067, "ret"),
+            (0x0005_00e7, "jalr    a0"),
+            (0x0045_0567, "jalr    a0, 4(a0)"),
+            (0xffdf_f06f, "j       pc - 0x4"),
+            (0x0200_00ef, "jal     pc + 0x20"),
+            (0x0200_056f, "jal     a0, pc + 0x20"),
+            (0x0005_0863, "beqz    a0, pc + 0x10"),
+            (0xfeb5_4ee3, "blt     a0, a1, pc - 0x4"),
+            (0x3405_1073, "csrw    mscratch, a0"),
+            (0x3405_9573, "csrrw   a0, mscratch, a1"),
+            (0x7c00_2573, "csrr    a0, unknown_7c0"),
+            (0x0ff0_000f, "fence   iorw,iorw"),
+            (0x8330_000f, "fence.tso"),
+            (0x3020_0073, "mret"),
+        ];
+        for (word, expected) in cases.iter() {
+            let instr = decode_opcode(*word).unwrap();
+            assert_eq!(spike_disassembly(&instr), *expected, "{:#010x}", word);
+        }
+    }
+}
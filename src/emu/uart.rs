@@ -0,0 +1,228 @@
+use std::collections::VecDeque;
+use std::io::Write;
+
+use super::{BusFault, Device};
+
+/// The size of the register block, with registers one byte apart
+pub const UART_SIZE: u32 = 0x8;
+
+/// `IER`: interrupt when received data is available
+const IER_RDA: u8 = 1 << 0;
+
+/// `IER`: interrupt when the transmit holding register is empty
+const IER_THRE: u8 = 1 << 1;
+
+/// `IIR` when no interrupt is pending
+const IIR_NONE: u8 = 0x01;
+const IIR_THRE: u8 = 0x02;
+const IIR_RDA: u8 = 0x04;
+
+/// `IIR` bits reporting that the FIFOs are enabled
+const IIR_FIFO: u8 = 0xc0;
+
+const FCR_FIFO_ENABLE: u8 = 1 << 0;
+const FCR_CLEAR_RX: u8 = 1 << 1;
+
+/// `LCR.DLAB`: registers 0 and 1 access the baud rate divisor
+const LCR_DLAB: u8 = 1 << 7;
+
+/// `LSR.DR`: received data is ready
+const LSR_DR: u8 = 1 << 0;
+const LSR_THRE: u8 = 1 << 5;
+const LSR_TEMT: u8 = 1 << 6;
+
+/// A 16550-compatible UART
+///
+/// Transmitted bytes are written straight to `out`, so the transmitter is
+/// always empty. Received bytes are queued with [`Uart::push_input`].
+/// The baud rate and line settings can be written and read back, but they
+/// don't change anything.
+pub struct Uart<W: Write> {
+    out: W,
+    input: VecDeque<u8>,
+
+    ier: u8,
+    fcr: u8,
+    lcr: u8,
+    mcr: u8,
+    scr: u8,
+    divisor: u16,
+
+    /// Whether the "transmitter empty" interrupt is waiting to be seen in
+    /// `IIR`
+    thre_pending: bool,
+}
+
+impl<W: Write> Uart<W> {
+    pub fn new(out: W) -> Self {
+        Uart {
+            out,
+            input: VecDeque::new(),
+            ier: 0,
+            fcr: 0,
+            lcr: 0,
+            mcr: 0,
+            scr: 0,
+            divisor: 0,
+            thre_pending: false,
+        }
+    }
+
+    /// Queue `bytes` to be read from the receive buffer
+    pub fn push_input(&mut self, bytes: &[u8]) {
+        self.input.extend(bytes);
+    }
+
+    fn dlab(&self) -> bool {
+        self.lcr & LCR_DLAB != 0
+    }
+
+    fn rda_pending(&self) -> bool {
+        self.ier & IER_RDA != 0 && !self.input.is_empty()
+    }
+
+    /// Read the register at `offset`, with any side effects that has
+    fn read(&mut self, offset: u32) -> Option<u8> {
+        let value = match offset {
+            0 if self.dlab() => self.divisor as u8,
+            0 => self.input.pop_front().unwrap_or(0),
+            1 if self.dlab() => (self.divisor >> 8) as u8,
+            1 => self.ier,
+            2 => {
+                let fifo = if self.fcr & FCR_FIFO_ENABLE != 0 {
+                    IIR_FIFO
+                } else {
+                    0
+                };
+                // Reading IIR acknowledges a transmitter empty interrupt
+                let id = if self.rda_pending() {
+                    IIR_RDA
+                } else if self.ier & IER_THRE != 0 && self.thre_pending {
+                    self.thre_pending = false;
+                    IIR_THRE
+                } else {
+                    IIR_NONE
+                };
+                fifo | id
+            }
+            3 => self.lcr,
+            4 => self.mcr,
+            5 => {
+                let ready = if self.input.is_empty() { 0 } else { LSR_DR };
+                ready | LSR_THRE | LSR_TEMT
+            }
+            // No modem is connected
+            6 => 0,
+            7 => self.scr,
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    /// Write `value` to the register at `offset`
+    fn write(&mut self, offset: u32, value: u8) -> Option<()> {
+        match offset {
+            0 if self.dlab() => self.divisor = (self.divisor & 0xff00) | value as u16,
+            0 => {
+                // There's nowhere to report a failed write, and a real UART
+                // would drop the byte too
+                let _ = self.out.write_all(&[value]).and_then(|_| self.out.flush());
+                self.thre_pending = true;
+            }
+            1 if self.dlab() => self.divisor = (self.divisor & 0x00ff) | (value as u16) << 8,
+            1 => {
+                // Enabling the interrupt raises it straight away, since the
+                // transmitter is always empty
+                if self.ier & IER_THRE == 0 && value & IER_THRE != 0 {
+                    self.thre_pending = true;
+                }
+                self.ier = value & 0x0f;
+            }
+            2 => {
+                if value & FCR_CLEAR_RX != 0 {
+                    self.input.clear();
+                }
+                self.fcr = value & FCR_FIFO_ENABLE;
+            }
+            3 => self.lcr = value,
+            4 => self.mcr = value & 0x1f,
+            // LSR and MSR are read-only
+            5 | 6 => {}
+            7 => self.scr = value,
+            _ => return None,
+        }
+
+        Some(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write> Device for Uart<W> {
+    fn load(&mut self, offset: u32, _width: u8) -> Result<u32, BusFault> {
+        self.read(offset)
+            .map(|value| value as u32)
+            .ok_or(BusFault { addr: offset })
+    }
+
+    fn store(&mut self, offset: u32, _width: u8, value: u32) -> Result<(), BusFault> {
+        self.write(offset, value as u8)
+            .ok_or(BusFault { addr: offset })
+    }
+
+    fn irq(&self) -> bool {
+        self.rda_pending() || (self.ier & IER_THRE != 0 && self.thre_pending)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    #[test]
+    fn check_uart() {
+        let mut uart = Uart::new(vec![]);
+
+        // Set the divisor, and make sure it doesn't get in the way of data
+        uart.store(3, 1, LCR_DLAB as u32).unwrap();
+        uart.store(0, 1, 0x01).unwrap();
+        uart.store(1, 1, 0x02).unwrap();
+        uart.store(3, 1, 0x03).unwrap();
+        assert_eq!(uart.divisor, 0x0201);
+
+        for &byte in b"hi\n" {
+            assert_eq!(uart.load(5, 1).unwrap() as u8 & LSR_THRE, LSR_THRE);
+            uart.store(0, 1, byte as u32).unwrap();
+        }
+
+        // Nothing to read yet
+        assert_eq!(uart.load(5, 1).unwrap() as u8 & LSR_DR, 0);
+        assert_eq!(uart.load(2, 1), Ok(IIR_NONE as u32));
+        assert!(!uart.irq());
+
+        uart.push_input(b"ok");
+        uart.store(1, 1, (IER_RDA | IER_THRE) as u32).unwrap();
+        assert!(uart.irq());
+        assert_eq!(uart.load(2, 1), Ok(IIR_RDA as u32));
+
+        let mut received = vec![];
+        while uart.load(5, 1).unwrap() as u8 & LSR_DR != 0 {
+            received.push(uart.load(0, 1).unwrap() as u8);
+        }
+        assert_eq!(received, b"ok");
+
+        // Reading IIR acknowledges the transmit interrupt
+        assert!(uart.irq());
+        assert_eq!(uart.load(2, 1), Ok(IIR_THRE as u32));
+        assert!(!uart.irq());
+
+        assert_eq!(uart.load(8, 1), Err(BusFault { addr: 8 }));
+        assert_eq!(uart.into_inner(), b"hi\n");
+    }
+}
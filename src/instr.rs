@@ -1,12 +1,16 @@
 #![deny(unreachable_patterns)]
 
 use core::convert::TryFrom;
-use std::fmt;
+use core::fmt;
+use core::ops::Deref;
+
+use crate::csr;
+use crate::custom;
 
 /// Register mnemonics for the standard ABI
 ///
 /// See: https://github.com/riscv/riscv-elf-psabi-doc/blob/master/riscv-elf.md#integer-register-convention-
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum Reg {
     Zero = 0,
@@ -43,6 +47,53 @@ pub enum Reg {
     T6 = 31,
 }
 
+impl Reg {
+    /// The register with the index in the low 5 bits of `bits`
+    ///
+    /// Unlike `Reg::try_from`, this can't fail, so it's what the decoder uses
+    /// for register fields. Every index is its own discriminant, so this
+    /// compiles down to nothing.
+    #[inline]
+    pub const fn from_u5(bits: u32) -> Reg {
+        use Reg::*;
+
+        match bits & 0x1f {
+            0 => Zero,
+            1 => Ra,
+            2 => Sp,
+            3 => Gp,
+            4 => Tp,
+            5 => T0,
+            6 => T1,
+            7 => T2,
+            8 => S0,
+            9 => S1,
+            10 => A0,
+            11 => A1,
+            12 => A2,
+            13 => A3,
+            14 => A4,
+            15 => A5,
+            16 => A6,
+            17 => A7,
+            18 => S2,
+            19 => S3,
+            20 => S4,
+            21 => S5,
+            22 => S6,
+            23 => S7,
+            24 => S8,
+            25 => S9,
+            26 => S10,
+            27 => S11,
+            28 => T3,
+            29 => T4,
+            30 => T5,
+            _ => T6,
+        }
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Instr {
@@ -366,6 +417,14 @@ pub enum Instr {
         /// Most of them use rd == x0 as a reserved space
         hint: (),
     },
+
+    /// An instruction in the `custom-0` or `custom-1` opcode space
+    ///
+    /// `id` is the instruction that decoded it, from [`crate::custom::register`].
+    Custom {
+        id: u16,
+        word: u32,
+    },
 }
 
 /// Instructions have arguments that specify the data that they used when executed.
@@ -377,7 +436,7 @@ pub enum Instr {
 ///     3. A pair that forms a base (stored in a register) and offset (as an immediate) (`0(ra)`, `4(sp)`, ...)
 ///
 /// This is used when enumerating over the instructions arguments. offset + base pairs are treated as a single argument
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Arg {
     /// A value read from a register before executing the instruction, or written to one afterwards
     Register(Reg),
@@ -391,12 +450,172 @@ pub enum Arg {
     /// Some (usually `SYSTEM`) instructions take special, named values that correspond to immediates
     ///
     /// It's more useful to represent them as strings than immediates, so there's a dedicated Arg type for them.
-    Special(String),
+    Special(&'static str),
+
+    /// A CSR, which is shown by name if it has one and in hex otherwise
+    Csr(u16),
 
     /// A pair of a register and a signed offset that is added to get an address that acts as a single argument
     Address { base: Reg, offset: i32 },
 }
 
+/// The layout of the fields in an instruction's encoding
+///
+/// See the diagrams in `decode_opcode` for the base formats.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// Register-register operations
+    R,
+    /// Register-immediate operations, loads, `jalr`, and `SYSTEM` instructions
+    I,
+    /// Stores
+    S,
+    /// Conditional branches
+    B,
+    /// Upper immediates: `lui` and `auipc`
+    U,
+    /// `jal`
+    J,
+}
+
+/// The ISA extension that defines an instruction
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Extension {
+    /// The base integer ISA, including the RV64I-only loads and stores
+    I,
+    /// Control and status register instructions
+    Zicsr,
+    /// `fence.i`
+    Zifencei,
+    /// Instructions from the privileged architecture, like `mret` and `wfi`
+    Privileged,
+    /// Vendor instructions in the `custom-0` and `custom-1` opcode spaces
+    Custom,
+}
+
+/// How an instruction decides which instruction runs after it
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ControlFlow {
+    /// Always continues to the next instruction
+    Sequential,
+
+    /// Either continues to the next instruction or jumps to a pc-relative target
+    ConditionalBranch,
+
+    /// Jumps to a pc-relative target without linking, e.g. `j`
+    DirectJump,
+
+    /// Jumps and links a return address, e.g. `call` or `jalr a0`
+    Call,
+
+    /// Jumps to the return address in `ra` or `t0`, e.g. `ret`
+    Return,
+
+    /// Jumps to an address in a register without linking, e.g. `jr a0`
+    IndirectJump,
+
+    /// Raises an exception, e.g. `ecall`
+    Trap,
+
+    /// Returns from a trap handler, e.g. `mret`
+    TrapReturn,
+}
+
+/// A set of registers, stored as one bit per register
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RegSet(u32);
+
+impl RegSet {
+    /// A set with no registers in it
+    pub const fn new() -> Self {
+        RegSet(0)
+    }
+
+    pub fn insert(&mut self, reg: Reg) {
+        self.0 |= 1 << (reg as u32);
+    }
+
+    pub fn remove(&mut self, reg: Reg) {
+        self.0 &= !(1 << (reg as u32));
+    }
+
+    pub const fn contains(&self, reg: Reg) -> bool {
+        self.0 & (1 << (reg as u32)) != 0
+    }
+
+    pub const fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Every register that is in either set
+    pub const fn union(&self, other: RegSet) -> RegSet {
+        RegSet(self.0 | other.0)
+    }
+
+    /// Every register in this set, in order of register index
+    pub fn iter(&self) -> impl Iterator<Item = Reg> {
+        let bits = self.0;
+        (0_u32..32)
+            .filter(move |idx| bits & (1 << idx) != 0)
+            .map(Reg::from_u5)
+    }
+}
+
+impl core::iter::FromIterator<Reg> for RegSet {
+    fn from_iter<I: IntoIterator<Item = Reg>>(iter: I) -> Self {
+        let mut set = RegSet::new();
+        for reg in iter {
+            set.insert(reg);
+        }
+        set
+    }
+}
+
+/// Whether an instruction reads from or writes to memory
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MemOp {
+    Load,
+    Store,
+}
+
+/// The memory accessed by a load or store instruction
+///
+/// The address accessed is `base + offset`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MemAccess {
+    pub op: MemOp,
+
+    /// Number of bytes accessed: 1, 2, 4, or 8
+    pub width: u8,
+
+    /// Whether a load sign-extends the value it reads
+    ///
+    /// This is always `false` for stores.
+    pub signed: bool,
+
+    pub base: Reg,
+    pub offset: i32,
+}
+
+/// The CSR accessed by a `Zicsr` instruction
+///
+/// Some forms skip the read or the write, which matters for CSRs with side
+/// effects and for read-only CSRs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CsrAccess {
+    pub csr: u16,
+
+    /// Whether the old value is read, which is skipped by `csrrw[i]` into `zero`
+    pub read: bool,
+
+    /// Whether a new value is written, which is skipped by `csrr[sc][i]` of `zero`
+    pub write: bool,
+}
+
 impl fmt::Display for Reg {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use Reg::*;
@@ -449,6 +668,10 @@ impl fmt::Display for Arg {
             UnsignedImm(imm) => write!(f, "{}", imm),
             SignedImm(imm) => write!(f, "{}", imm),
             Special(special) => write!(f, "{}", special),
+            Csr(num) => match csr::by_number(*num) {
+                Some((name, _csr)) => write!(f, "{}", name),
+                None => write!(f, "{:#x}", num),
+            },
             Address { base, offset } => write!(f, "{offset}({base})", base = base, offset = offset),
         }
     }
@@ -472,14 +695,118 @@ impl From<u32> for Arg {
     }
 }
 
+/// The arguments of an instruction, in assembly order
+///
+/// These are kept in a fixed-size array instead of a `Vec`, so that decoding
+/// never allocates. `Args` derefs to a slice of just the arguments.
+#[derive(Copy, Clone)]
+pub struct Args {
+    args: [Arg; Args::MAX],
+    len: u8,
+}
+
+impl Args {
+    /// The most arguments that an instruction can have
+    pub const MAX: usize = 4;
+
+    /// No arguments
+    pub const fn new() -> Self {
+        Args {
+            args: [Arg::UnsignedImm(0); Args::MAX],
+            len: 0,
+        }
+    }
+
+    /// Add `arg` after the others
+    ///
+    /// Returns `arg` back if there are already [`Args::MAX`] arguments.
+    pub fn push(&mut self, arg: Arg) -> Result<(), Arg> {
+        let len = self.len as usize;
+        if len == Args::MAX {
+            return Err(arg);
+        }
+        self.args[len] = arg;
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn as_slice(&self) -> &[Arg] {
+        &self.args[..self.len as usize]
+    }
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Args::new()
+    }
+}
+
+impl Deref for Args {
+    type Target = [Arg];
+
+    fn deref(&self) -> &[Arg] {
+        self.as_slice()
+    }
+}
+
+impl fmt::Debug for Args {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for Args {
+    fn eq(&self, other: &Args) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Eq for Args {}
+
+impl<const N: usize> From<[Arg; N]> for Args {
+    fn from(args: [Arg; N]) -> Args {
+        args.iter().copied().collect()
+    }
+}
+
+/// Arguments after the first [`Args::MAX`] are left out
+impl core::iter::FromIterator<Arg> for Args {
+    fn from_iter<I: IntoIterator<Item = Arg>>(iter: I) -> Self {
+        let mut args = Args::new();
+        for arg in iter.into_iter().take(Args::MAX) {
+            let _ = args.push(arg);
+        }
+        args
+    }
+}
+
+impl IntoIterator for Args {
+    type Item = Arg;
+    type IntoIter = core::iter::Take<core::array::IntoIter<Arg, { Args::MAX }>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIterator::into_iter(self.args).take(self.len as usize)
+    }
+}
+
+impl<'a> IntoIterator for &'a Args {
+    type Item = &'a Arg;
+    type IntoIter = core::slice::Iter<'a, Arg>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.as_slice().iter()
+    }
+}
+
 impl Instr {
     /// The all-lowercase neumonic for this instruction
-    pub fn name(&self) -> String {
+    pub fn name(&self) -> &'static str {
         use Instr::*;
 
         match *self {
             Illegal => "illegal",
             Hint { .. } => "hint",
+            Custom { id, word } => custom::get(id).map_or("custom", |instr| instr.name(word)),
 
             Add { .. } => "add",
             Addi { .. } => "addi",
@@ -500,8 +827,14 @@ impl Instr {
             Csrrwi { .. } => "csrrwi",
             Ebreak { .. } => "ebreak",
             Ecall { .. } => "ecall",
+            Fence {
+                fm: FENCE_TSO,
+                predecessor: 0b0011,
+                successor: 0b0011,
+                ..
+            } => "fence.tso",
             Fence { .. } => "fence",
-            FenceI { .. } => "fencei",
+            FenceI { .. } => "fence.i",
             Jal { .. } => "jal",
             Jalr { .. } => "jalr",
             Lb { .. } => "lb",
@@ -536,167 +869,528 @@ impl Instr {
             Xor { .. } => "xor",
             Xori { .. } => "xori",
         }
-        .into()
     }
 
     /// Values provided to an instruction that change its behavior
     ///
     /// Note: The number of arguments may change depending on the value of some arguments (e.g. omitting a trivial argument)
     // TODO: Clarify when this happens.
-    pub fn args(&self) -> Vec<Arg> {
+    pub fn args(&self) -> Args {
         use Arg::*;
         use Instr::*;
         use Reg::*;
 
         match *self {
-            Illegal => vec![],
-            Hint { .. } => vec![],
+            Illegal => Args::new(),
+            Hint { .. } => Args::new(),
+            Custom { id, word } => custom::get(id)
+                .map(|instr| instr.args(word))
+                .unwrap_or_default(),
 
-            Add { rd, rs1, rs2 } => vec![rd.into(), rs1.into(), rs2.into()],
-            Addi { rd, rs1, imm } => vec![rd.into(), rs1.into(), imm.into()],
+            Add { rd, rs1, rs2 } => [rd.into(), rs1.into(), rs2.into()].into(),
+            Addi { rd, rs1, imm } => [rd.into(), rs1.into(), imm.into()].into(),
 
-            And { rd, rs1, rs2 } => vec![rd.into(), rs1.into(), rs2.into()],
-            Andi { rd, rs1, imm } => vec![rd.into(), rs1.into(), imm.into()],
+            And { rd, rs1, rs2 } => [rd.into(), rs1.into(), rs2.into()].into(),
+            Andi { rd, rs1, imm } => [rd.into(), rs1.into(), imm.into()].into(),
 
-            Auipc { rd, imm } => vec![rd.into(), imm.into()],
+            Auipc { rd, imm } => [rd.into(), imm.into()].into(),
 
-            Beq { rs1, rs2, imm } => vec![rs1.into(), rs2.into(), imm.into()],
-            Bge { rs1, rs2, imm } => vec![rs1.into(), rs2.into(), imm.into()],
-            Bgeu { rs1, rs2, imm } => vec![rs1.into(), rs2.into(), imm.into()],
-            Blt { rs1, rs2, imm } => vec![rs1.into(), rs2.into(), imm.into()],
-            Bltu { rs1, rs2, imm } => vec![rs1.into(), rs2.into(), imm.into()],
-            Bne { rs1, rs2, imm } => vec![rs1.into(), rs2.into(), imm.into()],
+            Beq { rs1, rs2, imm } => [rs1.into(), rs2.into(), imm.into()].into(),
+            Bge { rs1, rs2, imm } => [rs1.into(), rs2.into(), imm.into()].into(),
+            Bgeu { rs1, rs2, imm } => [rs1.into(), rs2.into(), imm.into()].into(),
+            Blt { rs1, rs2, imm } => [rs1.into(), rs2.into(), imm.into()].into(),
+            Bltu { rs1, rs2, imm } => [rs1.into(), rs2.into(), imm.into()].into(),
+            Bne { rs1, rs2, imm } => [rs1.into(), rs2.into(), imm.into()].into(),
 
-            Csrrc { .. } => vec![],
-            Csrrci { .. } => vec![],
-            Csrrs { .. } => vec![],
-            Csrrsi { .. } => vec![],
-            Csrrw { .. } => vec![],
-            Csrrwi { .. } => vec![],
-            Ebreak { .. } => vec![],
-            Ecall { .. } => vec![],
+            Csrrc { rd, rs1, csr } => [rd.into(), csr_arg(csr), rs1.into()].into(),
+            Csrrci { rd, src, csr } => [rd.into(), csr_arg(csr), UnsignedImm(src as u32)].into(),
+            Csrrs { rd, rs1, csr } => [rd.into(), csr_arg(csr), rs1.into()].into(),
+            Csrrsi { rd, src, csr } => [rd.into(), csr_arg(csr), UnsignedImm(src as u32)].into(),
+            Csrrw { rd, rs1, csr } => [rd.into(), csr_arg(csr), rs1.into()].into(),
+            Csrrwi { rd, src, csr } => [rd.into(), csr_arg(csr), UnsignedImm(src as u32)].into(),
+            Ebreak { .. } => Args::new(),
+            Ecall { .. } => Args::new(),
 
             Fence {
-                rd,
-                rs1,
+                fm: FENCE_TSO,
+                predecessor: 0b0011,
+                successor: 0b0011,
+                ..
+            } => Args::new(),
+            Fence {
                 successor,
                 predecessor,
-                fm,
-            } => vec![
-                Register(rd),
-                Register(rs1),
-                Special(format!(
-                    "succ: 0b{:b}, pred: 0b{:b}, fm: 0b{:b}",
-                    successor, predecessor, fm
-                )),
-            ],
-            FenceI { rd, rs1, imm12 } => vec![rd.into(), rs1.into(), imm12.into()],
-
-            Jal { rd, imm } => vec![rd.into(), imm.into()],
-            Jalr { rd: Ra, rs1, imm } => vec![Address {
-                base: rs1,
-                offset: imm,
-            }],
-            Jalr { rd, rs1, imm } => vec![
+                ..
+            } => [
+                Special(fence_set(predecessor)),
+                Special(fence_set(successor)),
+            ]
+            .into(),
+            // The fields of `fence.i` are reserved, and are only shown when they're used
+            FenceI {
+                rd: Zero,
+                rs1: Zero,
+                imm12: 0,
+            } => Args::new(),
+            FenceI { rd, rs1, imm12 } => [rd.into(), rs1.into(), imm12.into()].into(),
+
+            Jal { rd, imm } => [rd.into(), imm.into()].into(),
+            Jalr { rd, rs1, imm } => [
                 rd.into(),
                 Address {
                     base: rs1,
                     offset: imm,
                 },
-            ],
+            ]
+            .into(),
 
-            Lb { rd, rs1, imm } => vec![
+            Lb { rd, rs1, imm } => [
                 rd.into(),
                 Address {
                     base: rs1,
                     offset: imm,
                 },
-            ],
-            Ld { rd, rs1, imm } => vec![
+            ]
+            .into(),
+            Ld { rd, rs1, imm } => [
                 rd.into(),
                 Address {
                     base: rs1,
                     offset: imm,
                 },
-            ],
-            Lh { rd, rs1, imm } => vec![
+            ]
+            .into(),
+            Lh { rd, rs1, imm } => [
                 rd.into(),
                 Address {
                     base: rs1,
                     offset: imm,
                 },
-            ],
-            Lw { rd, rs1, imm } => vec![
+            ]
+            .into(),
+            Lw { rd, rs1, imm } => [
                 rd.into(),
                 Address {
                     base: rs1,
                     offset: imm,
                 },
-            ],
+            ]
+            .into(),
 
-            Lbu { rd, rs1: _, imm: _ } => vec![rd.into()],
-            Lhu { rd, rs1: _, imm: _ } => vec![rd.into()],
-            Lwu { rd, rs1: _, imm: _ } => vec![rd.into()],
+            Lbu { rd, rs1, imm } | Lhu { rd, rs1, imm } | Lwu { rd, rs1, imm } => [
+                rd.into(),
+                Address {
+                    base: rs1,
+                    offset: imm as i32,
+                },
+            ]
+            .into(),
 
-            Lui { rd, imm } => vec![rd.into(), imm.into()],
+            Lui { rd, imm } => [rd.into(), imm.into()].into(),
 
-            Mret { .. } => vec![],
+            Mret { .. } => Args::new(),
 
-            Or { rd, rs1, rs2 } => vec![Register(rd), Register(rs1), Register(rs2)],
-            Ori { rd, rs1, imm12 } => vec![Register(rd), Register(rs1), SignedImm(imm12)],
+            Or { rd, rs1, rs2 } => [Register(rd), Register(rs1), Register(rs2)].into(),
+            Ori { rd, rs1, imm12 } => [Register(rd), Register(rs1), SignedImm(imm12)].into(),
 
-            Sb { rs1, rs2, imm } => vec![
+            Sb { rs1, rs2, imm } => [
                 rs2.into(),
                 Address {
                     base: rs1,
                     offset: imm,
                 },
-            ],
-            Sd { rs1, rs2, imm } => vec![
+            ]
+            .into(),
+            Sd { rs1, rs2, imm } => [
                 rs2.into(),
                 Address {
                     base: rs1,
                     offset: imm,
                 },
-            ],
-            Sh { rs1, rs2, imm } => vec![
+            ]
+            .into(),
+            Sh { rs1, rs2, imm } => [
                 rs2.into(),
                 Address {
                     base: rs1,
                     offset: imm,
                 },
-            ],
-            Sw { rs1, rs2, imm } => vec![
+            ]
+            .into(),
+            Sw { rs1, rs2, imm } => [
                 rs2.into(),
                 Address {
                     base: rs1,
                     offset: imm,
                 },
-            ],
-
-            Sll { .. } => vec![],
-            Slli { .. } => vec![],
-            Slt { .. } => vec![],
-            Slti { .. } => vec![],
-            Sltiu { .. } => vec![],
-            Sltu { .. } => vec![],
-            Sra { .. } => vec![],
-            Srai { .. } => vec![],
-            Sret { .. } => vec![],
-            Srl { .. } => vec![],
-            Srli { .. } => vec![],
-
-            Sub { rd, rs1, rs2 } => vec![Register(rd), Register(rs1), Register(rs2)],
-
-            Uret { .. } => vec![],
-            Wfi { .. } => vec![],
-
-            Xor { rd, rs1, rs2 } => vec![rd.into(), rs1.into(), rs2.into()],
-            Xori { rd, rs1, imm12 } => vec![rd.into(), rs1.into(), imm12.into()],
+            ]
+            .into(),
+
+            Sll { rd, rs1, rs2 } => [rd.into(), rs1.into(), rs2.into()].into(),
+            Slli { rd, rs1, imm5 } => [rd.into(), rs1.into(), UnsignedImm(imm5 as u32)].into(),
+            Slt { rd, rs1, rs2 } => [rd.into(), rs1.into(), rs2.into()].into(),
+            Slti { rd, rs1, imm12 } => [rd.into(), rs1.into(), imm12.into()].into(),
+            Sltiu { rd, rs1, imm12 } => [rd.into(), rs1.into(), imm12.into()].into(),
+            Sltu { rd, rs1, rs2 } => [rd.into(), rs1.into(), rs2.into()].into(),
+            Sra { rd, rs1, rs2 } => [rd.into(), rs1.into(), rs2.into()].into(),
+            Srai { rd, rs1, imm5 } => [rd.into(), rs1.into(), UnsignedImm(imm5 as u32)].into(),
+            Sret { .. } => Args::new(),
+            Srl { rd, rs1, rs2 } => [rd.into(), rs1.into(), rs2.into()].into(),
+            Srli { rd, rs1, imm5 } => [rd.into(), rs1.into(), UnsignedImm(imm5 as u32)].into(),
+
+            Sub { rd, rs1, rs2 } => [Register(rd), Register(rs1), Register(rs2)].into(),
+
+            Uret { .. } => Args::new(),
+            Wfi { .. } => Args::new(),
+
+            Xor { rd, rs1, rs2 } => [rd.into(), rs1.into(), rs2.into()].into(),
+            Xori { rd, rs1, imm12 } => [rd.into(), rs1.into(), imm12.into()].into(),
         }
     }
 }
 
+/// A CSR as an argument
+fn csr_arg(csr: u16) -> Arg {
+    Arg::Csr(csr)
+}
+
+/// The `fm` of `fence.tso`, which orders everything except stores before
+/// later loads when both sets are `rw`
+const FENCE_TSO: u8 = 0b1000;
+
+/// The predecessor or successor set of a `fence`, like "rw"
+fn fence_set(bits: u8) -> &'static str {
+    const SETS: [&str; 16] = [
+        "0", "w", "r", "rw", "o", "ow", "or", "orw", "i", "iw", "ir", "irw", "io", "iow", "ior",
+        "iorw",
+    ];
+    SETS[(bits & 0xf) as usize]
+}
+
+impl Instr {
+    /// Registers whose values are used by this instruction
+    ///
+    /// `zero` always reads as 0, so nothing depends on its value, and it's
+    /// never included.
+    pub fn reads(&self) -> RegSet {
+        use Instr::*;
+
+        let regs: &[Reg] = match self {
+            Illegal | Hint { .. } => &[],
+            Custom { id, word } => {
+                let mut regs = custom::get(*id).map_or(RegSet::new(), |instr| instr.reads(*word));
+                regs.remove(Reg::Zero);
+                return regs;
+            }
+
+            // The register fields of these are reserved, and ignored
+            Fence { .. } | FenceI { .. } => &[],
+            Ecall { .. } | Ebreak { .. } => &[],
+            Wfi {} | Uret {} | Sret {} | Mret {} => &[],
+
+            Lui { .. } | Auipc { .. } | Jal { .. } => &[],
+            Csrrwi { .. } | Csrrsi { .. } | Csrrci { .. } => &[],
+
+            Lb { rs1, .. }
+            | Lh { rs1, .. }
+            | Lw { rs1, .. }
+            | Ld { rs1, .. }
+            | Lbu { rs1, .. }
+            | Lhu { rs1, .. }
+            | Lwu { rs1, .. }
+            | Addi { rs1, .. }
+            | Slli { rs1, .. }
+            | Slti { rs1, .. }
+            | Sltiu { rs1, .. }
+            | Xori { rs1, .. }
+            | Srli { rs1, .. }
+            | Srai { rs1, .. }
+            | Ori { rs1, .. }
+            | Andi { rs1, .. }
+            | Jalr { rs1, .. }
+            | Csrrw { rs1, .. }
+            | Csrrs { rs1, .. }
+            | Csrrc { rs1, .. } => core::slice::from_ref(rs1),
+
+            Sb { rs1, rs2, .. }
+            | Sh { rs1, rs2, .. }
+            | Sw { rs1, rs2, .. }
+            | Sd { rs1, rs2, .. }
+            | Add { rs1, rs2, .. }
+            | Sub { rs1, rs2, .. }
+            | Sll { rs1, rs2, .. }
+            | Slt { rs1, rs2, .. }
+            | Sltu { rs1, rs2, .. }
+            | Xor { rs1, rs2, .. }
+            | Srl { rs1, rs2, .. }
+            | Sra { rs1, rs2, .. }
+            | Or { rs1, rs2, .. }
+            | And { rs1, rs2, .. }
+            | Beq { rs1, rs2, .. }
+            | Bne { rs1, rs2, .. }
+            | Blt { rs1, rs2, .. }
+            | Bge { rs1, rs2, .. }
+            | Bltu { rs1, rs2, .. }
+            | Bgeu { rs1, rs2, .. } => &[*rs1, *rs2],
+        };
+
+        regs.iter()
+            .cloned()
+            .filter(|&reg| reg != Reg::Zero)
+            .collect()
+    }
+
+    /// Registers whose values are changed by this instruction
+    ///
+    /// Writes to `zero` are discarded by the hardware, so `zero` is never
+    /// included.
+    pub fn writes(&self) -> RegSet {
+        use Instr::*;
+
+        let rd = match *self {
+            Illegal | Hint { .. } => None,
+            Custom { id, word } => {
+                let mut regs = custom::get(id).map_or(RegSet::new(), |instr| instr.writes(word));
+                regs.remove(Reg::Zero);
+                return regs;
+            }
+
+            Fence { .. } | FenceI { .. } => None,
+            Ecall { .. } | Ebreak { .. } => None,
+            Wfi {} | Uret {} | Sret {} | Mret {} => None,
+
+            Sb { .. } | Sh { .. } | Sw { .. } | Sd { .. } => None,
+            Beq { .. } | Bne { .. } | Blt { .. } | Bge { .. } | Bltu { .. } | Bgeu { .. } => None,
+
+            Lb { rd, .. }
+            | Lh { rd, .. }
+            | Lw { rd, .. }
+            | Ld { rd, .. }
+            | Lbu { rd, .. }
+            | Lhu { rd, .. }
+            | Lwu { rd, .. }
+            | Addi { rd, .. }
+            | Slli { rd, .. }
+            | Slti { rd, .. }
+            | Sltiu { rd, .. }
+            | Xori { rd, .. }
+            | Srli { rd, .. }
+            | Srai { rd, .. }
+            | Ori { rd, .. }
+            | Andi { rd, .. }
+            | Auipc { rd, .. }
+            | Lui { rd, .. }
+            | Add { rd, .. }
+            | Sub { rd, .. }
+            | Sll { rd, .. }
+            | Slt { rd, .. }
+            | Sltu { rd, .. }
+            | Xor { rd, .. }
+            | Srl { rd, .. }
+            | Sra { rd, .. }
+            | Or { rd, .. }
+            | And { rd, .. }
+            | Jal { rd, .. }
+            | Jalr { rd, .. }
+            | Csrrw { rd, .. }
+            | Csrrs { rd, .. }
+            | Csrrc { rd, .. }
+            | Csrrwi { rd, .. }
+            | Csrrsi { rd, .. }
+            | Csrrci { rd, .. } => Some(rd),
+        };
+
+        rd.into_iter().filter(|&rd| rd != Reg::Zero).collect()
+    }
+
+    /// The layout of this instruction's encoding
+    ///
+    /// `Illegal`, `Hint`, and `Custom` don't have a single format, and return
+    /// `None`.
+    pub fn format(&self) -> Option<Format> {
+        use Instr::*;
+
+        let format = match *self {
+            Illegal | Hint { .. } | Custom { .. } => return None,
+
+            Add { .. }
+            | Sub { .. }
+            | Sll { .. }
+            | Slt { .. }
+            | Sltu { .. }
+            | Xor { .. }
+            | Srl { .. }
+            | Sra { .. }
+            | Or { .. }
+            | And { .. } => Format::R,
+
+            Lb { .. }
+            | Lh { .. }
+            | Lw { .. }
+            | Ld { .. }
+            | Lbu { .. }
+            | Lhu { .. }
+            | Lwu { .. }
+            | Fence { .. }
+            | FenceI { .. }
+            | Addi { .. }
+            | Slli { .. }
+            | Slti { .. }
+            | Sltiu { .. }
+            | Xori { .. }
+            | Srli { .. }
+            | Srai { .. }
+            | Ori { .. }
+            | Andi { .. }
+            | Jalr { .. }
+            | Ecall { .. }
+            | Ebreak { .. }
+            | Wfi {}
+            | Uret {}
+            | Sret {}
+            | Mret {}
+            | Csrrw { .. }
+            | Csrrs { .. }
+            | Csrrc { .. }
+            | Csrrwi { .. }
+            | Csrrsi { .. }
+            | Csrrci { .. } => Format::I,
+
+            Sb { .. } | Sh { .. } | Sw { .. } | Sd { .. } => Format::S,
+
+            Beq { .. } | Bne { .. } | Blt { .. } | Bge { .. } | Bltu { .. } | Bgeu { .. } => {
+                Format::B
+            }
+
+            Lui { .. } | Auipc { .. } => Format::U,
+
+            Jal { .. } => Format::J,
+        };
+
+        Some(format)
+    }
+
+    /// The ISA extension that this instruction belongs to
+    pub fn extension(&self) -> Extension {
+        use Instr::*;
+
+        match *self {
+            Csrrw { .. }
+            | Csrrs { .. }
+            | Csrrc { .. }
+            | Csrrwi { .. }
+            | Csrrsi { .. }
+            | Csrrci { .. } => Extension::Zicsr,
+
+            FenceI { .. } => Extension::Zifencei,
+
+            Wfi {} | Uret {} | Sret {} | Mret {} => Extension::Privileged,
+
+            Custom { .. } => Extension::Custom,
+
+            _ => Extension::I,
+        }
+    }
+
+    /// How this instruction affects which instruction runs next
+    pub fn control_flow(&self) -> ControlFlow {
+        use Instr::*;
+
+        match *self {
+            Beq { .. } | Bne { .. } | Blt { .. } | Bge { .. } | Bltu { .. } | Bgeu { .. } => {
+                ControlFlow::ConditionalBranch
+            }
+
+            Jal { rd: Reg::Zero, .. } => ControlFlow::DirectJump,
+            Jal { .. } => ControlFlow::Call,
+
+            // `ra` and `t0` are the link registers in the calling convention
+            Jalr {
+                rd: Reg::Zero,
+                rs1: Reg::Ra,
+                ..
+            }
+            | Jalr {
+                rd: Reg::Zero,
+                rs1: Reg::T0,
+                ..
+            } => ControlFlow::Return,
+            Jalr { rd: Reg::Zero, .. } => ControlFlow::IndirectJump,
+            Jalr { .. } => ControlFlow::Call,
+
+            // Executing an illegal instruction raises an exception too
+            Ecall { .. } | Ebreak { .. } | Illegal => ControlFlow::Trap,
+
+            Uret {} | Sret {} | Mret {} => ControlFlow::TrapReturn,
+
+            _ => ControlFlow::Sequential,
+        }
+    }
+
+    /// Whether execution can continue to the next instruction after this one
+    ///
+    /// Conditional branches and calls (which return) can continue, but jumps,
+    /// returns, and trap returns never do.
+    pub fn falls_through(&self) -> bool {
+        use ControlFlow::*;
+
+        match self.control_flow() {
+            Sequential | ConditionalBranch | Call => true,
+            DirectJump | Return | IndirectJump | TrapReturn => false,
+
+            // Handlers for `ecall` and `ebreak` usually resume right after them
+            Trap => *self != Instr::Illegal,
+        }
+    }
+
+    /// The memory read or written by this instruction, if any
+    pub fn mem_access(&self) -> Option<MemAccess> {
+        use Instr::*;
+        use MemOp::*;
+
+        let (op, width, signed, base, offset) = match *self {
+            Lb { rs1, imm, .. } => (Load, 1, true, rs1, imm),
+            Lh { rs1, imm, .. } => (Load, 2, true, rs1, imm),
+            Lw { rs1, imm, .. } => (Load, 4, true, rs1, imm),
+            Ld { rs1, imm, .. } => (Load, 8, true, rs1, imm),
+            Lbu { rs1, imm, .. } => (Load, 1, false, rs1, imm as i32),
+            Lhu { rs1, imm, .. } => (Load, 2, false, rs1, imm as i32),
+            Lwu { rs1, imm, .. } => (Load, 4, false, rs1, imm as i32),
+
+            Sb { rs1, imm, .. } => (Store, 1, false, rs1, imm),
+            Sh { rs1, imm, .. } => (Store, 2, false, rs1, imm),
+            Sw { rs1, imm, .. } => (Store, 4, false, rs1, imm),
+            Sd { rs1, imm, .. } => (Store, 8, false, rs1, imm),
+
+            _ => return None,
+        };
+
+        Some(MemAccess {
+            op,
+            width,
+            signed,
+            base,
+            offset,
+        })
+    }
+
+    /// The CSR accessed by this instruction, if it is a `Zicsr` instruction
+    pub fn csr_access(&self) -> Option<CsrAccess> {
+        use Instr::*;
+
+        let (csr, read, write) = match *self {
+            Csrrw { rd, csr, .. } => (csr, rd != Reg::Zero, true),
+            Csrrwi { rd, csr, .. } => (csr, rd != Reg::Zero, true),
+            Csrrs { rs1, csr, .. } | Csrrc { rs1, csr, .. } => (csr, true, rs1 != Reg::Zero),
+            Csrrsi { src, csr, .. } | Csrrci { src, csr, .. } => (csr, true, src != 0),
+
+            _ => return None,
+        };
+
+        Some(CsrAccess { csr, read, write })
+    }
+}
+
 /// An error when a register is referenced out of bounds
 ///
 /// A `Reg` value can be constructed from a `u8` or a `u32`, whichever is more
@@ -710,40 +1404,10 @@ pub struct RegIndexError {
 impl TryFrom<u32> for Reg {
     type Error = RegIndexError;
     fn try_from(idx: u32) -> Result<Reg, Self::Error> {
-        match idx {
-            0 => Ok(Reg::Zero),
-            1 => Ok(Reg::Ra),
-            2 => Ok(Reg::Sp),
-            3 => Ok(Reg::Gp),
-            4 => Ok(Reg::Tp),
-            5 => Ok(Reg::T0),
-            6 => Ok(Reg::T1),
-            7 => Ok(Reg::T2),
-            8 => Ok(Reg::S0),
-            9 => Ok(Reg::S1),
-            10 => Ok(Reg::A0),
-            11 => Ok(Reg::A1),
-            12 => Ok(Reg::A2),
-            13 => Ok(Reg::A3),
-            14 => Ok(Reg::A4),
-            15 => Ok(Reg::A5),
-            16 => Ok(Reg::A6),
-            17 => Ok(Reg::A7),
-            18 => Ok(Reg::S2),
-            19 => Ok(Reg::S3),
-            20 => Ok(Reg::S4),
-            21 => Ok(Reg::S5),
-            22 => Ok(Reg::S6),
-            23 => Ok(Reg::S7),
-            24 => Ok(Reg::S8),
-            25 => Ok(Reg::S9),
-            26 => Ok(Reg::S10),
-            27 => Ok(Reg::S11),
-            28 => Ok(Reg::T3),
-            29 => Ok(Reg::T4),
-            30 => Ok(Reg::T5),
-            31 => Ok(Reg::T6),
-            _ => Err(RegIndexError { idx }),
+        if idx < 32 {
+            Ok(Reg::from_u5(idx))
+        } else {
+            Err(RegIndexError { idx })
         }
     }
 }
@@ -754,3 +1418,345 @@ impl TryFrom<u8> for Reg {
         Reg::try_from(idx as u32)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::decode::decode_opcode;
+
+    use Instr::*;
+    use Reg::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    fn regs(regs: &[Reg]) -> RegSet {
+        regs.iter().cloned().collect()
+    }
+
+    #[test]
+    fn check_reg_from_u5() {
+        for idx in 0..32 {
+            let reg = Reg::from_u5(idx);
+            assert_eq!(reg as u32, idx);
+            assert_eq!(Reg::try_from(idx), Ok(reg));
+        }
+        assert!(Reg::try_from(32_u32).is_err());
+
+        // Only the low 5 bits are used
+        assert_eq!(Reg::from_u5(0xffff_ffea), A0);
+    }
+
+    #[test]
+    fn check_regset() {
+        let mut set = regs(&[A0, Zero, T6]);
+        assert_eq!(set.len(), 3);
+        assert!(set.contains(T6));
+        assert!(!set.contains(A1));
+
+        set.remove(Zero);
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![A0, T6]);
+        assert_eq!(set.union(regs(&[Sp])), regs(&[Sp, A0, T6]));
+
+        assert!(RegSet::new().is_empty());
+    }
+
+    #[test]
+    fn check_reads_and_writes() {
+        let add = Add {
+            rd: A0,
+            rs1: A1,
+            rs2: A2,
+        };
+        assert_eq!(add.reads(), regs(&[A1, A2]));
+        assert_eq!(add.writes(), regs(&[A0]));
+
+        let sw = Sw {
+            rs1: Sp,
+            rs2: Ra,
+            imm: 12,
+        };
+        assert_eq!(sw.reads(), regs(&[Sp, Ra]));
+        assert_eq!(sw.writes(), regs(&[]));
+
+        let call = Jal { rd: Ra, imm: 64 };
+        assert_eq!(call.reads(), regs(&[]));
+        assert_eq!(call.writes(), regs(&[Ra]));
+
+        let csrr = Csrrs {
+            rd: A0,
+            rs1: Zero,
+            csr: 0x342,
+        };
+        assert_eq!(csrr.reads(), regs(&[]));
+        assert_eq!(csrr.writes(), regs(&[A0]));
+    }
+
+    #[test]
+    fn check_zero_reads_are_left_out() {
+        let li = Addi {
+            rd: A0,
+            rs1: Zero,
+            imm: 5,
+        };
+        assert_eq!(li.reads(), regs(&[]));
+
+        let beqz = Beq {
+            rs1: A0,
+            rs2: Zero,
+            imm: 8,
+        };
+        assert_eq!(beqz.reads(), regs(&[A0]));
+
+        let csrw = Csrrw {
+            rd: Zero,
+            rs1: Zero,
+            csr: 0x340,
+        };
+        assert_eq!(csrw.reads(), regs(&[]));
+        assert_eq!(csrw.writes(), regs(&[]));
+    }
+
+    #[test]
+    fn check_zero_writes_are_discarded() {
+        let j = Jal { rd: Zero, imm: -4 };
+        assert_eq!(j.writes(), regs(&[]));
+
+        let ret = Jalr {
+            rd: Zero,
+            rs1: Ra,
+            imm: 0,
+        };
+        assert_eq!(ret.reads(), regs(&[Ra]));
+        assert_eq!(ret.writes(), regs(&[]));
+    }
+
+    #[test]
+    fn check_mem_access() {
+        let lbu = Lbu {
+            rd: A0,
+            rs1: A1,
+            imm: 4,
+        };
+        assert_eq!(
+            lbu.mem_access(),
+            Some(MemAccess {
+                op: MemOp::Load,
+                width: 1,
+                signed: false,
+                base: A1,
+                offset: 4,
+            })
+        );
+
+        let lh = Lh {
+            rd: A0,
+            rs1: Sp,
+            imm: -2,
+        };
+        assert_eq!(
+            lh.mem_access(),
+            Some(MemAccess {
+                op: MemOp::Load,
+                width: 2,
+                signed: true,
+                base: Sp,
+                offset: -2,
+            })
+        );
+
+        let sw = Sw {
+            rs1: Sp,
+            rs2: Ra,
+            imm: 12,
+        };
+        assert_eq!(
+            sw.mem_access(),
+            Some(MemAccess {
+                op: MemOp::Store,
+                width: 4,
+                signed: false,
+                base: Sp,
+                offset: 12,
+            })
+        );
+
+        assert_eq!(
+            Addi {
+                rd: A0,
+                rs1: A0,
+                imm: 1
+            }
+            .mem_access(),
+            None
+        );
+    }
+
+    #[test]
+    fn check_csr_access() {
+        let access = |instr: Instr| instr.csr_access().map(|a| (a.csr, a.read, a.write));
+
+        // csrr a0, mcause
+        let csrr = Csrrs {
+            rd: A0,
+            rs1: Zero,
+            csr: 0x342,
+        };
+        assert_eq!(access(csrr), Some((0x342, true, false)));
+
+        // csrw mtvec, t0
+        let csrw = Csrrw {
+            rd: Zero,
+            rs1: T0,
+            csr: 0x305,
+        };
+        assert_eq!(access(csrw), Some((0x305, false, true)));
+
+        // csrsi mstatus, 8
+        let csrsi = Csrrsi {
+            rd: Zero,
+            src: 8,
+            csr: 0x300,
+        };
+        assert_eq!(access(csrsi), Some((0x300, true, true)));
+
+        let args = |instr: Instr| -> Vec<String> {
+            instr.args().iter().map(|arg| arg.to_string()).collect()
+        };
+        assert_eq!(args(csrr), vec!["a0", "mcause", "zero"]);
+        assert_eq!(args(csrsi), vec!["zero", "mstatus", "8"]);
+        assert_eq!(
+            args(Csrrw {
+                rd: A1,
+                rs1: A0,
+                csr: 0x7c0
+            }),
+            vec!["a1", "0x7c0", "a0"]
+        );
+
+        assert_eq!(
+            access(Ecall {
+                rd: Zero,
+                rs1: Zero
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn check_shift_and_compare_args() {
+        let args = |word: u32| -> Vec<String> {
+            let instr = decode_opcode(word).unwrap();
+            instr.args().iter().map(|arg| arg.to_string()).collect()
+        };
+
+        assert_eq!(args(0x0015_9513), vec!["a0", "a1", "1"]); // slli a0, a1, 1
+        assert_eq!(args(0x41f5_d513), vec!["a0", "a1", "31"]); // srai a0, a1, 31
+        assert_eq!(args(0x00c5_d533), vec!["a0", "a1", "a2"]); // srl a0, a1, a2
+        assert_eq!(args(0xffb5_a513), vec!["a0", "a1", "-5"]); // slti a0, a1, -5
+        assert_eq!(args(0x00c5_b533), vec!["a0", "a1", "a2"]); // sltu a0, a1, a2
+    }
+
+    #[test]
+    fn check_args_without_alloc() {
+        let mut args = Args::new();
+        assert!(args.is_empty());
+        args.push(A0.into()).unwrap();
+        args.push(Arg::Csr(0x342)).unwrap();
+        args.push(Arg::Csr(0x7c0)).unwrap();
+        assert_eq!(
+            args,
+            [Arg::Register(A0), Arg::Csr(0x342), Arg::Csr(0x7c0)].into()
+        );
+        assert_eq!(args.len(), 3);
+
+        let names: Vec<String> = args.into_iter().map(|arg| arg.to_string()).collect();
+        assert_eq!(names, ["a0", "mcause", "0x7c0"]);
+
+        // fence iorw, w
+        let fence = decode_opcode(0x0f10_000f).unwrap();
+        assert_eq!(
+            fence.args().as_slice(),
+            [Arg::Special("iorw"), Arg::Special("w")]
+        );
+    }
+
+    #[test]
+    fn check_args_overflow() {
+        let mut args: Args = [Arg::UnsignedImm(0); Args::MAX + 1].into();
+        assert_eq!(args.len(), Args::MAX);
+
+        assert_eq!(args.push(Arg::Csr(0x342)), Err(Arg::Csr(0x342)));
+        assert_eq!(args.len(), Args::MAX);
+    }
+
+    #[test]
+    fn check_classification() {
+        let ret = Jalr {
+            rd: Zero,
+            rs1: Ra,
+            imm: 0,
+        };
+        assert_eq!(ret.format(), Some(Format::I));
+        assert_eq!(ret.extension(), Extension::I);
+        assert_eq!(ret.control_flow(), ControlFlow::Return);
+        assert!(!ret.falls_through());
+
+        let jr = Jalr {
+            rd: Zero,
+            rs1: A0,
+            imm: 0,
+        };
+        assert_eq!(jr.control_flow(), ControlFlow::IndirectJump);
+
+        let jalr = Jalr {
+            rd: Ra,
+            rs1: A0,
+            imm: 0,
+        };
+        assert_eq!(jalr.control_flow(), ControlFlow::Call);
+        assert!(jalr.falls_through());
+
+        let j = Jal { rd: Zero, imm: -8 };
+        assert_eq!(j.format(), Some(Format::J));
+        assert_eq!(j.control_flow(), ControlFlow::DirectJump);
+
+        let bne = Bne {
+            rs1: A0,
+            rs2: A1,
+            imm: 16,
+        };
+        assert_eq!(bne.format(), Some(Format::B));
+        assert_eq!(bne.control_flow(), ControlFlow::ConditionalBranch);
+        assert!(bne.falls_through());
+
+        let sw = Sw {
+            rs1: Sp,
+            rs2: Ra,
+            imm: 12,
+        };
+        assert_eq!(sw.format(), Some(Format::S));
+        assert_eq!(sw.control_flow(), ControlFlow::Sequential);
+
+        let csrw = Csrrw {
+            rd: Zero,
+            rs1: T0,
+            csr: 0x305,
+        };
+        assert_eq!(csrw.extension(), Extension::Zicsr);
+
+        assert_eq!(Mret {}.extension(), Extension::Privileged);
+        assert_eq!(Mret {}.control_flow(), ControlFlow::TrapReturn);
+
+        let ecall = Ecall {
+            rd: Zero,
+            rs1: Zero,
+        };
+        assert_eq!(ecall.control_flow(), ControlFlow::Trap);
+        assert!(ecall.falls_through());
+        assert!(!Illegal.falls_through());
+
+        assert_eq!(Lui { rd: A0, imm: 1 }.format(), Some(Format::U));
+        assert_eq!(Illegal.format(), None);
+    }
+}
@@ -1,15 +1,39 @@
+//! Decoding works without `std`, and without allocating, so that firmware can
+//! decode the instruction that trapped. Everything else needs the `std`
+//! feature, which is on by default.
+
+// Unit tests always have `std`, for the test harness
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "std")]
+pub mod callgraph;
 pub mod csr;
+pub mod custom;
+#[cfg(feature = "std")]
 pub mod dis;
+#[cfg(feature = "std")]
+pub mod emu;
 pub mod instr;
+#[cfg(feature = "std")]
+pub mod lint;
+#[cfg(feature = "std")]
+pub mod listing;
+pub mod opcodes;
+#[cfg(feature = "std")]
+pub mod stack;
+#[cfg(feature = "std")]
+pub mod xref;
 
 mod decode;
 
 // TODO: Add an error type
+#[cfg(feature = "std")]
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
 pub mod prelude {
     pub use crate::decode::*;
     pub use crate::instr::*;
 
+    #[cfg(feature = "std")]
     pub use crate::Result;
 }
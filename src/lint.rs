@@ -0,0 +1,229 @@
+//! Static checks for instructions that can't work the way they were written
+//!
+//! Each check needs to know what privilege mode code will run in. Without
+//! more information, code is assumed to run in machine mode, except for
+//! sections named like user code (e.g. `.text.user`).
+
+use std::fmt;
+
+use crate::csr::{self, Csr, Mode};
+use crate::dis::Disassembly;
+use crate::prelude::*;
+
+/// A problem with a single instruction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lint {
+    /// Address of the instruction
+    pub addr: u32,
+
+    /// The instruction itself
+    pub instr: Instr,
+
+    pub kind: LintKind,
+}
+
+/// What is wrong with an instruction
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LintKind {
+    /// Code running in `mode` accesses a CSR that needs at least `required`
+    CsrPrivilege {
+        csr: u16,
+        mode: Mode,
+        required: Mode,
+    },
+
+    /// A read-only CSR is written
+    CsrReadOnly { csr: u16 },
+
+    /// A CSR that only debug mode can access is accessed
+    CsrDebugOnly { csr: u16 },
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:08x}: {}: ", self.addr, self.instr.name())?;
+
+        match self.kind {
+            LintKind::CsrPrivilege {
+                csr,
+                mode,
+                required,
+            } => write!(
+                f,
+                "{} mode code accesses CSR {}, which needs {} mode",
+                mode,
+                csr::name_or_number(csr),
+                required
+            ),
+            LintKind::CsrReadOnly { csr } => {
+                write!(f, "writes read-only CSR {}", csr::name_or_number(csr))
+            }
+            LintKind::CsrDebugOnly { csr } => write!(
+                f,
+                "accesses CSR {}, which needs debug mode",
+                csr::name_or_number(csr)
+            ),
+        }
+    }
+}
+
+/// Guess the mode of code in the section named `name`
+///
+/// Sections with "user" in their name hold user-mode code. Everything else
+/// runs in machine mode.
+pub fn section_mode(name: &str) -> Mode {
+    if name.contains("user") {
+        Mode::User
+    } else {
+        Mode::Machine
+    }
+}
+
+/// Check every CSR access in `dis`
+///
+/// `mode_of` gives the mode that the instruction at an address runs in.
+pub fn check_csr_accesses(dis: &Disassembly, mode_of: impl Fn(u32) -> Mode) -> Vec<Lint> {
+    let mut lints = vec![];
+
+    for entry in dis.disassembly() {
+        let (instr, access) = match entry.o_instr {
+            Some(instr) => match instr.csr_access() {
+                Some(access) => (instr, access),
+                None => continue,
+            },
+            None => continue,
+        };
+
+        // The table is what the simulator enforces, and the number only
+        // tells us about CSRs that aren't in it
+        let privilage = match csr::by_number(access.csr) {
+            Some((_name, csr)) => csr.privilage(),
+            None => Csr::from_number_conventions(access.csr).privilage(),
+        };
+
+        let mode = mode_of(entry.addr);
+        let kind = if privilage.allows(mode, access.write) {
+            continue;
+        } else if privilage == csr::Drw {
+            LintKind::CsrDebugOnly { csr: access.csr }
+        } else if mode < privilage.mode() {
+            LintKind::CsrPrivilege {
+                csr: access.csr,
+                mode,
+                required: privilage.mode(),
+            }
+        } else {
+            LintKind::CsrReadOnly { csr: access.csr }
+        };
+
+        lints.push(Lint {
+            addr: entry.addr,
+            instr,
+            kind,
+        });
+    }
+
+    lints
+}
+
+/// Run every check on `dis`, using the section names to decide the mode of
+/// each instruction
+///
+/// See [`section_mode`].
+pub fn lint(dis: &Disassembly) -> Vec<Lint> {
+    check_csr_accesses(dis, |addr| {
+        dis.section_containing(addr)
+            .map_or(Mode::Machine, |section| section_mode(&section.name))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    #[test]
+    fn check_csr_lints() {
+        #[rustfmt::skip]
+        let dis = Disassembly::from_words(0x0, &[
+            // machine:
+            0x3000_2573, // csrr    a0, mstatus
+            0xc000_1073, // csrw    cycle, zero
+            // user:
+            0xc000_25f3, // rdcycle a1
+            0x3000_2573, // csrr    a0, mstatus
+            0x1000_2573, // csrr    a0, sstatus
+            0xc000_15f3, // csrrw   a1, cycle, zero
+        ]);
+
+        let mode_of = |addr: u32| {
+            if addr < 0x8 {
+                Mode::Machine
+            } else {
+                Mode::User
+            }
+        };
+        let lints: Vec<(u32, LintKind)> = check_csr_accesses(&dis, mode_of)
+            .into_iter()
+            .map(|lint| (lint.addr, lint.kind))
+            .collect();
+
+        assert_eq!(
+            lints,
+            vec![
+                (0x4, LintKind::CsrReadOnly { csr: 0xc00 }),
+                (
+                    0xc,
+                    LintKind::CsrPrivilege {
+                        csr: 0x300,
+                        mode: Mode::User,
+                        required: Mode::Machine,
+                    }
+                ),
+                (
+                    0x10,
+                    LintKind::CsrPrivilege {
+                        csr: 0x100,
+                        mode: Mode::User,
+                        required: Mode::Supervisor,
+                    }
+                ),
+                (0x14, LintKind::CsrReadOnly { csr: 0xc00 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_debug_csr_lints() {
+        #[rustfmt::skip]
+        let dis = Disassembly::from_words(0x0, &[
+            0x7b00_2573, // csrr    a0, dcsr
+            0x7b20_2573, // csrr    a0, dscratch0
+            0x7bf0_2573, // csrr    a0, 0x7bf
+        ]);
+
+        // Even machine mode can't access these, just like in the simulator
+        let lints: Vec<(u32, LintKind)> = check_csr_accesses(&dis, |_addr| Mode::Machine)
+            .into_iter()
+            .map(|lint| (lint.addr, lint.kind))
+            .collect();
+        assert_eq!(
+            lints,
+            vec![
+                (0x0, LintKind::CsrDebugOnly { csr: 0x7b0 }),
+                (0x4, LintKind::CsrDebugOnly { csr: 0x7b2 }),
+                (0x8, LintKind::CsrDebugOnly { csr: 0x7bf }),
+            ]
+        );
+
+        assert!(!crate::emu::CsrFile::new().allows(0x7b0, false));
+
+        let lint = &check_csr_accesses(&dis, |_addr| Mode::Machine)[0];
+        assert_eq!(
+            lint.to_string(),
+            "00000000: csrrs: accesses CSR dcsr, which needs debug mode"
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Listings of a disassembly in the format of `llvm-objdump -d`
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::dis::{Disassembly, Entry};
+
+/// Extra text for a single instruction in a listing
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Annotation {
+    /// Written before the instruction's address
+    ///
+    /// This should be the same width for every instruction, so that the
+    /// listing stays aligned.
+    pub margin: String,
+
+    /// Written as a comment after the instruction
+    pub comment: Option<String>,
+}
+
+impl Disassembly {
+    /// Write every instruction that `filter` accepts as a listing
+    ///
+    /// Each section starts with a header, and labels are followed by the
+    /// instructions that refer to them. `annotate` adds a margin and comment
+    /// to each instruction.
+    ///
+    /// Returns the line that each instruction was written on, counting from 1.
+    pub fn write_listing(
+        &self,
+        out: &mut dyn Write,
+        filter: impl Fn(&Entry) -> bool,
+        annotate: impl Fn(&Entry) -> Annotation,
+    ) -> io::Result<BTreeMap<u32, usize>> {
+        let xrefs = self.all_xrefs();
+
+        let mut out = LineCounter { out, lines: 0 };
+        let mut lines = BTreeMap::new();
+
+        let mut section: Option<&str> = None;
+
+        for entry in self.disassembly().filter(|entry| filter(entry)) {
+            let entry_section = self
+                .section_containing(entry.addr)
+                .map_or(".text", |section| section.name.as_str());
+            if section != Some(entry_section) {
+                writeln!(out, "\nDisassembly of section {}:", entry_section)?;
+                section = Some(entry_section);
+            }
+
+            if !entry.labels.is_empty() {
+                // Add a blank line to separate sections with a label
+                writeln!(out)?;
+
+                for label in &entry.labels {
+                    // Print the entire address, zero-padding included
+                    writeln!(out, "{addr:08x} {label}:", addr = entry.addr, label = label)?;
+                }
+
+                // Show where this label is used from
+                for xref in xrefs.get(&entry.addr).into_iter().flatten() {
+                    writeln!(out, "; XREF from {:#010x} ({})", xref.from, xref.kind)?;
+                }
+            }
+
+            let annotation = annotate(entry);
+            lines.insert(entry.addr, out.lines + 1);
+
+            // Address of instruction
+            write!(out, "{}{:8x}: ", annotation.margin, entry.addr)?;
+
+            // Raw bytes of instruction
+            for byte in entry.bytes.iter() {
+                write!(out, "{:02x} ", byte)?;
+            }
+
+            // Spacing
+            write!(out, "{:17}\t", "")?;
+
+            // Instruction as text
+            if let Some(instr) = entry.o_instr {
+                // First, print the instruction name
+                write!(out, "{}", instr.name())?;
+
+                // Then, follow with any args, comma separated.
+                let args = instr.args();
+
+                let mut iter = args.iter();
+                if let Some(arg) = iter.next() {
+                    // No comma preceeding the first item
+                    write!(out, "\t{}", arg)?;
+
+                    for arg in iter {
+                        write!(out, ", {}", arg)?;
+                    }
+                }
+            } else {
+                write!(out, "???")?;
+            }
+
+            if let Some(comment) = &annotation.comment {
+                write!(out, "\t; {}", comment)?;
+            }
+
+            // Always end the entry with a newline
+            writeln!(out)?;
+        }
+
+        Ok(lines)
+    }
+}
+
+/// Counts the lines written through it
+struct LineCounter<'a> {
+    out: &'a mut dyn Write,
+    lines: usize,
+}
+
+impl Write for LineCounter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.out.write(buf)?;
+        self.lines += buf[..n].iter().filter(|&&b| b == b'\n').count();
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::prelude::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    /// Words and how `llvm-objdump -M no-aliases` shows them, from
+    /// `scripts/generate-objdump-corpus.sh`
+    const CORPUS: &str = include_str!("../testdata/objdump-corpus.txt");
+
+    /// The instruction text of `word` in a listing, as if it were at address 0
+    fn listing_text(word: u32) -> String {
+        let dis = Disassembly::from_words(0, &[word]);
+        let mut listing = vec![];
+        dis.write_listing(&mut listing, |_| true, |_| Annotation::default())
+            .unwrap();
+        let listing = String::from_utf8(listing).unwrap();
+
+        // The text follows the tab after the raw bytes
+        let line = listing.lines().last().unwrap();
+        line.split_once('\t').unwrap().1.to_string()
+    }
+
+    /// Rewrite llvm-objdump's text in the few ways that `dis` means to differ
+    ///
+    /// Branch and jump targets are offsets rather than addresses, and words
+    /// that aren't instructions are shown as "???".
+    fn normalize(word: u32, text: &str) -> String {
+        if text == "<unknown>" {
+            return "???".to_string();
+        }
+
+        let is_jump = decode_opcode(word).is_some_and(|instr| {
+            matches!(
+                instr.control_flow(),
+                ControlFlow::ConditionalBranch | ControlFlow::DirectJump
+            ) || matches!(instr, Instr::Jal { .. })
+        });
+        if is_jump {
+            let (operands, target) = text.rsplit_once(", ").unwrap();
+            let target = u32::from_str_radix(target.trim_start_matches("0x"), 16).unwrap();
+            return format!("{}, {}", operands, target as i32);
+        }
+
+        text.to_string()
+    }
+
+    #[test]
+    fn check_objdump_corpus() {
+        let mut checked = 0;
+        let mut mismatches = vec![];
+        for line in CORPUS.lines().filter(|line| !line.starts_with('#')) {
+            let (word, text) = line.split_once('\t').unwrap();
+            let word = u32::from_str_radix(word.trim_start_matches("0x"), 16).unwrap();
+
+            let expected = normalize(word, text);
+            let actual = listing_text(word);
+            if actual != expected {
+                mismatches.push(format!("{:#010x}: {:?} != {:?}", word, actual, expected));
+            }
+            checked += 1;
+        }
+
+        assert!(checked > 1000, "only {} words in the corpus", checked);
+        assert_eq!(mismatches, Vec::<String>::new());
+    }
+}
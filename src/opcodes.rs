@@ -0,0 +1,201 @@
+//! Instruction encodings from the official riscv-opcodes definitions
+//!
+//! The tables are generated by `build.rs` from the files in `riscv-opcodes/`,
+//! so that decoding matches the spec exactly and anything that encodes
+//! instructions can use the same definitions.
+
+use crate::decode::Bits;
+
+/// A field of an encoding that holds one of the instruction's arguments
+///
+/// These are named after the fields in riscv-opcodes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Field {
+    Rd,
+    Rs1,
+    Rs2,
+    /// The upper immediate of `lui` and `auipc`
+    Imm20,
+    /// The scrambled offset of `jal`
+    Jimm20,
+    /// The immediate of I-type instructions
+    Imm12,
+    /// The upper 7 bits of a store's offset
+    Imm12hi,
+    /// The lower 5 bits of a store's offset
+    Imm12lo,
+    /// The upper bits of a branch's scrambled offset
+    Bimm12hi,
+    /// The lower bits of a branch's scrambled offset
+    Bimm12lo,
+    /// A 5-bit shift amount
+    Shamtw,
+    /// A 6-bit shift amount, for RV64
+    Shamt,
+    /// The fence mode of `fence`
+    Fm,
+    /// The predecessor set of `fence`
+    Pred,
+    /// The successor set of `fence`
+    Succ,
+    /// The immediate source of the `csrr*i` instructions
+    Zimm,
+    Csr,
+}
+
+impl Field {
+    /// The highest and lowest bits of the field, inclusive
+    pub fn bits(self) -> (u8, u8) {
+        use Field::*;
+
+        match self {
+            Rd => (11, 7),
+            Rs1 => (19, 15),
+            Rs2 => (24, 20),
+            Imm20 | Jimm20 => (31, 12),
+            Imm12 => (31, 20),
+            Imm12hi | Bimm12hi => (31, 25),
+            Imm12lo | Bimm12lo => (11, 7),
+            Shamtw => (24, 20),
+            Shamt => (25, 20),
+            Fm => (31, 28),
+            Pred => (27, 24),
+            Succ => (23, 20),
+            Zimm => (19, 15),
+            Csr => (31, 20),
+        }
+    }
+
+    /// The bits of a word that hold this field
+    pub fn mask(self) -> u32 {
+        let (hi, lo) = self.bits();
+        u32::MAX.bits(hi, lo) << lo
+    }
+
+    /// The raw value of this field in `word`, shifted down to bit 0
+    pub fn extract(self, word: u32) -> u32 {
+        let (hi, lo) = self.bits();
+        word.bits(hi, lo)
+    }
+}
+
+/// How an instruction is encoded
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Encoding {
+    /// The name used by riscv-opcodes, like `fence.i`
+    pub name: &'static str,
+
+    /// The same name, as something cheaper to match on
+    pub mnemonic: Mnemonic,
+
+    /// The riscv-opcodes file that defines it, like `rv_zicsr`
+    pub extension: &'static str,
+
+    /// The bits that are the same in every word encoding this instruction
+    pub mask: u32,
+
+    /// The value of the bits in `mask`
+    pub match_bits: u32,
+
+    /// The fields that hold arguments, in the order riscv-opcodes lists them
+    pub fields: &'static [Field],
+}
+
+impl Encoding {
+    /// Whether `word` encodes this instruction
+    pub fn matches(&self, word: u32) -> bool {
+        word & self.mask == self.match_bits
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/opcodes.rs"));
+
+/// The encoding that `word` matches, if any
+///
+/// Only the few encodings with the same opcode and funct3 as `word` are
+/// checked.
+#[inline]
+pub fn find(word: u32) -> Option<&'static Encoding> {
+    let index = ((word >> 5) & 0x380) | (word & 0x7f);
+    DISPATCH[index as usize]
+        .iter()
+        .find(|encoding| encoding.matches(word))
+        .copied()
+}
+
+/// The encoding of the instruction named `name`, like `addi`
+pub fn by_name(name: &str) -> Option<&'static Encoding> {
+    ENCODINGS.iter().find(|encoding| encoding.name == name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    #[test]
+    fn check_encodings() {
+        for encoding in ENCODINGS {
+            assert_eq!(encoding.match_bits & !encoding.mask, 0, "{}", encoding.name);
+
+            // Every bit is either fixed or in exactly one field
+            let mut covered = encoding.mask;
+            for field in encoding.fields {
+                assert_eq!(covered & field.mask(), 0, "{} {:?}", encoding.name, field);
+                covered |= field.mask();
+            }
+            assert_eq!(covered, u32::MAX, "{}", encoding.name);
+
+            assert_eq!(find(encoding.match_bits), Some(encoding));
+        }
+    }
+
+    #[test]
+    fn check_find_matches_every_encoding() {
+        // The dispatch table must give the same answer as trying them all
+        let mut random: u32 = 1;
+        for _ in 0..10_000 {
+            random ^= random << 13;
+            random ^= random >> 17;
+            random ^= random << 5;
+
+            // Random words rarely have an opcode that's in use, so try each
+            for encoding in ENCODINGS {
+                let word = (random & !0x7f) | (encoding.match_bits & 0x7f);
+                let slow = ENCODINGS.iter().find(|encoding| encoding.matches(word));
+                assert_eq!(find(word), slow, "{:#010x}", word);
+            }
+        }
+    }
+
+    #[test]
+    fn check_by_name() {
+        let addi = by_name("addi").unwrap();
+        assert_eq!(addi.extension, "rv_i");
+        assert_eq!(addi.mnemonic, Mnemonic::Addi);
+        assert_eq!((addi.mask, addi.match_bits), (0x0000_707f, 0x0000_0013));
+        assert_eq!(addi.fields, &[Field::Rd, Field::Rs1, Field::Imm12]);
+
+        // The RV32 encoding, where shift amounts only have 5 bits
+        let slli = by_name("slli").unwrap();
+        assert_eq!(slli.extension, "rv32_i");
+        assert_eq!((slli.mask, slli.match_bits), (0xfe00_707f, 0x0000_1013));
+
+        assert_eq!(by_name("fence.i").unwrap().extension, "rv_zifencei");
+        assert_eq!(by_name("fence.i").unwrap().mnemonic, Mnemonic::FenceI);
+        assert_eq!(by_name("fence.tso"), None);
+        assert_eq!(by_name("mul"), None);
+    }
+
+    #[test]
+    fn check_extract() {
+        // csrrs a0, mcause, zero
+        let word = 0x3420_2573;
+        assert_eq!(find(word).unwrap().name, "csrrs");
+        assert_eq!(Field::Rd.extract(word), 10);
+        assert_eq!(Field::Csr.extract(word), 0x342);
+        assert_eq!(Field::Zimm.extract(word), 0);
+    }
+}
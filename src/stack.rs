@@ -0,0 +1,467 @@
+//! Worst-case stack depth analysis
+//!
+//! Each function's frame size is found from the instructions that move `sp`
+//! down, usually a single `addi sp, sp, -N` in its prologue.
+//! Walking the call graph from each root then gives the most stack that can
+//! be in use at once.
+//!
+//! Calls are always charged on top of the caller's frame, even tail calls
+//! which (when made by a compiler) release the frame first. This can only
+//! over-estimate the real depth.
+
+use std::collections::BTreeMap;
+
+use crate::callgraph::{CallGraph, KnownRegs};
+use crate::dis::Disassembly;
+use crate::prelude::*;
+
+/// The stack space that a function allocates for itself
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    /// Bytes allocated by constant adjustments to `sp`
+    pub size: u32,
+
+    /// Address of an instruction that moves `sp` by an amount that isn't known
+    ///
+    /// When this is set, `size` is only a lower bound.
+    pub dynamic: Option<u32>,
+}
+
+/// The deepest the stack can get from some function
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StackDepth {
+    /// At most `bytes` of stack are in use, when following `path`
+    ///
+    /// `path` lists the address of each function along the deepest chain of
+    /// calls, starting with the function that was asked about.
+    Bounded { bytes: u32, path: Vec<u32> },
+
+    /// There is no static bound on the stack depth
+    Unbounded(Unbounded),
+}
+
+/// Why the stack depth from a function cannot be bounded
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Unbounded {
+    /// The functions in `cycle` can call each other forever
+    Recursion { cycle: Vec<u32> },
+
+    /// The instruction at `site` calls through a register we couldn't resolve
+    IndirectCall { site: u32 },
+
+    /// The instruction at `site` calls `target`, which isn't in the disassembly
+    UnknownCallee { site: u32, target: u32 },
+
+    /// The function at `func` moves `sp` by an unknown amount at `site`
+    DynamicFrame { func: u32, site: u32 },
+
+    /// The frames from the function at `func` down add up to more than 4 GiB
+    Overflow { func: u32 },
+}
+
+/// Frame sizes and worst-case stack depths for every function in a program
+pub struct StackAnalysis {
+    graph: CallGraph,
+    frames: BTreeMap<u32, Frame>,
+    depths: BTreeMap<u32, StackDepth>,
+}
+
+impl StackAnalysis {
+    pub fn new(dis: &Disassembly) -> Self {
+        let graph = CallGraph::new(dis);
+
+        let frames: BTreeMap<u32, Frame> = graph
+            .functions()
+            .map(|func| (func.addr, frame_of(dis, func.addr, func.end)))
+            .collect();
+
+        let mut analysis = StackAnalysis {
+            graph,
+            frames,
+            depths: BTreeMap::new(),
+        };
+
+        let addrs: Vec<u32> = analysis.graph.functions().map(|func| func.addr).collect();
+        for addr in addrs {
+            let mut on_stack = vec![];
+            analysis.visit(addr, &mut on_stack);
+        }
+
+        analysis
+    }
+
+    /// The call graph used for this analysis
+    pub fn graph(&self) -> &CallGraph {
+        &self.graph
+    }
+
+    /// The frame allocated by the function that begins at `func`
+    pub fn frame(&self, func: u32) -> Option<Frame> {
+        self.frames.get(&func).cloned()
+    }
+
+    /// The worst-case stack depth when calling the function at `func`
+    pub fn max_depth(&self, func: u32) -> Option<&StackDepth> {
+        self.depths.get(&func)
+    }
+
+    /// The worst-case stack depth from each root of the program
+    ///
+    /// See [`CallGraph::roots`] for what counts as a root.
+    pub fn roots(&self) -> impl Iterator<Item = (u32, &StackDepth)> {
+        self.graph
+            .roots()
+            .iter()
+            .filter_map(move |&root| self.max_depth(root).map(|depth| (root, depth)))
+    }
+
+    /// Depth-first search of the call graph from `addr`
+    ///
+    /// `on_stack` holds the functions currently being visited, so that we can
+    /// tell when a call would recurse.
+    fn visit(&mut self, addr: u32, on_stack: &mut Vec<u32>) -> StackDepth {
+        if let Some(depth) = self.depths.get(&addr) {
+            return depth.clone();
+        }
+
+        if let Some(pos) = on_stack.iter().position(|&a| a == addr) {
+            return StackDepth::Unbounded(Unbounded::Recursion {
+                cycle: on_stack[pos..].to_vec(),
+            });
+        }
+
+        let frame = self.frames[&addr];
+        let calls = self.graph.function(addr).unwrap().calls.clone();
+
+        let mut result = StackDepth::Bounded {
+            bytes: frame.size,
+            path: vec![addr],
+        };
+
+        if let Some(site) = frame.dynamic {
+            result = StackDepth::Unbounded(Unbounded::DynamicFrame { func: addr, site });
+        }
+
+        on_stack.push(addr);
+
+        for call in calls {
+            if let StackDepth::Unbounded(_) = result {
+                break;
+            }
+
+            let target = match call.target {
+                Some(target) => target,
+                None => {
+                    result = StackDepth::Unbounded(Unbounded::IndirectCall { site: call.addr });
+                    break;
+                }
+            };
+
+            // Jumps may land in the middle of a function (e.g. into a shared
+            // epilogue), so charge the whole function that they land in.
+            let callee = match self.graph.function_containing(target) {
+                Some(callee) => callee.addr,
+                None => {
+                    result = StackDepth::Unbounded(Unbounded::UnknownCallee {
+                        site: call.addr,
+                        target,
+                    });
+                    break;
+                }
+            };
+
+            match self.visit(callee, on_stack) {
+                StackDepth::Bounded { bytes, path } => {
+                    if let StackDepth::Bounded {
+                        bytes: worst_bytes,
+                        path: worst_path,
+                    } = &mut result
+                    {
+                        match frame.size.checked_add(bytes) {
+                            Some(bytes) if bytes > *worst_bytes => {
+                                *worst_bytes = bytes;
+                                *worst_path = std::iter::once(addr).chain(path).collect();
+                            }
+                            Some(_) => {}
+                            None => {
+                                result = StackDepth::Unbounded(Unbounded::Overflow { func: addr })
+                            }
+                        }
+                    }
+                }
+                unbounded => result = unbounded,
+            }
+        }
+
+        on_stack.pop();
+
+        self.depths.insert(addr, result.clone());
+        result
+    }
+}
+
+/// Find the frame size of the function spanning `[addr, end)`
+fn frame_of(dis: &Disassembly, addr: u32, end: u32) -> Frame {
+    let mut frame = Frame {
+        size: 0,
+        dynamic: None,
+    };
+
+    // Large frames are allocated with a register, like:
+    //      lui     t0, 1
+    //      sub     sp, sp, t0
+    // so we need to know what constants are in registers.
+    let mut known = KnownRegs::new();
+
+    // The amounts come from the program, so they can overflow. A move of
+    // 2 GiB could be in either direction, and a frame over 4 GiB can't be
+    // real, so both count as moving `sp` by an unknown amount.
+    for entry in (addr..end).step_by(4).filter_map(|addr| dis.get(addr)) {
+        // How far this instruction moves `sp` down, if it does at all
+        let grows: Option<Option<i32>> = match entry.o_instr {
+            Some(Instr::Addi {
+                rd: Reg::Sp,
+                rs1: Reg::Sp,
+                imm,
+            }) => Some(Some(-imm)),
+            Some(Instr::Sub {
+                rd: Reg::Sp,
+                rs1: Reg::Sp,
+                rs2,
+            }) => Some(
+                known
+                    .get(rs2)
+                    .map(|value| value as i32)
+                    .filter(|&bytes| bytes != i32::MIN),
+            ),
+            Some(Instr::Add {
+                rd: Reg::Sp,
+                rs1: Reg::Sp,
+                rs2,
+            }) => known.get(rs2).map(|value| (value as i32).checked_neg()),
+
+            // Anything else that writes `sp` switches stacks or releases
+            // stack, which doesn't add to this function's frame.
+            _ => None,
+        };
+
+        match grows {
+            Some(Some(bytes)) if bytes > 0 => match frame.size.checked_add(bytes as u32) {
+                Some(size) => frame.size = size,
+                None => frame.dynamic = frame.dynamic.or(Some(entry.addr)),
+            },
+            Some(None) => frame.dynamic = frame.dynamic.or(Some(entry.addr)),
+            _ => {}
+        }
+
+        known.step(entry);
+    }
+
+    frame
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    #[test]
+    fn check_nested_calls() {
+        #[rustfmt::skip]
+        let mut dis = Disassembly::from_words(0x8000_0000, &[
+            // _start:
+            0xff01_0113, // addi    sp, sp, -16
+            0x00c0_00ef, // jal     foo
+            0x0180_00ef, // jal     bar
+            0x0000_006f, // j       .
+            // foo:
+            0xfe01_0113, // addi    sp, sp, -32
+            0x00c0_00ef, // jal     bar
+            0x0201_0113, // addi    sp, sp, 32
+            0x0000_8067, // ret
+            // bar:
+            0xff81_0113, // addi    sp, sp, -8
+            0x0081_0113, // addi    sp, sp, 8
+            0x0000_8067, // ret
+        ]);
+        dis.set_entry_point(0x8000_0000);
+
+        let stack = StackAnalysis::new(&dis);
+
+        assert_eq!(stack.frame(0x8000_0000).unwrap().size, 16);
+        assert_eq!(stack.frame(0x8000_0010).unwrap().size, 32);
+        assert_eq!(stack.frame(0x8000_0020).unwrap().size, 8);
+
+        assert_eq!(
+            stack.roots().collect::<Vec<_>>(),
+            vec![(
+                0x8000_0000,
+                &StackDepth::Bounded {
+                    bytes: 16 + 32 + 8,
+                    path: vec![0x8000_0000, 0x8000_0010, 0x8000_0020],
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn check_unbounded() {
+        #[rustfmt::skip]
+        let mut dis = Disassembly::from_words(0x0, &[
+            // _start:
+            0x0000_02b7, // lui     t0, 0
+            0x0282_8293, // addi    t0, t0, 0x28
+            0x3052_9073, // csrw    mtvec, t0
+            0x00c0_00ef, // jal     fact
+            0x0005_00e7, // jalr    a0
+            0x0000_006f, // j       .
+            // fact:
+            0xff01_0113, // addi    sp, sp, -16
+            0xffdf_f0ef, // jal     fact
+            0x0101_0113, // addi    sp, sp, 16
+            0x0000_8067, // ret
+            // handler:
+            0x0000_12b7, // lui     t0, 1
+            0x4051_0133, // sub     sp, sp, t0
+            0x00c0_00ef, // jal     alloca
+            0x0051_0133, // add     sp, sp, t0
+            0x3020_0073, // mret
+            // alloca:
+            0x40a1_0133, // sub     sp, sp, a0
+            0x0000_8067, // ret
+        ]);
+        dis.set_entry_point(0x0);
+
+        let stack = StackAnalysis::new(&dis);
+
+        assert_eq!(
+            stack.frame(0x28),
+            Some(Frame {
+                size: 4096,
+                dynamic: None
+            })
+        );
+        assert_eq!(
+            stack.frame(0x3c),
+            Some(Frame {
+                size: 0,
+                dynamic: Some(0x3c)
+            })
+        );
+
+        assert_eq!(
+            stack.roots().collect::<Vec<_>>(),
+            vec![
+                (
+                    0x0,
+                    &StackDepth::Unbounded(Unbounded::Recursion { cycle: vec![0x18] })
+                ),
+                (
+                    0x28,
+                    &StackDepth::Unbounded(Unbounded::DynamicFrame {
+                        func: 0x3c,
+                        site: 0x3c
+                    })
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_overflow() {
+        #[rustfmt::skip]
+        let mut dis = Disassembly::from_words(0x0, &[
+            0x8000_02b7, // lui     t0, 0x80000
+            0x0051_0133, // add     sp, sp, t0
+            0x0000_006f, // j       .
+        ]);
+        dis.set_entry_point(0x0);
+
+        // 0x8000_0000 can't be negated, and moves `sp` the same either way
+        let stack = StackAnalysis::new(&dis);
+        assert_eq!(
+            stack.frame(0x0),
+            Some(Frame {
+                size: 0,
+                dynamic: Some(0x4)
+            })
+        );
+        assert_eq!(
+            stack.max_depth(0x0),
+            Some(&StackDepth::Unbounded(Unbounded::DynamicFrame {
+                func: 0x0,
+                site: 0x4
+            }))
+        );
+
+        // Frames over 4 GiB can't be real either
+        #[rustfmt::skip]
+        let mut dis = Disassembly::from_words(0x0, &[
+            0x7fff_f2b7, // lui     t0, 0x7ffff
+            0x4051_0133, // sub     sp, sp, t0
+            0x4051_0133, // sub     sp, sp, t0
+            0x4051_0133, // sub     sp, sp, t0
+            0x0000_006f, // j       .
+        ]);
+        dis.set_entry_point(0x0);
+
+        let stack = StackAnalysis::new(&dis);
+        assert_eq!(
+            stack.frame(0x0),
+            Some(Frame {
+                size: 0xffff_e000,
+                dynamic: Some(0xc)
+            })
+        );
+
+        // Nor are stacks over 4 GiB, even when each frame fits
+        #[rustfmt::skip]
+        let mut dis = Disassembly::from_words(0x0, &[
+            // _start:
+            0x7fff_f2b7, // lui     t0, 0x7ffff
+            0x4051_0133, // sub     sp, sp, t0
+            0x4051_0133, // sub     sp, sp, t0
+            0x0080_00ef, // jal     huge
+            0x0000_006f, // j       .
+            // huge:
+            0x7fff_f2b7, // lui     t0, 0x7ffff
+            0x4051_0133, // sub     sp, sp, t0
+            0x0000_8067, // ret
+        ]);
+        dis.set_entry_point(0x0);
+
+        let stack = StackAnalysis::new(&dis);
+        assert_eq!(
+            stack.max_depth(0x14),
+            Some(&StackDepth::Bounded {
+                bytes: 0x7fff_f000,
+                path: vec![0x14]
+            })
+        );
+        assert_eq!(
+            stack.max_depth(0x0),
+            Some(&StackDepth::Unbounded(Unbounded::Overflow { func: 0x0 }))
+        );
+    }
+
+    #[test]
+    fn check_indirect_call() {
+        #[rustfmt::skip]
+        let mut dis = Disassembly::from_words(0x0, &[
+            0x0005_00e7, // jalr    a0
+            0x0000_006f, // j       .
+        ]);
+        dis.set_entry_point(0x0);
+
+        let stack = StackAnalysis::new(&dis);
+
+        assert_eq!(
+            stack.max_depth(0x0),
+            Some(&StackDepth::Unbounded(Unbounded::IndirectCall {
+                site: 0x0
+            }))
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! Cross references between instructions and the addresses they use
+//!
+//! An instruction references an address when it branches or jumps there, or
+//! when it computes the address from a `lui` or `auipc` and then uses it.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::callgraph::KnownRegs;
+use crate::dis::{Disassembly, Entry};
+use crate::prelude::*;
+
+/// How an instruction uses the address that it references
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum XrefKind {
+    /// A conditional branch, like `beq`
+    Branch,
+
+    /// A jump that doesn't link a return address, like `j` or `tail`
+    Jump,
+
+    /// A jump that links a return address, like `call`
+    Call,
+
+    /// The address is computed into a register, like `la`
+    Address,
+
+    /// A load from the address
+    Load,
+
+    /// A store to the address
+    Store,
+}
+
+impl fmt::Display for XrefKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use XrefKind::*;
+
+        let kind = match *self {
+            Branch => "branch",
+            Jump => "jump",
+            Call => "call",
+            Address => "address",
+            Load => "load",
+            Store => "store",
+        };
+
+        write!(f, "{}", kind)
+    }
+}
+
+/// A single reference from the instruction at `from` to the address `to`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Xref {
+    pub from: u32,
+    pub to: u32,
+    pub kind: XrefKind,
+}
+
+impl Disassembly {
+    /// Every instruction that references `addr`, in address order
+    pub fn xrefs(&self, addr: u32) -> Vec<Xref> {
+        self.all_xrefs().remove(&addr).unwrap_or_default()
+    }
+
+    /// Every reference in the program, keyed by the referenced address
+    ///
+    /// References to each address are in the order of the instructions that
+    /// make them.
+    pub fn all_xrefs(&self) -> BTreeMap<u32, Vec<Xref>> {
+        let mut xrefs: BTreeMap<u32, Vec<Xref>> = BTreeMap::new();
+        let mut known = KnownRegs::new();
+
+        for entry in self.disassembly() {
+            // Anything could jump to a label, so registers built before it
+            // can't be trusted when looking at it.
+            if !entry.labels.is_empty() {
+                known.forget_all();
+            }
+
+            if let Some((to, kind)) = xref_of(entry, &known) {
+                xrefs.entry(to).or_default().push(Xref {
+                    from: entry.addr,
+                    to,
+                    kind,
+                });
+            }
+
+            known.step(entry);
+        }
+
+        xrefs
+    }
+}
+
+/// Find the address referenced by `entry`, if there is one
+///
+/// `known` holds the registers with values that were computed before `entry`.
+fn xref_of(entry: &Entry, known: &KnownRegs) -> Option<(u32, XrefKind)> {
+    use Instr::*;
+    use XrefKind::*;
+
+    let pc = entry.addr;
+    let instr = entry.o_instr?;
+
+    // An address computed from a register holding a constant.
+    // `zero` is ignored here: it almost always builds small integers, not addresses.
+    let based = |base: Reg, offset: i32| -> Option<u32> {
+        if base == Reg::Zero {
+            return None;
+        }
+        known.get(base).map(|base| base.wrapping_add(offset as u32))
+    };
+
+    match instr {
+        Beq { imm, .. }
+        | Bne { imm, .. }
+        | Blt { imm, .. }
+        | Bge { imm, .. }
+        | Bltu { imm, .. }
+        | Bgeu { imm, .. } => Some((pc.wrapping_add(imm as u32), Branch)),
+
+        Jal { imm, .. } => {
+            let kind = match instr.control_flow() {
+                ControlFlow::Call => Call,
+                _ => Jump,
+            };
+            Some((pc.wrapping_add(imm as u32), kind))
+        }
+        Jalr { rs1, imm, .. } => {
+            let kind = match instr.control_flow() {
+                ControlFlow::Call => Call,
+                // We can't know where a `ret` returns to
+                ControlFlow::Return => return None,
+                _ => Jump,
+            };
+            let target = known.get(rs1)?.wrapping_add(imm as u32) & !1;
+            Some((target, kind))
+        }
+
+        Addi { rs1, imm, .. } => based(rs1, imm).map(|addr| (addr, Address)),
+
+        instr => {
+            let access = instr.mem_access()?;
+            let kind = match access.op {
+                MemOp::Load => Load,
+                MemOp::Store => Store,
+            };
+            based(access.base, access.offset).map(|addr| (addr, kind))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    #[test]
+    fn check_xrefs() {
+        #[rustfmt::skip]
+        let dis = Disassembly::from_words(0x8000_0000, &[
+            // _start:
+            0x0000_0517, // auipc   a0, 0           ┐ la a0, data
+            0x02c5_0513, // addi    a0, a0, 44      ┘
+            0x0000_0597, // auipc   a1, 0           ┐ lw a1, data
+            0x0245_a583, // lw      a1, 36(a1)      ┘
+            0x0000_0097, // auipc   ra, 0           ┐ call foo
+            0x0100_80e7, // jalr    16(ra)          ┘
+            0xfe05_84e3, // beqz    a1, _start
+            0xfe5f_f06f, // j       _start
+            // foo:
+            0x0000_0297, // auipc   t0, 0           ┐ sw a1, data, t0
+            0x00b2_a623, // sw      a1, 12(t0)      ┘
+            0x0000_8067, // ret
+            // data:
+            0x0000_0000, // .word   0
+        ]);
+
+        let xref = |from: u32, kind: XrefKind, to: u32| Xref {
+            from: 0x8000_0000 + from,
+            to: 0x8000_0000 + to,
+            kind,
+        };
+
+        assert_eq!(
+            dis.xrefs(0x8000_0000),
+            vec![
+                xref(0x18, XrefKind::Branch, 0x00),
+                xref(0x1c, XrefKind::Jump, 0x00)
+            ]
+        );
+        assert_eq!(
+            dis.xrefs(0x8000_0020),
+            vec![xref(0x14, XrefKind::Call, 0x20)]
+        );
+        assert_eq!(
+            dis.xrefs(0x8000_002c),
+            vec![
+                xref(0x04, XrefKind::Address, 0x2c),
+                xref(0x0c, XrefKind::Load, 0x2c),
+                xref(0x24, XrefKind::Store, 0x2c),
+            ]
+        );
+        assert_eq!(dis.xrefs(0x8000_0004), vec![]);
+    }
+}